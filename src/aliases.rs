@@ -0,0 +1,48 @@
+// `--alias`: names to report contigs under, such as UCSC names for a RefSeq assembly or
+// contig names without their PanSN prefix. The table is tab-separated, the FASTA name then
+// the output name, with `#` comment lines. Files that name contigs may use either name.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Aliases {
+    outputs: HashMap<String, String>,
+    fasta_names: HashMap<String, String>,
+}
+
+impl Aliases {
+    pub fn from_path(path: &str) -> Result<Aliases, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut aliases = Aliases::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.trim_end_matches('\r').split('\t').collect();
+            let [fasta_name, output] = fields[..] else {
+                return Err(format!("{}: line {}: expected a FASTA name and an output name separated by a tab", path, i + 1));
+            };
+            if aliases.outputs.insert(fasta_name.to_string(), output.to_string()).is_some() {
+                return Err(format!("{}: line {}: {} is aliased twice", path, i + 1, fasta_name));
+            }
+            // two contigs under one output name could no longer be told apart
+            if let Some(other) = aliases.fasta_names.insert(output.to_string(), fasta_name.to_string()) {
+                return Err(format!("{}: line {}: {} is the output name of both {} and {}", path, i + 1, output, other, fasta_name));
+            }
+        }
+        Ok(aliases)
+    }
+
+    // The name `fasta_name` is reported under: its alias, or itself.
+    pub fn output<'a>(&'a self, fasta_name: &'a str) -> &'a str {
+        self.outputs.get(fasta_name).map_or(fasta_name, String::as_str)
+    }
+
+    // The FASTA name of a contig given by its output name, or `name` itself.
+    pub fn fasta_name<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.fasta_names.get(name) {
+            Some(fasta_name) if !self.outputs.contains_key(name) => fasta_name,
+            _ => name,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// `--autotune`: times a search on a sample of the reference with a few thread counts, chunk
+// sizes and backends, and picks the fastest. What pays off depends on the machine: fetching
+// whole contigs suits local NVMe, while on network storage smaller chunks or fewer threads
+// can keep reads from stalling the scan.
+
+use crate::search::{Query, SearchConfig, Searcher};
+use crate::Backend;
+use std::time::{Duration, Instant};
+
+// Pieces the sample is made of, spread evenly over the searched contigs.
+const SAMPLE_PIECE_LENGTH: usize = 8 << 20;
+const MAX_SAMPLE_PIECES: usize = 32;
+
+const CHUNK_SIZES: [Option<usize>; 3] = [None, Some(4 << 20), Some(1 << 20)];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+    pub threads: usize,
+    pub chunk_size: Option<usize>,
+    pub backend: Backend,
+}
+
+// One timed search of the sample.
+pub struct Trial {
+    pub tuning: Tuning,
+    pub seconds: f64,
+    pub bases: usize,
+}
+
+// `pieces` ranges of at most SAMPLE_PIECE_LENGTH bases at evenly spaced offsets of the
+// contigs laid end to end, or the contigs themselves when they are no longer than that.
+fn sample(contigs: &[(String, usize)], pieces: usize) -> Vec<(String, usize, usize)> {
+    let total: usize = contigs.iter().map(|(_, length)| length).sum();
+    if total <= pieces * SAMPLE_PIECE_LENGTH {
+        return contigs.iter().filter(|(_, length)| *length > 0).map(|(seq_name, length)| (seq_name.clone(), 0, *length)).collect();
+    }
+    let mut sample: Vec<(String, usize, usize)> = Vec::new();
+    let (mut contig, mut contig_start) = (0, 0);
+    for i in 0..pieces {
+        let offset = i * (total / pieces);
+        while contig_start + contigs[contig].1 <= offset {
+            contig_start += contigs[contig].1;
+            contig += 1;
+        }
+        let (seq_name, length) = &contigs[contig];
+        let start = offset - contig_start;
+        // a piece stops at the end of its contig, and so does not overlap the next one
+        sample.push((seq_name.clone(), start, (start + SAMPLE_PIECE_LENGTH).min(*length)));
+    }
+    sample
+}
+
+// Times `queries` on a sample of `fasta` under a configuration from `config` with each
+// candidate tuning, one setting at a time: the thread count (up to `max_threads`), then the
+// chunk size, then the backend. No trial is started once `budget` has passed. Returns the
+// fastest tuning and every trial, in the order they ran.
+pub fn autotune(fasta: &str, config: impl Fn() -> SearchConfig, queries: &[Query], max_threads: usize, budget: Duration) -> Result<(Tuning, Vec<Trial>), String> {
+    let started = Instant::now();
+    let searcher = Searcher::new(fasta, config())?;
+    let sample = sample(searcher.contigs(), (2 * max_threads).clamp(1, MAX_SAMPLE_PIECES));
+    let bases = sample.iter().map(|(_, start, end)| end - start).sum();
+    // a chunk no shorter than every piece changes nothing
+    let longest_piece = sample.iter().map(|(_, start, end)| end - start).max().unwrap_or(0);
+    let mut trials: Vec<Trial> = Vec::new();
+    let run = |tuning: Tuning, trials: &mut Vec<Trial>| -> Result<(), String> {
+        let searcher = Searcher::new(fasta, SearchConfig { chunk_size: tuning.chunk_size, backend: tuning.backend, ..config() })?;
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(tuning.threads).build().map_err(|e| e.to_string())?;
+        let trial_started = Instant::now();
+        pool.install(|| searcher.scan_sample(queries, &sample))?;
+        trials.push(Trial { tuning, seconds: trial_started.elapsed().as_secs_f64(), bases });
+        Ok(())
+    };
+    let fastest = |trials: &[Trial]| trials.iter().min_by(|a, b| a.seconds.total_cmp(&b.seconds)).unwrap().tuning;
+    let mut best = Tuning { threads: max_threads, chunk_size: None, backend: Backend::Auto };
+    // untimed, so the first trial does not pay for reading the sample into the page cache
+    run(best, &mut Vec::new())?;
+    let mut threads = vec![max_threads, max_threads / 2, max_threads / 4];
+    threads.retain(|&n| n > 0);
+    threads.dedup();
+    // the backend only matters when a query is seeded, or could be with shorter pieces
+    let seeded = queries.iter().any(|query| query.is_seeded(&config()));
+    let pigeonhole = queries.iter().any(|query| !query.is_seeded(&config()) && query.is_seeded(&SearchConfig { backend: Backend::Pigeonhole, ..config() }));
+    for axis in 0..3 {
+        // each setting is varied around the best of the ones before it
+        let candidates: Vec<Tuning> = match axis {
+            0 => threads.iter().map(|&threads| Tuning { threads, ..best }).collect(),
+            1 => CHUNK_SIZES.iter().filter(|chunk_size| chunk_size.is_none_or(|size| size < longest_piece)).map(|&chunk_size| Tuning { chunk_size, ..best }).collect(),
+            _ => [(seeded, Backend::Windows), (pigeonhole, Backend::Pigeonhole)].into_iter().filter(|&(tried, _)| tried).map(|(_, backend)| Tuning { backend, ..best }).collect(),
+        };
+        for candidate in candidates {
+            if trials.iter().any(|trial| trial.tuning == candidate) {
+                continue;
+            }
+            if !trials.is_empty() && started.elapsed() > budget {
+                return Ok((fastest(&trials), trials));
+            }
+            run(candidate, &mut trials)?;
+        }
+        best = fastest(&trials);
+    }
+    Ok((best, trials))
+}
@@ -0,0 +1,66 @@
+// Base-editor windows: which protospacer bases a cytosine or adenine base editor can convert.
+
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseEditor {
+    // adenine base editor, A>G
+    Abe,
+    // cytosine base editor, C>T
+    Cbe,
+}
+
+impl BaseEditor {
+    pub fn substrate(&self) -> u8 {
+        match self {
+            BaseEditor::Abe => b'A',
+            BaseEditor::Cbe => b'C',
+        }
+    }
+
+    pub fn product(&self) -> u8 {
+        match self {
+            BaseEditor::Abe => b'G',
+            BaseEditor::Cbe => b'T',
+        }
+    }
+}
+
+impl FromStr for BaseEditor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BaseEditor, String> {
+        match s.to_ascii_uppercase().as_str() {
+            "ABE" => Ok(BaseEditor::Abe),
+            "CBE" => Ok(BaseEditor::Cbe),
+            _ => Err(format!("unknown base editor {:?}, expected ABE or CBE", s)),
+        }
+    }
+}
+
+// 1-based protospacer positions counted from the PAM-distal end, inclusive.
+#[derive(Clone, Copy, Debug)]
+pub struct EditWindow {
+    pub first: usize,
+    pub last: usize,
+}
+
+impl FromStr for EditWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EditWindow, String> {
+        let (first, last) = s.split_once('-').ok_or_else(|| format!("edit window {:?} is not of the form FIRST-LAST", s))?;
+        let first: usize = first.trim().parse().map_err(|_| format!("bad edit window start {:?}", first))?;
+        let last: usize = last.trim().parse().map_err(|_| format!("bad edit window end {:?}", last))?;
+        if first == 0 || first > last {
+            return Err(format!("edit window {:?} must satisfy 1 <= FIRST <= LAST", s));
+        }
+        Ok(EditWindow { first, last })
+    }
+}
+
+// Positions of the editor's substrate base within the window of `protospacer`.
+pub fn editable_positions(editor: BaseEditor, window: EditWindow, protospacer: &str) -> Vec<usize> {
+    let bases = protospacer.as_bytes();
+    (window.first..=window.last.min(bases.len())).filter(|&pos| bases[pos - 1].to_ascii_uppercase() == editor.substrate()).collect()
+}
@@ -0,0 +1,145 @@
+// `bench`: plant hits at known distances into a synthetic sequence and time the scan.
+
+use hamming_fasta::get_reverse_complement;
+use hamming_fasta::rng::Rng;
+use hamming_fasta::search::{scan_windows, ScanParams};
+use hamming_fasta::{seed, sliced};
+use std::time::Instant;
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// Length of the synthetic sequence in bases
+    #[arg(long, default_value_t = 10_000_000)]
+    length: usize,
+
+    /// GC content of the synthetic sequence (percent)
+    #[arg(long, default_value_t = 41.0)]
+    gc: f64,
+
+    /// Length of the random query
+    #[arg(long, default_value_t = 20)]
+    query_length: usize,
+
+    /// Mismatch budget of the search; planted hits use 0 up to this many mismatches
+    #[arg(short, long, default_value_t = 3)]
+    distance: usize,
+
+    /// Number of hits to plant, half of them on the minus strand
+    #[arg(long, default_value_t = 1000)]
+    planted: usize,
+
+    /// Seed of the sequence generator
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+}
+
+struct Planted {
+    start: usize,
+    strand: &'static str,
+    mismatches: usize,
+}
+
+pub fn run(args: &BenchArgs) {
+    if args.query_length == 0 || args.query_length * 2 > args.length {
+        eprintln!("error: --query-length must be positive and at most half of --length");
+        std::process::exit(crate::EXIT_USAGE);
+    }
+    let mut rng = Rng::new(args.seed);
+    let gc = args.gc / 100.0;
+    let mut sequence: Vec<u8> = (0..args.length).map(|_| rng.base(gc)).collect();
+    let query: String = (0..args.query_length).map(|_| rng.base(gc) as char).collect();
+
+    // plant non-overlapping copies of the query carrying an exact number of substitutions
+    let slots = args.length / args.query_length;
+    let mut used = vec![false; slots];
+    let mut planted = Vec::new();
+    for i in 0..args.planted.min(slots / 2) {
+        let slot = loop {
+            let slot = rng.below(slots);
+            if !used[slot] {
+                used[slot] = true;
+                break slot;
+            }
+        };
+        let mismatches = i % (args.distance.min(args.query_length) + 1);
+        let mut site: Vec<u8> = query.bytes().collect();
+        let mut positions: Vec<usize> = (0..site.len()).collect();
+        for k in 0..mismatches {
+            let j = k + rng.below(positions.len() - k);
+            positions.swap(k, j);
+            let p = positions[k];
+            site[p] = loop {
+                let b = rng.base(0.5);
+                if b != site[p] {
+                    break b;
+                }
+            };
+        }
+        let strand = if i % 2 == 0 { "+" } else { "-" };
+        if strand == "-" {
+            site = get_reverse_complement(std::str::from_utf8(&site).unwrap()).into_bytes();
+        }
+        let start = slot * args.query_length;
+        sequence[start..start + site.len()].copy_from_slice(&site);
+        planted.push(Planted { start, strand, mismatches });
+    }
+
+    let sequence = String::from_utf8(sequence).unwrap();
+    let params = ScanParams { target: &query, pams: &[], pam_5prime: false, ignored: &[], degenerate: false, max_mismatches: args.distance, tm: None, cancel: None, budget: None, seed_length: None, step: 1, phase: 0 };
+
+    println!("backend\tbases\tseconds\tmbases_per_second\tplanted\trecovered\treported\tstatus");
+    // a row for each backend the search knows, with the ones that cannot run here skipped
+    let skipped = |backend: &str, why: &str| println!("{}\t{}\t.\t.\t{}\t.\t.\tskipped: {}", backend, args.length, planted.len(), why);
+    let mut missed = false;
+    type Scanner = fn(&str, &ScanParams, &[(usize, usize)], &mut dyn FnMut(usize, &str, usize, Option<f64>));
+    let backends: [(&str, Scanner); 3] = [
+        ("naive", |seq, params, skip, report| scan_windows(seq, params, skip, report)),
+        ("pigeonhole", |seq, params, skip, report| seed::scan_seeded(seq, params, skip, report)),
+        ("simd", |seq, params, skip, report| sliced::scan_sliced(seq, std::slice::from_ref(params), skip, |_, idx, window, distance, tm| report(idx, window, distance, tm))),
+    ];
+    for (backend, scan) in backends {
+        if backend == "pigeonhole" && !seed::possible(&params) {
+            skipped(backend, "the query cannot be seeded");
+            continue;
+        }
+        if backend == "simd" && !sliced::possible(&params) {
+            skipped(backend, "the query is longer than 32 bases");
+            continue;
+        }
+        let started = Instant::now();
+        let rev_sequence = get_reverse_complement(&sequence);
+        let mut found = std::collections::HashMap::new();
+        let mut reported = 0;
+        for (seq, strand) in [(&sequence, "+"), (&rev_sequence, "-")] {
+            scan(seq, &params, &[], &mut |idx, window, distance, _| {
+                let start = if strand == "-" { args.length - (idx + window.len()) } else { idx };
+                found.insert((start, strand), distance);
+                reported += 1;
+            });
+        }
+        let seconds = started.elapsed().as_secs_f64();
+
+        let recovered = planted.iter().filter(|p| found.get(&(p.start, p.strand)) == Some(&p.mismatches)).count();
+        println!(
+            "{}\t{}\t{:.3}\t{:.2}\t{}\t{}\t{}\t{}",
+            backend,
+            args.length,
+            seconds,
+            args.length as f64 / seconds / 1e6,
+            planted.len(),
+            recovered,
+            reported,
+            if recovered < planted.len() { "missed" } else { "ok" }
+        );
+        if recovered < planted.len() {
+            eprintln!("error: {} planted hits were not reported with their planted distance by {}", planted.len() - recovered, backend);
+            missed = true;
+        }
+    }
+    for backend in ["bitap", "fm-index"] {
+        skipped(backend, "not available in this build");
+    }
+    if missed {
+        std::process::exit(1);
+    }
+}
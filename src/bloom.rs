@@ -0,0 +1,105 @@
+// Per-contig Bloom filters of canonical reference k-mers, built by `index --bloom`. A window
+// within k mismatches of a query contains one of k + 1 disjoint query pieces exactly, so a
+// contig lacking a k-mer of every piece cannot hold a hit and is not scanned.
+
+use crate::search::{Pieces, Prefilter, Query};
+use crate::sketch::{self, canonical_kmers, mix, MAX_K};
+use std::collections::HashMap;
+
+const MAGIC: &[u8; 8] = b"HFBLOOM2";
+
+struct Bloom {
+    words: Vec<u64>,
+}
+
+impl Bloom {
+    fn positions(&self, kmer: u64, hashes: usize) -> impl Iterator<Item = usize> {
+        let bits = self.words.len() as u64 * 64;
+        let (h1, h2) = (mix(kmer), mix(kmer ^ 0x9e3779b97f4a7c15) | 1);
+        (0..hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    fn insert(&mut self, kmer: u64, hashes: usize) {
+        for bit in self.positions(kmer, hashes).collect::<Vec<usize>>() {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, kmer: u64, hashes: usize) -> bool {
+        self.positions(kmer, hashes).all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+pub struct KmerFilter {
+    k: usize,
+    hashes: usize,
+    // per contig: indexed length, MD5 and filter
+    contigs: HashMap<String, (usize, [u8; 16], Bloom)>,
+}
+
+pub fn filter_path(fasta: &str) -> String {
+    format!("{}.bloom", fasta)
+}
+
+fn filter(seq: &[u8], k: usize, hashes: usize, bits_per_base: f64) -> Vec<u64> {
+    let mut bloom = Bloom { words: vec![0; ((seq.len() as f64 * (bits_per_base / 64.0)).ceil() as usize).max(1)] };
+    canonical_kmers(seq, k, |_, kmer| bloom.insert(kmer, hashes));
+    bloom.words
+}
+
+// Builds filters of `k`-mers with `bits_per_base` filter bits per contig base and writes them
+// next to the FASTA.
+pub fn build(fasta: &str, contigs: &[(String, usize)], k: usize, bits_per_base: f64) -> Result<(), String> {
+    if k == 0 || k > MAX_K {
+        return Err(format!("k-mer length must be between 1 and {}", MAX_K));
+    }
+    // the number of hash functions minimizing false positives at this density
+    let hashes = ((bits_per_base * std::f64::consts::LN_2).round() as usize).clamp(1, 16);
+    sketch::build(fasta, &filter_path(fasta), MAGIC, [k as u32, hashes as u32], contigs, |seq| filter(seq, k, hashes, bits_per_base))
+}
+
+// Adds filters of the `contigs` of `added` to those of `fasta`, with the k and hash count they
+// were built with.
+pub fn add(fasta: &str, added: &str, contigs: &[(String, usize)], bits_per_base: f64) -> Result<(), String> {
+    let path = filter_path(fasta);
+    let [k, hashes] = sketch::params(&path, MAGIC, "index --bloom")?;
+    sketch::append(added, &path, contigs, |seq| filter(seq, k as usize, hashes as usize, bits_per_base))
+}
+
+impl KmerFilter {
+    pub fn load(fasta: &str) -> Result<KmerFilter, String> {
+        let ([k, hashes], contigs) = sketch::load(&filter_path(fasta), MAGIC, "index --bloom")?;
+        let contigs = contigs.into_iter().map(|(seq_name, (length, md5, words))| (seq_name, (length, md5, Bloom { words }))).collect();
+        Ok(KmerFilter { k: k as usize, hashes: hashes as usize, contigs })
+    }
+}
+
+impl Prefilter for KmerFilter {
+    fn length(&self, seq_name: &str) -> Option<usize> {
+        self.contigs.get(seq_name).map(|(length, _, _)| *length)
+    }
+
+    fn md5(&self, seq_name: &str) -> Option<[u8; 16]> {
+        self.contigs.get(seq_name).map(|(_, md5, _)| *md5)
+    }
+
+    // The canonical k-mers of each pigeonhole piece; pieces must be at least k long.
+    fn pieces(&self, query: &Query) -> Option<Pieces> {
+        let pieces = sketch::pigeonhole_pieces(query, self.k)?
+            .into_iter()
+            .map(|piece| {
+                let mut kmers = Vec::new();
+                canonical_kmers(piece, self.k, |_, kmer| kmers.push(kmer));
+                kmers
+            })
+            .collect();
+        Some(pieces)
+    }
+
+    fn may_contain(&self, seq_name: &str, pieces: &[Vec<u64>]) -> bool {
+        match self.contigs.get(seq_name) {
+            Some((_, _, bloom)) => pieces.iter().any(|kmers| kmers.iter().all(|&kmer| bloom.contains(kmer, self.hashes))),
+            None => true,
+        }
+    }
+}
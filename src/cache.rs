@@ -0,0 +1,319 @@
+// On-disk cache of finished per-query results. An entry is keyed by the query, every search
+// parameter that changes its hits, and a checksum of the FASTA, so an edited reference or a
+// different setting simply misses.
+
+use crate::search::{Hit, Query, SearchConfig, Strand};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+// FNV-1a, stable across builds unlike the std hasher.
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub struct ResultCache {
+    dir: PathBuf,
+    reference: u64,
+    gaps: u64,
+    regions: u64,
+    aliases: u64,
+}
+
+impl ResultCache {
+    pub fn open(dir: &str, fasta: &str, config: &SearchConfig) -> Result<ResultCache, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        let dir = PathBuf::from(dir);
+        let reference = file_checksum(&dir, fasta)?;
+        let gaps = match &config.gaps {
+            Some(path) => file_checksum(&dir, path)?,
+            None => 0,
+        };
+        let regions = match &config.regions {
+            Some(path) => file_checksum(&dir, path)?,
+            None => 0,
+        };
+        // which gaps and regions a contig gets when those files use its alias
+        let aliases = match &config.aliases {
+            Some(path) => file_checksum(&dir, path)?,
+            None => 0,
+        };
+        Ok(ResultCache { dir, reference, gaps, regions, aliases })
+    }
+
+    pub fn key(&self, query: &Query, config: &SearchConfig) -> String {
+        let fields = format!(
+            "{}\t{}\t{:?}\t{}\t{:?}\t{}\t{:?}\t{:?}\t{}\t{}\t{}\t{}\t{:?}\t{:?}\t{:?}\t{:?}\t{:?}\t{}\t{}\t{}\t{}\t{:?}\t{}\t{}",
+            query.sequence,
+            query.max_mismatches,
+            query.strand_max_mismatches,
+            query.cas9,
+            query.pams(),
+            query.pam_5prime,
+            query.ignored,
+            query.min_tm,
+            query.degenerate,
+            config.tm_model.strand_conc,
+            config.tm_model.sodium,
+            config.prefix,
+            config.min_contig_length,
+            config.max_contig_length,
+            config.skip_n_runs,
+            config.seed_length,
+            config.step,
+            self.gaps,
+            self.regions,
+            self.aliases,
+            // which contigs an entry lists
+            config.skip_duplicate_contigs,
+            config.on_duplicate_names,
+            config.alphabet,
+            self.reference
+        );
+        format!("{:016x}", fnv1a(FNV_OFFSET, fields.as_bytes()))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.tsv", key))
+    }
+
+    // Hits per scanned contig, with `query` as their query index.
+    pub fn load(&self, key: &str, query: usize) -> Option<HashMap<String, Vec<Hit>>> {
+        let file = File::open(self.path(key)).ok()?;
+        let mut hits: HashMap<String, Vec<Hit>> = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.ok()?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            // a contig without hits is listed alone, so the entry still names every contig
+            if fields.len() == 1 {
+                hits.entry(fields[0].to_string()).or_default();
+                continue;
+            }
+            if fields.len() != 7 {
+                return None;
+            }
+            let strand = match fields[1] {
+                "+" => Strand::Forward,
+                "-" => Strand::Reverse,
+                _ => return None,
+            };
+            let hit = Hit {
+                query,
+                seq_name: Arc::from(fields[0]),
+                strand,
+                start: fields[2].parse().ok()?,
+                end: fields[3].parse().ok()?,
+                sequence: fields[4].to_string(),
+                mismatches: fields[5].parse().ok()?,
+                tm: if fields[6] == "." { None } else { Some(fields[6].parse().ok()?) },
+            };
+            hits.entry(fields[0].to_string()).or_default().push(hit);
+        }
+        Some(hits)
+    }
+
+    // Written to a temporary file first so a concurrent or interrupted run never reads half an entry.
+    pub fn store(&self, key: &str, hits: &HashMap<String, Vec<Hit>>) -> Result<(), String> {
+        let mut out = Vec::new();
+        for (seq_name, hits) in hits {
+            if hits.is_empty() {
+                writeln!(out, "{}", seq_name).unwrap();
+            }
+            for hit in hits {
+                let tm = hit.tm.map_or_else(|| ".".to_string(), |tm| tm.to_string());
+                writeln!(out, "{}\t{}\t{}\t{}\t{}\t{}\t{}", seq_name, hit.strand, hit.start, hit.end, hit.sequence, hit.mismatches, tm).unwrap();
+            }
+        }
+        let path = self.path(key);
+        let partial = path.with_extension(format!("tsv.{}", std::process::id()));
+        fs::write(&partial, out).map_err(|e| format!("{}: {}", partial.display(), e))?;
+        fs::rename(&partial, &path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+// Checksum of a file's contents, remembered in the cache directory by path, size and
+// modification time so a multi-gigabyte FASTA is only read again after it changes.
+fn file_checksum(dir: &Path, path: &str) -> Result<u64, String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("{}: {}", path, e))?;
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).map_or(0, |t| t.as_nanos());
+    let canonical = fs::canonicalize(path).map_err(|e| format!("{}: {}", path, e))?;
+    let stamp = format!("{}\t{}\t{}", canonical.display(), metadata.len(), modified);
+    let memo = dir.join(format!("checksum.{:016x}", fnv1a(FNV_OFFSET, stamp.as_bytes())));
+    if let Some(checksum) = fs::read_to_string(&memo).ok().and_then(|s| u64::from_str_radix(s.trim(), 16).ok()) {
+        return Ok(checksum);
+    }
+    let checksum = checksum(path)?;
+    fs::write(&memo, format!("{:016x}\n", checksum)).map_err(|e| format!("{}: {}", memo.display(), e))?;
+    Ok(checksum)
+}
+
+// FNV-1a of a file's contents.
+fn checksum(path: &str) -> Result<u64, String> {
+    let mut file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut checksum = FNV_OFFSET;
+    loop {
+        let n = file.read(&mut buffer).map_err(|e| format!("{}: {}", path, e))?;
+        if n == 0 {
+            break;
+        }
+        checksum = fnv1a(checksum, &buffer[..n]);
+    }
+    Ok(checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A directory of its own for each test, removed when the test ends.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = std::env::temp_dir().join(format!("hamming-fasta-cache-{}-{}", std::process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        fn file(&self, name: &str, text: &str) -> String {
+            let path = self.0.join(name);
+            fs::write(&path, text).unwrap();
+            path.display().to_string()
+        }
+
+        fn cache(&self, fasta: &str, config: &SearchConfig) -> ResultCache {
+            ResultCache::open(self.0.join("cache").to_str().unwrap(), fasta, config).unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn query() -> Query {
+        Query::new("ACGTACGTACGTACGTACGT", 3)
+    }
+
+    type Change<'a> = Box<dyn Fn(&mut Query, &mut SearchConfig) + 'a>;
+
+    #[test]
+    fn every_search_field_changes_the_key() {
+        let scratch = Scratch::new("fields");
+        let fasta = scratch.file("ref.fa", ">c\nACGT\n");
+        let key = |change: &dyn Fn(&mut Query, &mut SearchConfig)| {
+            let (mut query, mut config) = (query(), SearchConfig::default());
+            change(&mut query, &mut config);
+            scratch.cache(&fasta, &config).key(&query, &config)
+        };
+        let changes: Vec<(&str, Change)> = vec![
+            ("sequence", Box::new(|q, _| q.sequence = "ACGTACGTACGTACGTACGA".to_string())),
+            ("max_mismatches", Box::new(|q, _| q.max_mismatches = 2)),
+            ("strand_max_mismatches", Box::new(|q, _| q.strand_max_mismatches = Some([3, 1]))),
+            ("cas9", Box::new(|q, _| q.cas9 = true)),
+            ("pam", Box::new(|q, _| (q.cas9, q.pam) = (true, "NAG".to_string()))),
+            ("alt_pams", Box::new(|q, _| (q.cas9, q.alt_pams) = (true, vec!["NAG".to_string()]))),
+            ("pam_5prime", Box::new(|q, _| q.pam_5prime = true)),
+            ("ignored", Box::new(|q, _| q.ignored = vec![0])),
+            ("min_tm", Box::new(|q, _| q.min_tm = Some(50.0))),
+            ("degenerate", Box::new(|q, _| q.degenerate = true)),
+            ("strand_conc", Box::new(|_, c| c.tm_model.strand_conc *= 2.0)),
+            ("sodium", Box::new(|_, c| c.tm_model.sodium *= 2.0)),
+            ("prefix", Box::new(|_, c| c.prefix = "HG002".to_string())),
+            ("min_contig_length", Box::new(|_, c| c.min_contig_length = Some(10))),
+            ("max_contig_length", Box::new(|_, c| c.max_contig_length = Some(10))),
+            ("skip_n_runs", Box::new(|_, c| c.skip_n_runs = Some(5))),
+            ("seed_length", Box::new(|_, c| c.seed_length = Some(8))),
+            ("step", Box::new(|_, c| c.step = Some(2))),
+            ("skip_duplicate_contigs", Box::new(|_, c| c.skip_duplicate_contigs = true)),
+            ("on_duplicate_names", Box::new(|_, c| c.on_duplicate_names = crate::DuplicateNames::Suffix)),
+            ("alphabet", Box::new(|_, c| c.alphabet = crate::Alphabet::Protein)),
+            ("gaps", Box::new(|_, c| c.gaps = Some(scratch.file("gaps.bed", "c\t0\t2\n")))),
+            ("regions", Box::new(|_, c| c.regions = Some(scratch.file("regions.bed.gz", "c\t0\t4\n")))),
+            ("aliases", Box::new(|_, c| c.aliases = Some(scratch.file("aliases.tsv", "c\tchr1\n")))),
+        ];
+        let base = key(&|_, _| {});
+        let mut keys = HashMap::from([(base.clone(), "none")]);
+        for (field, change) in &changes {
+            let changed = key(change.as_ref());
+            assert_ne!(changed, base, "{}", field);
+            assert_eq!(keys.insert(changed, field), None, "{} collides", field);
+        }
+        // how a search is run does not change its hits, and so not the key either
+        assert_eq!(key(&|_, c| (c.chunk_size, c.block_size, c.backend) = (Some(100), Some(10), crate::Backend::Simd)), base);
+        assert_eq!(key(&|_, c| (c.no_mmap, c.fetch_retries, c.cache_bytes) = (true, 3, Some(1 << 20))), base);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let scratch = Scratch::new("entries");
+        let fasta = scratch.file("ref.fa", ">c\nACGT\n");
+        let config = SearchConfig::default();
+        let cache = scratch.cache(&fasta, &config);
+        let key = cache.key(&query(), &config);
+        assert!(cache.load(&key, 0).is_none());
+        let hit = |seq_name: &str, strand, start: usize, mismatches, tm| Hit { query: 0, seq_name: Arc::from(seq_name), strand, start, end: start + 20, sequence: "ACGTACGTACGTACGTACGT".to_string(), mismatches, tm };
+        let names = ["HG002#1#chr1", "chrUn_KI270302v1|x:1-100", "contig=\"1\";é", "no_hits"];
+        let mut stored: HashMap<String, Vec<Hit>> = HashMap::new();
+        stored.insert(names[0].to_string(), vec![hit(names[0], Strand::Forward, 5, 0, None), hit(names[0], Strand::Reverse, 40, 3, None)]);
+        stored.insert(names[1].to_string(), vec![hit(names[1], Strand::Reverse, 0, 1, Some(61.25))]);
+        stored.insert(names[2].to_string(), vec![hit(names[2], Strand::Forward, 1 << 40, 2, Some(-3.5))]);
+        stored.insert(names[3].to_string(), Vec::new());
+        cache.store(&key, &stored).unwrap();
+        // as another query of a batch
+        let loaded = cache.load(&key, 7).unwrap();
+        let fields = |hits: &HashMap<String, Vec<Hit>>, query: usize| {
+            let mut fields: Vec<_> = hits.iter().flat_map(|(name, hits)| hits.iter().map(move |h| (name.clone(), query, h.seq_name.to_string(), h.strand, h.start, h.end, h.sequence.clone(), h.mismatches, h.tm.map(f64::to_bits)))).collect();
+            fields.sort();
+            fields
+        };
+        assert_eq!(fields(&loaded, 0).len(), 4);
+        assert_eq!(fields(&loaded, 7), fields(&stored, 7));
+        let mut contigs: Vec<&String> = loaded.keys().collect();
+        contigs.sort();
+        assert_eq!(contigs, [names[0], names[1], names[2], names[3]]);
+        assert!(loaded.values().all(|hits| hits.iter().all(|hit| hit.query == 7)));
+        // a damaged entry misses rather than returning part of it
+        fs::write(cache.path(&key), "c\t+\t1\n").unwrap();
+        assert!(cache.load(&key, 0).is_none());
+    }
+
+    #[test]
+    fn a_changed_fasta_changes_the_key() {
+        let scratch = Scratch::new("fasta");
+        let fasta = scratch.file("ref.fa", ">c\nACGTACGT\n");
+        let config = SearchConfig::default();
+        let key = || scratch.cache(&fasta, &config).key(&query(), &config);
+        let set_modified = |secs: u64| File::options().write(true).open(&fasta).unwrap().set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)).unwrap();
+        set_modified(1_000_000);
+        let original = key();
+        assert_eq!(key(), original);
+        // the checksum is remembered by path, size and modification time, so an edit that keeps
+        // all three is not read
+        scratch.file("ref.fa", ">c\nACGTACGA\n");
+        set_modified(1_000_000);
+        assert_eq!(key(), original);
+        // a new modification time reads the file again
+        set_modified(2_000_000);
+        let edited = key();
+        assert_ne!(edited, original);
+        // as does a new size, even at the same time
+        scratch.file("ref.fa", ">c\nACGTACGAA\n");
+        set_modified(2_000_000);
+        assert_ne!(key(), edited);
+        // restoring the contents restores the key
+        scratch.file("ref.fa", ">c\nACGTACGT\n");
+        set_modified(3_000_000);
+        assert_eq!(key(), original);
+    }
+}
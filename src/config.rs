@@ -0,0 +1,375 @@
+// `--config`: search options and queries from a TOML or YAML file, for runs with too many
+// settings to give reliably on a command line. Options become command line flags placed before
+// the given ones, and a flag given on the command line replaces the file's setting.
+//
+// Only the flat subset the options need is read: `key = value` lines and `[[queries]]` tables
+// in TOML, and `key: value` lines, block lists and a `queries:` list of mappings in YAML. Values
+// are plain or quoted scalars and one-line lists. Anything else, such as inline tables, flow
+// mappings, multi-line strings, anchors or a second YAML document, is an error rather than
+// being read some other way.
+
+use std::fs;
+
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+pub struct ConfigQuery {
+    pub name: String,
+    pub sequence: String,
+    // each overrides the run's setting for this query
+    pub distance: Option<usize>,
+    pub cas9: Option<bool>,
+    pub pam: Option<String>,
+}
+
+pub struct RunConfig {
+    pub options: Vec<(String, Value)>,
+    pub queries: Vec<ConfigQuery>,
+}
+
+type Table = Vec<(String, Value)>;
+
+pub fn load(path: &str) -> Result<RunConfig, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse(path, &text)
+}
+
+// The config in `text`, read as YAML or TOML by the extension of `path`.
+fn parse(path: &str, text: &str) -> Result<RunConfig, String> {
+    let parsed = if path.ends_with(".yaml") || path.ends_with(".yml") { parse_yaml(text) } else { parse_toml(text) };
+    let (options, tables) = parsed.map_err(|e| format!("{}: {}", path, e))?;
+    let queries = tables.into_iter().enumerate().map(|(i, table)| query(table, i + 1).map_err(|e| format!("{}: {}", path, e))).collect::<Result<_, _>>()?;
+    Ok(RunConfig { options, queries })
+}
+
+fn query(table: Table, n: usize) -> Result<ConfigQuery, String> {
+    let (mut name, mut sequence, mut distance, mut cas9, mut pam) = (None, None, None, None, None);
+    for (key, value) in table {
+        match (key.as_str(), value) {
+            ("name", Value::Scalar(s)) => name = Some(s),
+            ("sequence", Value::Scalar(s)) => sequence = Some(s),
+            ("distance", Value::Scalar(s)) => distance = Some(s.parse().map_err(|_| format!("query {}: distance must be a number, not {}", n, s))?),
+            ("cas9", Value::Bool(b)) => cas9 = Some(b),
+            ("pam", Value::Scalar(s)) => pam = Some(s),
+            (key @ ("name" | "sequence" | "distance" | "cas9" | "pam"), _) => return Err(format!("query {}: bad value of {}", n, key)),
+            (key, _) => return Err(format!("query {}: unknown key {}, expected name, sequence, distance, cas9 or pam", n, key)),
+        }
+    }
+    let sequence = sequence.ok_or_else(|| format!("query {} has no sequence", n))?;
+    Ok(ConfigQuery { name: name.unwrap_or_else(|| sequence.clone()), sequence, distance, cas9, pam })
+}
+
+// Whether `arg` is among the command line tokens `given`: its long flag, alone or with `=value`,
+// or its short flag, alone or, if it takes a value, with the value attached as in `-d3`.
+fn is_given(arg: &clap::Arg, given: &[String]) -> bool {
+    let long = arg.get_long().map(|long| format!("--{}", long));
+    let short = arg.get_short().map(|c| format!("-{}", c));
+    let takes_value = arg.get_action().takes_values();
+    given.iter().any(|token| {
+        long.as_ref().is_some_and(|long| token == long || token.strip_prefix(long.as_str()).is_some_and(|rest| rest.starts_with('=')))
+            || short.as_ref().is_some_and(|short| token == short || takes_value && token.len() > short.len() && token.starts_with(short.as_str()))
+    })
+}
+
+// The flags for the file's options that `given` does not set itself, checked against `command`.
+pub fn command_line(config: &RunConfig, command: &clap::Command, given: &[String]) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    for (key, value) in &config.options {
+        let long = key.replace('_', "-");
+        let arg = command.get_arguments().find(|arg| arg.get_long() == Some(long.as_str())).ok_or_else(|| format!("unknown option {}", key))?;
+        if long == "config" {
+            return Err("a config file cannot name another one".to_string());
+        }
+        // an option also yields to a given one it cannot be combined with, as --distance does to --max-divergence
+        let conflicts = |other: &clap::Arg| command.get_arg_conflicts_with(arg).contains(&other) || command.get_arg_conflicts_with(other).contains(&arg);
+        let overridden = std::iter::once(arg).chain(command.get_arguments().filter(|other| conflicts(other))).any(|arg| is_given(arg, given));
+        if overridden {
+            continue;
+        }
+        let flag = format!("--{}", long);
+        match (value, arg.get_action().takes_values()) {
+            (Value::Bool(true), false) => flags.push(flag),
+            (Value::Bool(false), false) => {}
+            (_, false) => return Err(format!("{} is a switch and takes true or false", key)),
+            (Value::Bool(b), true) => flags.extend([flag, b.to_string()]),
+            (Value::Scalar(s), true) => flags.extend([flag, s.clone()]),
+            (Value::List(items), true) => {
+                for item in items {
+                    flags.extend([flag.clone(), item.clone()]);
+                }
+            }
+        }
+    }
+    Ok(flags)
+}
+
+// Cuts a `#` comment that is not inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// The text of a quoted string, which must end at its closing quote; of escapes, double quotes
+// only take \" and \\.
+fn quoted(s: &str, quote: char) -> Result<String, String> {
+    if s.starts_with(&quote.to_string().repeat(3)) {
+        return Err("multi-line strings are not supported".to_string());
+    }
+    let mut text = String::new();
+    let mut chars = s[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => {
+                let rest = chars.as_str().trim();
+                return if rest.is_empty() { Ok(text) } else { Err(format!("unexpected {} after {}{}{}", rest, quote, text, quote)) };
+            }
+            '\\' if quote == '"' => match chars.next() {
+                Some(c @ ('"' | '\\')) => text.push(c),
+                Some(c) => return Err(format!("unsupported escape \\{}", c)),
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+    Err(format!("unterminated string {}", s))
+}
+
+fn scalar(s: &str) -> Result<Value, String> {
+    let s = s.trim();
+    if let Some(quote) = s.chars().next().filter(|c| matches!(c, '"' | '\'')) {
+        return quoted(s, quote).map(Value::Scalar);
+    }
+    if s.starts_with(['[', ']', '{', '}', '&', '*', '!', '|', '>', '%', '@', '`']) {
+        return Err(format!("unsupported value {}: expected a number, true, false, a quoted string or a one-line list", s));
+    }
+    match s {
+        "" => Err("missing value".to_string()),
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => Ok(Value::Scalar(s.to_string())),
+    }
+}
+
+// A scalar, or a one-line `[a, b]` list.
+fn value(s: &str) -> Result<Value, String> {
+    let s = s.trim();
+    if s.starts_with('[') && !s.ends_with(']') {
+        return Err("a list must be on one line".to_string());
+    }
+    match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        Some(items) => {
+            let items = items.split(',').filter(|item| !item.trim().is_empty()).map(|item| match scalar(item)? {
+                Value::Scalar(s) => Ok(s),
+                _ => Err(format!("bad list item {}", item.trim())),
+            });
+            Ok(Value::List(items.collect::<Result<_, String>>()?))
+        }
+        None => scalar(s),
+    }
+}
+
+fn parse_toml(text: &str) -> Result<(Table, Vec<Table>), String> {
+    let (mut options, mut tables): (Table, Vec<Table>) = (Vec::new(), Vec::new());
+    for (n, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "[[queries]]" {
+            tables.push(Vec::new());
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(format!("line {}: only [[queries]] tables are supported", n + 1));
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("line {}: expected key = value", n + 1))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let value = self::value(value).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        // keys after a table header belong to the table
+        tables.last_mut().unwrap_or(&mut options).push((key, value));
+    }
+    Ok((options, tables))
+}
+
+fn parse_yaml(text: &str) -> Result<(Table, Vec<Table>), String> {
+    let (mut options, mut tables): (Table, Vec<Table>) = (Vec::new(), Vec::new());
+    // the top-level key whose indented block is being read
+    let mut block: Option<String> = None;
+    for (n, line) in text.lines().enumerate() {
+        let error = |e: String| format!("line {}: {}", n + 1, e);
+        let content = strip_comment(line);
+        if content.trim().is_empty() {
+            continue;
+        }
+        if line.trim() == "---" || line.trim() == "..." {
+            if !options.is_empty() || !tables.is_empty() {
+                return Err(error("only one YAML document is read".to_string()));
+            }
+            continue;
+        }
+        let (key, rest) = match content.trim().strip_prefix("- ") {
+            Some(item) => (None, item),
+            None => {
+                let (key, rest) = content.trim().split_once(':').ok_or_else(|| error("expected key: value".to_string()))?;
+                (Some(key.trim().trim_matches('"').to_string()), rest)
+            }
+        };
+        if !content.starts_with([' ', '-']) {
+            // a top-level key, with its value or a block below it
+            let key = key.ok_or_else(|| error("list item outside of a block".to_string()))?;
+            if rest.trim().is_empty() {
+                if key != "queries" {
+                    options.push((key.clone(), Value::List(Vec::new())));
+                }
+                block = Some(key);
+            } else {
+                options.push((key, value(rest).map_err(error)?));
+                block = None;
+            }
+            continue;
+        }
+        match (block.as_deref(), key) {
+            (Some("queries"), None) => {
+                let (key, rest) = rest.split_once(':').ok_or_else(|| error("expected a mapping of name, sequence, ...".to_string()))?;
+                tables.push(vec![(key.trim().to_string(), value(rest).map_err(error)?)]);
+            }
+            (Some("queries"), Some(key)) => {
+                let table = tables.last_mut().ok_or_else(|| error("expected a list of queries".to_string()))?;
+                table.push((key, value(rest).map_err(error)?));
+            }
+            (Some(_), None) => match (options.last_mut(), scalar(rest).map_err(error)?) {
+                (Some((_, Value::List(items))), Value::Scalar(item)) => items.push(item),
+                _ => return Err(error("bad list item".to_string())),
+            },
+            _ => return Err(error("unexpected indented line".to_string())),
+        }
+    }
+    Ok((options, tables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn scalar(s: &str) -> Value {
+        Value::Scalar(s.to_string())
+    }
+
+    fn list(items: &[&str]) -> Value {
+        Value::List(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    fn flags(config: &str, given: &[&str]) -> Result<Vec<String>, String> {
+        let given: Vec<String> = given.iter().map(|token| token.to_string()).collect();
+        command_line(&parse("run.toml", config).unwrap(), &crate::Cli::command(), &given)
+    }
+
+    #[test]
+    fn toml_options() {
+        let text = "# a run\n\ndistance = 3 # of 20\ncas9 = true\nprefix = \"run#1\"  # quoted\npam = 'NG#G'\nlibrary = [\"a.csv\", 'b#.csv', ]\n\"step\" = 2\n";
+        let config = parse("run.toml", text).unwrap();
+        let expected = [("distance", scalar("3")), ("cas9", Value::Bool(true)), ("prefix", scalar("run#1")), ("pam", scalar("NG#G")), ("library", list(&["a.csv", "b#.csv"])), ("step", scalar("2"))];
+        assert_eq!(config.options, expected.map(|(key, value)| (key.to_string(), value)));
+        assert!(config.queries.is_empty());
+    }
+
+    #[test]
+    fn yaml_options() {
+        let text = "---\n# a run\ndistance: 3 # of 20\ncas9: false\nprefix: \"run#1\"\nlibrary:\n  - a.csv\n  - 'b#.csv'  # second\nbed: [x.bed]\n";
+        let config = parse("run.yaml", text).unwrap();
+        let expected = [("distance", scalar("3")), ("cas9", Value::Bool(false)), ("prefix", scalar("run#1")), ("library", list(&["a.csv", "b#.csv"])), ("bed", list(&["x.bed"]))];
+        assert_eq!(config.options, expected.map(|(key, value)| (key.to_string(), value)));
+    }
+
+    #[test]
+    fn queries() {
+        let toml = "distance = 4\n\n[[queries]]\nname = \"one\"\nsequence = \"ACGTACGT\"\ndistance = 1\npam = \"NAG\"\n\n[[queries]]\nsequence = \"TTTTCCCC\" # unnamed\ncas9 = true\n";
+        let yaml = "distance: 4\nqueries:\n  - name: one\n    sequence: ACGTACGT\n    distance: 1\n    pam: NAG\n  - sequence: TTTTCCCC # unnamed\n    cas9: true\n";
+        for (path, text) in [("run.toml", toml), ("run.yml", yaml)] {
+            let config = parse(path, text).unwrap();
+            assert_eq!(config.options, vec![("distance".to_string(), scalar("4"))], "{}", path);
+            let queries: Vec<_> = config.queries.iter().map(|q| (q.name.as_str(), q.sequence.as_str(), q.distance, q.cas9, q.pam.as_deref())).collect();
+            assert_eq!(queries, [("one", "ACGTACGT", Some(1), None, Some("NAG")), ("TTTTCCCC", "TTTTCCCC", None, Some(true), None)], "{}", path);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_and_malformed_lines() {
+        let error = |path: &str, text: &str| parse(path, text).err().unwrap();
+        assert_eq!(error("run.toml", "[[queries]]\nsequence = \"ACGT\"\nstrand = \"+\"\n"), "run.toml: query 1: unknown key strand, expected name, sequence, distance, cas9 or pam");
+        assert_eq!(error("run.yaml", "queries:\n  - sequence: ACGT\n  - name: two\n"), "run.yaml: query 2 has no sequence");
+        assert_eq!(error("run.toml", "[[queries]]\nsequence = \"ACGT\"\ndistance = \"few\"\n"), "run.toml: query 1: distance must be a number, not few");
+        assert_eq!(error("run.toml", "[options]\n"), "run.toml: line 1: only [[queries]] tables are supported");
+        assert_eq!(error("run.toml", "distance\n"), "run.toml: line 1: expected key = value");
+        assert_eq!(error("run.toml", "prefix = # none\n"), "run.toml: line 1: missing value");
+        assert_eq!(flags("no_such_option = 1\n", &[]), Err("unknown option no_such_option".to_string()));
+        assert_eq!(flags("config = \"other.toml\"\n", &[]), Err("a config file cannot name another one".to_string()));
+        assert_eq!(flags("distance = true\n", &[]), Ok(vec!["--distance".to_string(), "true".to_string()]));
+        assert_eq!(flags("cas9 = 1\n", &[]), Err("cas9 is a switch and takes true or false".to_string()));
+    }
+
+    #[test]
+    fn syntax_outside_the_subset_is_an_error() {
+        let error = |path: &str, text: &str| parse(path, text).err().unwrap();
+        assert_eq!(error("run.toml", "library = [\n  \"a.csv\",\n]\n"), "run.toml: line 1: a list must be on one line");
+        assert_eq!(error("run.toml", "pam = { sequence = \"NGG\" }\n"), "run.toml: line 1: unsupported value { sequence = \"NGG\" }: expected a number, true, false, a quoted string or a one-line list");
+        assert_eq!(error("run.toml", "prefix = \"\"\"x\"\"\"\n"), "run.toml: line 1: multi-line strings are not supported");
+        assert_eq!(error("run.toml", "prefix = \"a\\tb\"\n"), "run.toml: line 1: unsupported escape \\t");
+        assert_eq!(error("run.toml", "prefix = \"chr\n"), "run.toml: line 1: unterminated string \"chr");
+        assert_eq!(error("run.toml", "prefix = \"chr\" 1\n"), "run.toml: line 1: unexpected 1 after \"chr\"");
+        assert_eq!(error("run.toml", "library = [\"a,b.csv\"]\n"), "run.toml: line 1: unterminated string \"a");
+        assert_eq!(error("run.toml", "library = [[\"a.csv\"]]\n"), "run.toml: line 1: unsupported value [\"a.csv\"]: expected a number, true, false, a quoted string or a one-line list");
+        assert_eq!(error("run.yaml", "pam: {sequence: NGG}\n"), "run.yaml: line 1: unsupported value {sequence: NGG}: expected a number, true, false, a quoted string or a one-line list");
+        assert_eq!(error("run.yaml", "prefix: &p chr\n"), "run.yaml: line 1: unsupported value &p chr: expected a number, true, false, a quoted string or a one-line list");
+        assert_eq!(error("run.yaml", "prefix: |\n  chr\n"), "run.yaml: line 1: unsupported value |: expected a number, true, false, a quoted string or a one-line list");
+        assert_eq!(error("run.yaml", "distance: 3\n---\ndistance: 4\n"), "run.yaml: line 2: only one YAML document is read");
+        assert_eq!(error("run.yaml", "options:\n  distance: 3\n"), "run.yaml: line 2: unexpected indented line");
+        // a quoted string may still hold what would otherwise be refused
+        assert_eq!(parse("run.toml", "prefix = \"{x} [y] \\\"z\\\"\"\n").unwrap().options, [("prefix".to_string(), scalar("{x} [y] \"z\""))]);
+    }
+
+    #[test]
+    fn given_flags_override_the_file() {
+        let config = "distance = 3\ncas9 = true\nprefix = \"x\"\nlibrary = [\"a.csv\", \"b.csv\"]\n";
+        let all = ["--distance", "3", "--cas9", "--prefix", "x", "--library", "a.csv", "--library", "b.csv"];
+        assert_eq!(flags(config, &[]).unwrap(), all);
+        assert_eq!(flags(config, &["-t", "4"]).unwrap(), all);
+        for given in [&["-d", "2"][..], &["-d2"], &["--distance", "2"], &["--distance=2"], &["--max-divergence", "0.1"], &["--max-divergence=0.1"]] {
+            assert_eq!(flags(config, given).unwrap(), all[2..], "{:?}", given);
+        }
+        assert_eq!(flags(config, &["-c"]).unwrap(), ["--distance", "3", "--prefix", "x", "--library", "a.csv", "--library", "b.csv"]);
+        assert_eq!(flags(config, &["--library", "c.csv"]).unwrap(), all[..5]);
+        // and the other way round, a file's --max-divergence yields to a given --distance
+        assert!(flags("max_divergence = 0.1\n", &["-d", "2"]).unwrap().is_empty());
+        assert_eq!(flags("cas9 = false\n", &[]).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn short_flags_match_exactly() {
+        let command = crate::Cli::command();
+        let arg = |long: &str| command.get_arguments().find(|arg| arg.get_long() == Some(long)).unwrap();
+        let given = |tokens: &[&str]| tokens.iter().map(|token| token.to_string()).collect::<Vec<_>>();
+        // -c is a switch, so only -c itself sets it
+        assert!(is_given(arg("cas9"), &given(&["-c"])));
+        assert!(!is_given(arg("cas9"), &given(&["-cas9"])));
+        assert!(!is_given(arg("cas9"), &given(&["--cas9-off"])));
+        // -d takes a value, which may be attached
+        assert!(is_given(arg("distance"), &given(&["-d3"])));
+        assert!(is_given(arg("distance"), &given(&["-d", "3"])));
+        assert!(!is_given(arg("distance"), &given(&["--distance-plus", "3"])));
+        assert!(!is_given(arg("distance"), &given(&["-t3"])));
+    }
+}
@@ -0,0 +1,153 @@
+// Column-wise tally of reported hit sequences. Hits of a query all have the query's
+// length and are reported in query orientation, so no alignment is needed.
+
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+#[derive(Default)]
+pub struct Profile {
+    hits: u64,
+    // per position counts of A, C, G, T and anything else
+    counts: Vec<[u64; 5]>,
+}
+
+impl Profile {
+    pub fn add(&mut self, seq: &str) {
+        if self.counts.len() < seq.len() {
+            self.counts.resize(seq.len(), [0; 5]);
+        }
+        for (column, b) in self.counts.iter_mut().zip(seq.bytes()) {
+            let i = match b.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => 4,
+            };
+            column[i] += 1;
+        }
+        self.hits += 1;
+    }
+
+    pub fn merge(&mut self, other: &Profile) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), [0; 5]);
+        }
+        for (mine, theirs) in self.counts.iter_mut().zip(other.counts.iter()) {
+            for i in 0..5 {
+                mine[i] += theirs[i];
+            }
+        }
+        self.hits += other.hits;
+    }
+
+    // Most frequent base per column, N where no base was observed or non-ACGT dominates.
+    pub fn consensus(&self) -> String {
+        self.counts
+            .iter()
+            .map(|column| {
+                let (best, count) = (0..4).map(|i| (i, column[i])).max_by_key(|&(i, c)| (c, std::cmp::Reverse(i))).unwrap();
+                if count == 0 || column[4] > count { 'N' } else { BASES[best] }
+            })
+            .collect()
+    }
+
+    // The position matrix under both reports: a row per column of its A, C, G, T and other
+    // cells, as counts or as frequencies, and optionally the column's information.
+    fn write_columns<W: std::io::Write>(&self, out: &mut W, query: &str, frequencies: bool, bits: bool) -> std::io::Result<()> {
+        writeln!(out, "position\tquery\tA\tC\tG\tT\tother{}", if bits { "\tbits" } else { "" })?;
+        let mut query_bases = query.chars();
+        for (i, column) in self.counts.iter().enumerate() {
+            write!(out, "{}\t{}", i + 1, query_bases.next().unwrap_or('-'))?;
+            let total: u64 = column.iter().sum();
+            for &count in column {
+                if frequencies {
+                    write!(out, "\t{:.4}", if total > 0 { count as f64 / total as f64 } else { 0.0 })?;
+                } else {
+                    write!(out, "\t{}", count)?;
+                }
+            }
+            if bits {
+                write!(out, "\t{:.3}", Profile::information(column))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    // `--hits-consensus`: the consensus with the frequency of each base at each position.
+    pub fn write<W: std::io::Write>(&self, out: &mut W, query: &str) -> std::io::Result<()> {
+        writeln!(out, "# consensus of {} hits", self.hits)?;
+        writeln!(out, "consensus\t{}", self.consensus())?;
+        self.write_columns(out, query, true, false)
+    }
+
+    // Information of each column in bits, over its A, C, G and T: 2 where one base is fixed,
+    // 0 where all four are equally frequent.
+    fn information(column: &[u64; 5]) -> f64 {
+        let total: u64 = column[..4].iter().sum();
+        let entropy: f64 = column[..4].iter().filter(|&&c| c > 0).map(|&c| c as f64 / total as f64).map(|p| -p * p.log2()).sum();
+        if total > 0 { 2.0 - entropy } else { 0.0 }
+    }
+
+    // `--hits-logo`: the position counts of the hits with each column's information.
+    pub fn write_matrix<W: std::io::Write>(&self, out: &mut W, query: &str) -> std::io::Result<()> {
+        writeln!(out, "# position counts of {} hits", self.hits)?;
+        self.write_columns(out, query, false, true)
+    }
+
+    // A sequence logo in text, `rows_per_bit` rows to a bit: each column stacks its bases, the
+    // most frequent on top, each as high as its share of the column's information. The query
+    // is written underneath, so variation shows as short or mixed stacks over its bases.
+    pub fn write_logo<W: std::io::Write>(&self, out: &mut W, query: &str, rows_per_bit: usize) -> std::io::Result<()> {
+        let stacks: Vec<Vec<char>> = self
+            .counts
+            .iter()
+            .map(|column| {
+                let total: u64 = column[..4].iter().sum();
+                let rows = Profile::information(column) * rows_per_bit as f64;
+                let mut bases: Vec<(u64, usize)> = (0..4).filter(|&i| column[i] > 0).map(|i| (column[i], i)).collect();
+                // from the bottom up, so the most frequent ends on top
+                bases.sort_by_key(|&(count, i)| (count, std::cmp::Reverse(i)));
+                bases.iter().flat_map(|&(count, i)| std::iter::repeat_n(BASES[i], (rows * count as f64 / total as f64).round() as usize)).collect()
+            })
+            .collect();
+        // rounding can stack a column a row above two bits
+        let height = stacks.iter().map(Vec::len).max().unwrap_or(0).max(2 * rows_per_bit);
+        for row in (0..height).rev() {
+            let line: String = stacks.iter().map(|stack| stack.get(row).copied().unwrap_or(' ')).collect();
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        writeln!(out, "{}", query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(write: impl Fn(&mut Vec<u8>) -> std::io::Result<()>) -> Vec<String> {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn frequencies_and_counts_come_from_one_matrix() {
+        let mut profile = Profile::default();
+        for seq in ["ACGT", "ACGA", "acNA"] {
+            profile.add(seq);
+        }
+        assert_eq!(profile.consensus(), "ACGA");
+        let counts = lines(|out| profile.write_matrix(out, "ACGG"));
+        assert_eq!(counts[0], "# position counts of 3 hits");
+        assert_eq!(counts[1], "position\tquery\tA\tC\tG\tT\tother\tbits");
+        assert_eq!(counts[2], "1\tA\t3\t0\t0\t0\t0\t2.000");
+        assert_eq!(counts[4], "3\tG\t0\t0\t2\t0\t1\t2.000");
+        assert_eq!(counts[5], "4\tG\t2\t0\t0\t1\t0\t1.082");
+        let frequencies = lines(|out| profile.write(out, "ACG"));
+        assert_eq!(frequencies[..3], ["# consensus of 3 hits", "consensus\tACGA", "position\tquery\tA\tC\tG\tT\tother"]);
+        assert_eq!(frequencies[5], "3\tG\t0.0000\t0.0000\t0.6667\t0.0000\t0.3333");
+        // past the end of the query
+        assert_eq!(frequencies[6], "4\t-\t0.6667\t0.0000\t0.0000\t0.3333\t0.0000");
+    }
+}
@@ -0,0 +1,88 @@
+// `--cache-bytes`: decompressed pieces of contigs kept in memory across fetches, so that the
+// searches of one `Searcher` and the per-hit fetches of --context and --hits-fasta-flank
+// that go back to the same contigs read them from the FASTA once. Pieces are CHUNK_BASES long
+// and aligned, shared by every thread, and the least recently used go first past the limit.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+pub const CHUNK_BASES: usize = 1 << 20;
+
+pub struct ContigCache {
+    limit: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    // (contig, chunk index) -> the chunk and when it was last used
+    chunks: HashMap<(String, usize), (Arc<String>, u64)>,
+    // last use -> chunk, oldest first
+    order: BTreeMap<u64, (String, usize)>,
+    held: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ContigCache {
+    pub fn new(limit: usize) -> ContigCache {
+        ContigCache { limit, state: Mutex::default() }
+    }
+
+    // [start, end) of a contig of `length` bases, with the chunks not cached read by `fetch`,
+    // which takes a range as well.
+    pub fn get<F: Fn(usize, usize) -> Result<String, String>>(&self, seq_name: &str, length: usize, start: usize, end: usize, fetch: F) -> Result<String, String> {
+        let mut bases = String::with_capacity(end - start);
+        for chunk in start / CHUNK_BASES..end.div_ceil(CHUNK_BASES) {
+            let chunk_start = chunk * CHUNK_BASES;
+            let bytes = self.chunk(seq_name, chunk, || fetch(chunk_start, (chunk_start + CHUNK_BASES).min(length)))?;
+            let (from, to) = (start.max(chunk_start) - chunk_start, end.min(chunk_start + CHUNK_BASES) - chunk_start);
+            bases.push_str(&bytes[from..to]);
+        }
+        Ok(bases)
+    }
+
+    fn chunk<F: FnOnce() -> Result<String, String>>(&self, seq_name: &str, chunk: usize, fetch: F) -> Result<Arc<String>, String> {
+        let key = (seq_name.to_string(), chunk);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some((bases, used)) = state.chunks.get_mut(&key) {
+                let (bases, last) = (bases.clone(), std::mem::replace(used, clock));
+                state.order.remove(&last);
+                state.order.insert(clock, key);
+                state.hits += 1;
+                return Ok(bases);
+            }
+            state.misses += 1;
+        }
+        // read without the lock, so other threads are not held up; two may read the same chunk
+        let bases = Arc::new(fetch()?);
+        if bases.len() > self.limit {
+            return Ok(bases);
+        }
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        if let Some((_, last)) = state.chunks.insert(key.clone(), (bases.clone(), clock)) {
+            state.order.remove(&last);
+        } else {
+            state.held += bases.len();
+        }
+        state.order.insert(clock, key);
+        while state.held > self.limit {
+            let (_, oldest) = state.order.pop_first().unwrap();
+            let (evicted, _) = state.chunks.remove(&oldest).unwrap();
+            state.held -= evicted.len();
+        }
+        Ok(bases)
+    }
+
+    // Chunks found cached and chunks read, so far.
+    pub fn stats(&self) -> (u64, u64) {
+        let state = self.state.lock().unwrap();
+        (state.hits, state.misses)
+    }
+}
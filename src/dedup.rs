@@ -0,0 +1,116 @@
+// Detection of contigs with identical sequence, so each distinct sequence is scanned once.
+
+use rayon::prelude::*;
+use rust_htslib::faidx::Reader;
+use std::collections::HashMap;
+use xxhash_rust::xxh3::Xxh3;
+
+const CHUNK: usize = 1 << 20;
+
+fn chunk(reader: &Reader, seq_name: &str, start: usize, length: usize) -> Result<Vec<u8>, String> {
+    let end = (start + CHUNK).min(length);
+    reader.fetch_seq(seq_name, start, end - 1).map(<[u8]>::to_vec).map_err(|e| format!("{}: {}", seq_name, e))
+}
+
+fn content_hash(reader: &Reader, seq_name: &str, length: usize) -> Result<u64, String> {
+    let mut hasher = Xxh3::new();
+    for start in (0..length).step_by(CHUNK) {
+        hasher.update(&chunk(reader, seq_name, start, length)?);
+    }
+    Ok(hasher.digest())
+}
+
+// Whether two contigs of `length` bases hold the same bytes, read a chunk of each at a time.
+fn same_sequence(reader: &Reader, a: &str, b: &str, length: usize) -> Result<bool, String> {
+    for start in (0..length).step_by(CHUNK) {
+        if chunk(reader, a, start, length)? != chunk(reader, b, start, length)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// Maps the first contig (in index order) of each group of identical contigs to the names
+// of the others. Only contigs sharing a length are read and hashed, and contigs sharing a hash
+// are compared base by base before they are grouped.
+pub fn find_duplicates(fasta: &str, names: &[String], lengths: &HashMap<String, usize>) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut by_length: HashMap<usize, Vec<&String>> = HashMap::new();
+    for name in names {
+        by_length.entry(lengths[name]).or_default().push(name);
+    }
+    let open = || Reader::from_path(fasta).map_err(|e| format!("{}: {}", fasta, e));
+    let candidates: Vec<&String> = names.iter().filter(|name| by_length[&lengths[*name]].len() > 1).collect();
+    let hashes: HashMap<&String, u64> = candidates
+        .par_iter()
+        .map(|name| Ok((*name, content_hash(&open()?, name, lengths[*name])?)))
+        .collect::<Result<_, String>>()?;
+
+    let mut groups: HashMap<(usize, u64), Vec<&String>> = HashMap::new();
+    for name in candidates {
+        groups.entry((lengths[name], hashes[name])).or_default().push(name);
+    }
+    let groups: Vec<Vec<&String>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    // a group splits into classes of identical contigs should two sequences share a hash
+    let classes: Vec<Vec<Vec<&String>>> = groups
+        .par_iter()
+        .map(|group| {
+            let reader = open()?;
+            let mut classes: Vec<Vec<&String>> = Vec::new();
+            for &name in group {
+                let mut same = None;
+                for (i, class) in classes.iter().enumerate() {
+                    if same_sequence(&reader, class[0], name, lengths[name])? {
+                        same = Some(i);
+                        break;
+                    }
+                }
+                match same {
+                    Some(i) => classes[i].push(name),
+                    None => classes.push(vec![name]),
+                }
+            }
+            Ok(classes)
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(classes
+        .into_iter()
+        .flatten()
+        .filter(|class| class.len() > 1)
+        .map(|class| (class[0].clone(), class[1..].iter().map(|name| name.to_string()).collect()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_identical_contigs_are_grouped() {
+        let contigs = [("a", "ACGTACGTAC"), ("b", "ACGTACGTAC"), ("c", "ACGTACGTAA"), ("d", "ACGTACGTA"), ("e", "ACGTACGTAC"), ("f", "ACGTACGTAA")];
+        let path = std::env::temp_dir().join(format!("hamming-fasta-dedup-{}.fa", std::process::id()));
+        let (mut fasta, mut fai) = (String::new(), String::new());
+        for (name, seq) in contigs {
+            fasta.push_str(&format!(">{}\n", name));
+            fai.push_str(&format!("{}\t{}\t{}\t4\t5\n", name, seq.len(), fasta.len()));
+            for line in seq.as_bytes().chunks(4) {
+                fasta.push_str(std::str::from_utf8(line).unwrap());
+                fasta.push('\n');
+            }
+        }
+        std::fs::write(&path, fasta).unwrap();
+        let fai_path = format!("{}.fai", path.display());
+        std::fs::write(&fai_path, fai).unwrap();
+        let names: Vec<String> = contigs.iter().map(|(name, _)| name.to_string()).collect();
+        let lengths: HashMap<String, usize> = contigs.iter().map(|(name, seq)| (name.to_string(), seq.len())).collect();
+        let path = path.to_str().unwrap();
+        let duplicates = find_duplicates(path, &names, &lengths);
+        let reader = Reader::from_path(path).unwrap();
+        let compared = (same_sequence(&reader, "a", "e", 10), same_sequence(&reader, "a", "c", 10));
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(fai_path).unwrap();
+        let expected = HashMap::from([("a".to_string(), vec!["b".to_string(), "e".to_string()]), ("c".to_string(), vec!["f".to_string()])]);
+        assert_eq!(duplicates, Ok(expected));
+        assert_eq!(compared, (Ok(true), Ok(false)));
+        assert!(find_duplicates("/nonexistent.fa", &names, &lengths).unwrap_err().starts_with("/nonexistent.fa: "));
+    }
+}
@@ -0,0 +1,108 @@
+// `diff`: run one query against two references and report the hits that differ.
+
+use hamming_fasta::liftover::Liftover;
+use hamming_fasta::{Hit, Query, SearchConfig, Searcher};
+use std::collections::BTreeMap;
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// FASTA file of the first reference
+    #[arg(short = 'a', long)]
+    fasta_a: String,
+
+    /// FASTA file of the second reference
+    #[arg(short = 'b', long)]
+    fasta_b: String,
+
+    /// Target sequence to search for
+    #[arg(short, long)]
+    sequence: String,
+
+    /// Prefix for the sequence names to search within
+    #[arg(short = 'p', long, default_value = "")]
+    prefix: String,
+
+    /// Maximum number of mismatches allowed (Hamming distance)
+    #[arg(short, long, default_value_t = 6)]
+    distance: usize,
+
+    /// Number of threads for parallel execution
+    #[arg(short = 't', long, default_value = "0")]
+    parallelism: usize,
+
+    /// Flag for the presence of a Cas9 PAM sequence
+    #[arg(short = 'c', long, default_value = "false")]
+    cas9: bool,
+
+    /// Chain or PAF (.paf) alignments of the first reference to the second, used to match hits whose coordinates differ
+    #[arg(long)]
+    liftover: Option<String>,
+}
+
+type Site = (String, String, usize);
+
+fn search(fasta: &str, prefix: &str, query: &Query) -> Vec<Hit> {
+    let config = SearchConfig { prefix: prefix.to_string(), ..SearchConfig::default() };
+    Searcher::new(fasta, config).unwrap().search(query)
+}
+
+fn columns(site: Option<(&Site, &Hit)>) -> String {
+    match site {
+        Some(((seq_name, strand, start), hit)) => format!("{}\t{}\t{}\t{}\t{}", seq_name, strand, start, hit.mismatches, hit.sequence),
+        None => ".\t.\t.\t.\t.".to_string(),
+    }
+}
+
+pub fn run(args: &DiffArgs) {
+    if args.parallelism > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.parallelism).build_global().unwrap();
+    }
+    let mut query = Query::new(&args.sequence, args.distance);
+    query.cas9 = args.cas9;
+    let liftover = args.liftover.as_ref().map(|path| Liftover::from_path(path).unwrap());
+
+    // hits of the first reference keyed by where they land in the second
+    let mut a_hits: BTreeMap<Site, (Site, Hit)> = BTreeMap::new();
+    let mut unmapped = Vec::new();
+    for hit in search(&args.fasta_a, &args.prefix, &query) {
+        let a_site = (hit.seq_name.to_string(), hit.strand.to_string(), hit.start);
+        let b_site = match &liftover {
+            Some(liftover) => match liftover.project(&hit.seq_name, hit.strand.as_str(), hit.start, hit.end) {
+                Some(p) => (p.seq_name.to_string(), p.strand.to_string(), p.start),
+                None => {
+                    unmapped.push((a_site, hit));
+                    continue;
+                }
+            },
+            None => a_site.clone(),
+        };
+        a_hits.insert(b_site, (a_site, hit));
+    }
+    let b_hits: BTreeMap<Site, Hit> = search(&args.fasta_b, &args.prefix, &query).into_iter().map(|hit| ((hit.seq_name.to_string(), hit.strand.to_string(), hit.start), hit)).collect();
+
+    let mut counts = BTreeMap::from([("gained", 0), ("lost", 0), ("changed", 0), ("unchanged", 0), ("unmapped", 0)]);
+    println!("status\ta_seq_name\ta_strand\ta_start\ta_mismatches\ta_sequence\tb_seq_name\tb_strand\tb_start\tb_mismatches\tb_sequence");
+    for (b_site, (a_site, a_hit)) in &a_hits {
+        let b_hit = b_hits.get(b_site);
+        let status = match b_hit {
+            None => "lost",
+            Some(b_hit) if b_hit.mismatches != a_hit.mismatches => "changed",
+            Some(_) => "unchanged",
+        };
+        *counts.get_mut(status).unwrap() += 1;
+        if status != "unchanged" {
+            println!("{}\t{}\t{}", status, columns(Some((a_site, a_hit))), columns(b_hit.map(|hit| (b_site, hit))));
+        }
+    }
+    for (b_site, b_hit) in b_hits.iter().filter(|(site, _)| !a_hits.contains_key(*site)) {
+        *counts.get_mut("gained").unwrap() += 1;
+        println!("gained\t{}\t{}", columns(None), columns(Some((b_site, b_hit))));
+    }
+    for (a_site, a_hit) in &unmapped {
+        *counts.get_mut("unmapped").unwrap() += 1;
+        println!("unmapped\t{}\t{}", columns(Some((a_site, a_hit))), columns(None));
+    }
+    for (status, count) in counts {
+        eprintln!("{}\t{}", status, count);
+    }
+}
@@ -0,0 +1,52 @@
+// Runs of N (assembly gaps) long enough that no window overlapping them can be a hit.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// Half-open intervals of N/n runs of at least `min_len` bases, in order.
+pub fn find_n_runs(seq: &[u8], min_len: usize) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < seq.len() {
+        if seq[i] == b'N' || seq[i] == b'n' {
+            let start = i;
+            while i < seq.len() && (seq[i] == b'N' || seq[i] == b'n') {
+                i += 1;
+            }
+            if i - start >= min_len {
+                runs.push((start, i));
+            }
+        } else {
+            i += 1;
+        }
+    }
+    runs
+}
+
+// BED-like file of gaps: `seq_name start end`, 0-based half-open.
+pub fn load_gaps(path: &str, min_len: usize) -> HashMap<String, Vec<(usize, usize)>> {
+    let file = File::open(path).unwrap();
+    let mut gaps: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.unwrap();
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 3 || parts[0].starts_with('#') {
+            continue;
+        }
+        let start: usize = parts[1].parse().unwrap();
+        let end: usize = parts[2].parse().unwrap();
+        if end - start >= min_len {
+            gaps.entry(parts[0].to_string()).or_default().push((start, end));
+        }
+    }
+    for runs in gaps.values_mut() {
+        runs.sort_unstable();
+    }
+    gaps
+}
+
+// The same runs in reverse-complement coordinates of a sequence of length `len`.
+pub fn mirror(runs: &[(usize, usize)], len: usize) -> Vec<(usize, usize)> {
+    runs.iter().rev().map(|&(start, end)| (len - end, len - start)).collect()
+}
@@ -0,0 +1,158 @@
+// Translation of hit coordinates on the searched contigs to node/offset coordinates of a
+// pangenome graph, through the GFA paths (P lines) or walks (W lines) that spell them.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+struct Step {
+    node: usize,
+    reverse: bool,
+    // end of the step on the path
+    end: usize,
+}
+
+pub struct GraphPaths {
+    nodes: Vec<String>,
+    // per searched contig: where its path starts on the contig, and its steps
+    paths: HashMap<String, (usize, Vec<Step>)>,
+}
+
+// A base of a hit on the graph: node, offset on the node's forward sequence and orientation,
+// as taken and printed by `odgi position`.
+pub struct GraphPosition<'a> {
+    pub node: &'a str,
+    pub offset: usize,
+    pub strand: &'static str,
+}
+
+pub struct GraphInterval<'a> {
+    pub first: GraphPosition<'a>,
+    pub last: GraphPosition<'a>,
+    // the oriented nodes the hit crosses, such as >12>13<14
+    pub walk: String,
+}
+
+impl GraphPaths {
+    // Only the paths of `contigs` are kept. A W line is named `sample#haplotype#contig` as in
+    // PanSN, with its start offset on the contig taken into account.
+    pub fn from_gfa(path: &str, contigs: &[(String, usize)]) -> Result<GraphPaths, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let wanted: HashSet<&str> = contigs.iter().map(|(name, _)| name.as_str()).collect();
+        let mut nodes = Vec::new();
+        let mut node_ids: HashMap<String, usize> = HashMap::new();
+        let mut lengths: Vec<Option<usize>> = Vec::new();
+        let mut node_id = |name: &str, nodes: &mut Vec<String>, lengths: &mut Vec<Option<usize>>| {
+            *node_ids.entry(name.to_string()).or_insert_with(|| {
+                nodes.push(name.to_string());
+                lengths.push(None);
+                nodes.len() - 1
+            })
+        };
+        // steps as (node, reverse) until every segment length is known
+        let mut walks = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("{}: {}", path, e))?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            let malformed = || format!("{}: malformed GFA line: {}", path, line);
+            match fields[0] {
+                "S" => {
+                    if fields.len() < 3 {
+                        return Err(malformed());
+                    }
+                    let length = match fields[2] {
+                        "*" => fields[3..].iter().find_map(|tag| tag.strip_prefix("LN:i:")).and_then(|n| n.parse().ok()).ok_or_else(malformed)?,
+                        sequence => sequence.len(),
+                    };
+                    let id = node_id(fields[1], &mut nodes, &mut lengths);
+                    lengths[id] = Some(length);
+                }
+                "P" => {
+                    if fields.len() < 3 {
+                        return Err(malformed());
+                    }
+                    if !wanted.contains(fields[1]) {
+                        continue;
+                    }
+                    let mut steps = Vec::new();
+                    for step in fields[2].split(',') {
+                        let reverse = match step.as_bytes().last() {
+                            Some(b'+') => false,
+                            Some(b'-') => true,
+                            _ => return Err(malformed()),
+                        };
+                        steps.push((node_id(&step[..step.len() - 1], &mut nodes, &mut lengths), reverse));
+                    }
+                    walks.push((fields[1].to_string(), 0, steps));
+                }
+                "W" => {
+                    if fields.len() < 7 {
+                        return Err(malformed());
+                    }
+                    let name = format!("{}#{}#{}", fields[1], fields[2], fields[3]);
+                    if !wanted.contains(name.as_str()) {
+                        continue;
+                    }
+                    let start = if fields[4] == "*" { 0 } else { fields[4].parse().map_err(|_| malformed())? };
+                    let mut steps = Vec::new();
+                    let walk = fields[6];
+                    let mut i = 0;
+                    while i < walk.len() {
+                        let reverse = match walk.as_bytes()[i] {
+                            b'>' => false,
+                            b'<' => true,
+                            _ => return Err(malformed()),
+                        };
+                        let end = walk[i + 1..].find(['>', '<']).map_or(walk.len(), |n| i + 1 + n);
+                        steps.push((node_id(&walk[i + 1..end], &mut nodes, &mut lengths), reverse));
+                        i = end;
+                    }
+                    walks.push((name, start, steps));
+                }
+                _ => {}
+            }
+        }
+        let mut paths = HashMap::new();
+        for (name, start, steps) in walks {
+            let mut end = 0;
+            let mut path_steps = Vec::with_capacity(steps.len());
+            for (node, reverse) in steps {
+                end += lengths[node].ok_or_else(|| format!("{}: path {} visits segment {}, which is not in the graph", path, name, nodes[node]))?;
+                path_steps.push(Step { node, reverse, end });
+            }
+            paths.insert(name, (start, path_steps));
+        }
+        Ok(GraphPaths { nodes, paths })
+    }
+
+    // The step holding contig position `pos`, with `pos` as an offset into it.
+    fn locate(&self, steps: &[Step], pos: usize) -> Option<(usize, usize)> {
+        let i = steps.partition_point(|step| step.end <= pos);
+        let step_start = if i == 0 { 0 } else { steps[i - 1].end };
+        (i < steps.len()).then(|| (i, pos - step_start))
+    }
+
+    fn position(&self, steps: &[Step], i: usize, within: usize, reverse_hit: bool) -> GraphPosition<'_> {
+        let step = &steps[i];
+        let length = step.end - if i == 0 { 0 } else { steps[i - 1].end };
+        GraphPosition {
+            node: &self.nodes[step.node],
+            offset: if step.reverse { length - 1 - within } else { within },
+            strand: if step.reverse != reverse_hit { "-" } else { "+" },
+        }
+    }
+
+    // Translates [start, end) of a hit on `reverse_hit` strand of a contig; None when the contig
+    // has no path or the hit is not entirely on it.
+    pub fn project(&self, seq_name: &str, reverse_hit: bool, start: usize, end: usize) -> Option<GraphInterval<'_>> {
+        let (offset, steps) = self.paths.get(seq_name)?;
+        let (first, first_within) = self.locate(steps, start.checked_sub(*offset)?)?;
+        let (last, last_within) = self.locate(steps, end - 1 - offset)?;
+        let walk = steps[first..=last].iter().map(|step| format!("{}{}", if step.reverse { '<' } else { '>' }, self.nodes[step.node])).collect();
+        Some(GraphInterval {
+            first: self.position(steps, first, first_within, reverse_hit),
+            last: self.position(steps, last, last_within, reverse_hit),
+            walk,
+        })
+    }
+}
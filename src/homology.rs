@@ -0,0 +1,59 @@
+// `--homology`: hits that are the same site in different assemblies, found through alignments
+// between them such as a wfmash all-vs-all PAF. A hit whose projection overlaps a hit of the
+// same query and strand on the other side is joined with it, and every hit joined so, directly
+// or through others, belongs to one site.
+
+use crate::intervals::IntervalIndex;
+use crate::liftover::Liftover;
+use crate::search::Hit;
+use std::collections::HashMap;
+
+pub struct Homology {
+    alignments: Liftover,
+}
+
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+impl Homology {
+    // Alignments read as for `Liftover`: PAF when the name ends in `.paf`, otherwise chain.
+    pub fn from_path(path: &str) -> Result<Homology, String> {
+        Ok(Homology { alignments: Liftover::from_path(path)? })
+    }
+
+    // For (seq_name, hit) rows, the row leading the site of each: the first of its members by
+    // seq_name, start and strand, so the choice does not depend on the order of the rows.
+    pub fn sites(&self, rows: &[(&str, &Hit)]) -> Vec<usize> {
+        let mut by_contig: HashMap<String, Vec<(usize, usize, usize)>> = HashMap::new();
+        for (i, (seq_name, hit)) in rows.iter().enumerate() {
+            by_contig.entry(seq_name.to_string()).or_default().push((hit.start, hit.end, i));
+        }
+        let index = IntervalIndex::new(by_contig);
+        let mut parents: Vec<usize> = (0..rows.len()).collect();
+        for (i, (seq_name, hit)) in rows.iter().enumerate() {
+            let Some(p) = self.alignments.project(seq_name, hit.strand.as_str(), hit.start, hit.end) else {
+                continue;
+            };
+            for &(_, _, j) in index.overlapping(p.seq_name, p.start, p.end) {
+                if rows[j].1.query == hit.query && rows[j].1.strand.as_str() == p.strand {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+        let key = |i: usize| (rows[i].0, rows[i].1.start, rows[i].1.strand);
+        let mut leaders: HashMap<usize, usize> = HashMap::new();
+        for i in 0..rows.len() {
+            let leader = leaders.entry(root(&mut parents, i)).or_insert(i);
+            if key(i) < key(*leader) {
+                *leader = i;
+            }
+        }
+        (0..rows.len()).map(|i| leaders[&root(&mut parents, i)]).collect()
+    }
+}
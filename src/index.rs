@@ -0,0 +1,85 @@
+// `index`: build lookup structures next to the FASTA that later searches can use.
+
+use hamming_fasta::{bloom, minimizer, SearchConfig, Searcher};
+
+#[derive(clap::Args, Debug)]
+pub struct IndexArgs {
+    /// Path to the FASTA file
+    #[arg(short, long)]
+    fasta: String,
+
+    /// Build per-contig Bloom filters of reference k-mers (FASTA.bloom), used by searches with --bloom
+    #[arg(long, default_value = "false")]
+    bloom: bool,
+
+    /// k-mer length of the --bloom filters; queries are prefiltered when each of their distance + 1 pieces is this long
+    #[arg(long, default_value_t = 16)]
+    bloom_k: usize,
+
+    /// Filter bits per reference base; more bits mean fewer contigs scanned needlessly
+    #[arg(long, default_value_t = 8.0)]
+    bloom_bits: f64,
+
+    /// Build per-contig minimizer sketches (FASTA.minimizers), used by searches with --minimizers
+    #[arg(long, default_value = "false")]
+    minimizers: bool,
+
+    /// k-mer length of the --minimizers sketches
+    #[arg(long, default_value_t = 13)]
+    minimizer_k: usize,
+
+    /// Window of the --minimizers sketches in k-mers; queries are prefiltered when each of their distance + 1 pieces spans k + w - 1 bases
+    #[arg(long, default_value_t = 8)]
+    minimizer_w: usize,
+
+    /// Append sketches of this FASTA's contigs to the existing --bloom and --minimizers files of --fasta instead of rebuilding them; --fasta's .fai must already list them
+    #[arg(long)]
+    add: Option<String>,
+
+    /// Number of threads for parallel execution
+    #[arg(short = 't', long, default_value = "0")]
+    parallelism: usize,
+}
+
+pub fn run(args: &IndexArgs) {
+    if args.parallelism > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.parallelism).build_global().unwrap();
+    }
+    if !args.bloom && !args.minimizers {
+        eprintln!("error: nothing to build; pass --bloom or --minimizers");
+        std::process::exit(1);
+    }
+    let searcher = Searcher::new(&args.fasta, SearchConfig::default()).unwrap();
+    let mut built = Vec::new();
+    if let Some(added) = &args.add {
+        let contigs = Searcher::new(added, SearchConfig::default()).unwrap().contigs().to_vec();
+        // the search reads the added contigs from --fasta, so they must be the same there
+        if let Some((seq_name, length)) = contigs.iter().find(|(seq_name, length)| searcher.length(seq_name) != Some(*length)) {
+            eprintln!("error: {} ({} bp) is not in {}.fai with that length; add the sequences to {} and index it first", seq_name, length, args.fasta, args.fasta);
+            std::process::exit(1);
+        }
+        if args.bloom {
+            built.push(bloom::add(&args.fasta, added, &contigs, args.bloom_bits).map(|_| bloom::filter_path(&args.fasta)));
+        }
+        if args.minimizers {
+            built.push(minimizer::add(&args.fasta, added, &contigs).map(|_| minimizer::sketch_path(&args.fasta)));
+        }
+    } else {
+        if args.bloom {
+            built.push(bloom::build(&args.fasta, searcher.contigs(), args.bloom_k, args.bloom_bits).map(|_| bloom::filter_path(&args.fasta)));
+        }
+        if args.minimizers {
+            built.push(minimizer::build(&args.fasta, searcher.contigs(), args.minimizer_k, args.minimizer_w).map(|_| minimizer::sketch_path(&args.fasta)));
+        }
+    }
+    for result in built {
+        match result {
+            Ok(path) if args.add.is_some() => eprintln!("updated {}", path),
+            Ok(path) => eprintln!("wrote {}", path),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// Overlap lookups in annotation intervals, shared by the features that annotate hits. Each
+// contig's intervals are sorted by start and laid out as an implicit augmented interval tree
+// (as in cgranges): the interval at index i is a node whose level is the number of trailing
+// 1 bits of i, holding the largest end in its subtree. A lookup costs O(log n) plus the number
+// of intervals found, however long or nested the intervals are.
+
+use std::collections::HashMap;
+
+struct Tree<T> {
+    // (start, end, value), sorted by start
+    intervals: Vec<(usize, usize, T)>,
+    // largest end in the subtree of each node
+    max_end: Vec<usize>,
+    root_level: u32,
+}
+
+impl<T> Tree<T> {
+    fn new(mut intervals: Vec<(usize, usize, T)>) -> Tree<T> {
+        // stable, so intervals starting together keep their order
+        intervals.sort_by_key(|&(start, _, _)| start);
+        let n = intervals.len();
+        let mut max_end: Vec<usize> = intervals.iter().map(|&(_, end, _)| end).collect();
+        // the largest end under the last node of each level, standing in for missing right children
+        let (mut last_i, mut last) = (0, 0);
+        for i in (0..n).step_by(2) {
+            (last_i, last) = (i, max_end[i]);
+        }
+        let mut level = 1;
+        while 1 << level <= n {
+            let x = 1 << (level - 1);
+            for i in ((x << 1) - 1..n).step_by(x << 2) {
+                let right = if i + x < n { max_end[i + x] } else { last };
+                max_end[i] = max_end[i].max(max_end[i - x]).max(right);
+            }
+            last_i = if last_i >> level & 1 == 1 { last_i - x } else { last_i + x };
+            if last_i < n {
+                last = last.max(max_end[last_i]);
+            }
+            level += 1;
+        }
+        Tree { intervals, max_end, root_level: level - 1 }
+    }
+
+    fn overlapping<'a>(&'a self, start: usize, end: usize, found: &mut Vec<&'a (usize, usize, T)>) {
+        let n = self.intervals.len();
+        if n == 0 {
+            return;
+        }
+        // (level, node, whether its left subtree is done), visited in order
+        let mut stack = vec![(self.root_level, (1usize << self.root_level) - 1, false)];
+        while let Some((level, node, left_done)) = stack.pop() {
+            if level <= 3 {
+                // small subtrees are scanned
+                let first = node >> level << level;
+                let last = (first + (1 << (level + 1)) - 1).min(n);
+                found.extend(self.intervals[first..last].iter().take_while(|interval| interval.0 < end).filter(|interval| interval.1 > start));
+            } else if !left_done {
+                stack.push((level, node, true));
+                let left = node - (1 << (level - 1));
+                if left >= n || self.max_end[left] > start {
+                    stack.push((level - 1, left, false));
+                }
+            } else if node < n && self.intervals[node].0 < end {
+                if self.intervals[node].1 > start {
+                    found.push(&self.intervals[node]);
+                }
+                stack.push((level - 1, node + (1 << (level - 1)), false));
+            }
+        }
+    }
+}
+
+pub struct IntervalIndex<T> {
+    trees: HashMap<String, Tree<T>>,
+}
+
+impl<T> IntervalIndex<T> {
+    // Indexes the (start, end, value) intervals of each contig, 0-based and half-open.
+    pub fn new(intervals: HashMap<String, Vec<(usize, usize, T)>>) -> IntervalIndex<T> {
+        IntervalIndex { trees: intervals.into_iter().map(|(seq_name, intervals)| (seq_name, Tree::new(intervals))).collect() }
+    }
+
+    // The intervals of `seq_name` overlapping [start, end), in order of start.
+    pub fn overlapping(&self, seq_name: &str, start: usize, end: usize) -> Vec<&(usize, usize, T)> {
+        let mut found = Vec::new();
+        if let Some(tree) = self.trees.get(seq_name) {
+            tree.overlapping(start, end, &mut found);
+        }
+        found
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub mod aliases;
+pub mod autotune;
+pub mod base_edit;
+pub mod bloom;
+pub mod cache;
+pub mod consensus;
+pub mod contig_cache;
+pub mod dedup;
+pub mod gaps;
+pub mod graph;
+pub mod homology;
+pub mod intervals;
+pub mod library;
+pub mod liftover;
+pub mod mapped;
+pub mod matrix;
+pub mod md5;
+pub mod minimizer;
+pub mod offtarget;
+pub mod pansn;
+pub mod population;
+pub mod probe;
+pub mod regions;
+pub mod repeats;
+pub mod rng;
+pub mod search;
+pub mod seed;
+pub mod shuffle;
+pub mod sketch;
+pub mod sliced;
+pub mod stats;
+pub mod stream;
+pub mod tm;
+pub mod validate;
+
+pub use search::{Hit, Query, SearchConfig, Searcher, Strand};
+
+// What to do with names occurring more than once in the index, as when FASTAs and their
+// indexes were concatenated. faidx only reaches the first record of a name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicateNames {
+    // search the first record of each name and ignore the others
+    First,
+    #[default]
+    Error,
+    // rename later records `<name>_2`, `<name>_3`, ... so that every record is searched
+    Suffix,
+}
+
+impl std::str::FromStr for DuplicateNames {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DuplicateNames, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "first" => Ok(DuplicateNames::First),
+            "error" => Ok(DuplicateNames::Error),
+            "suffix" => Ok(DuplicateNames::Suffix),
+            _ => Err(format!("unknown duplicate name handling {:?}, expected first, error or suffix", s)),
+        }
+    }
+}
+
+// How windows are compared. `Auto` seeds long queries where that cannot miss a hit (see
+// `seed`) and compares the others window by window; `Windows` compares every window and
+// `Pigeonhole` seeds every query it can, however short its pieces. `Simd` compares the queries
+// of a pass bit-sliced (see `sliced`), as auto does for passes of several short queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Backend {
+    #[default]
+    Auto,
+    Windows,
+    Pigeonhole,
+    Simd,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Backend, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Backend::Auto),
+            "naive" | "windows" => Ok(Backend::Windows),
+            "pigeonhole" => Ok(Backend::Pigeonhole),
+            "simd" => Ok(Backend::Simd),
+            // engines other tools offer, named so that asking for one says it is missing
+            "bitap" | "fm-index" => Err(format!("backend {:?} is not available in this build, expected auto, naive, pigeonhole or simd", s)),
+            _ => Err(format!("unknown backend {:?}, expected auto, naive, pigeonhole or simd", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Backend::Auto => "auto",
+            Backend::Windows => "naive",
+            Backend::Pigeonhole => "pigeonhole",
+            Backend::Simd => "simd",
+        })
+    }
+}
+
+// What the reference and queries are spelled in. Proteins have a single strand and no PAM,
+// and windows are compared residue by residue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alphabet {
+    #[default]
+    Dna,
+    Protein,
+}
+
+impl std::str::FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Alphabet, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "dna" => Ok(Alphabet::Dna),
+            "protein" => Ok(Alphabet::Protein),
+            _ => Err(format!("unknown alphabet {:?}, expected dna or protein", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Alphabet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Alphabet::Dna => "dna",
+            Alphabet::Protein => "protein",
+        })
+    }
+}
+
+// Names occurring more than once in `path.fai`, in index order.
+pub fn duplicate_fai_names(path: &str) -> Result<Vec<String>, String> {
+    let fai_path = format!("{}.fai", path);
+    let file = File::open(&fai_path).map_err(|e| format!("{}: {}", fai_path, e))?;
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("{}: {}", fai_path, e))?;
+        let name = line.split_whitespace().next().unwrap_or_default().to_string();
+        if !seen.insert(name.clone()) && !duplicates.contains(&name) {
+            duplicates.push(name);
+        }
+    }
+    Ok(duplicates)
+}
+
+// A copy of `fasta` in `dir` whose index renames the later records of each duplicate name
+// `<name>_2`, `<name>_3`, ..., skipping names already taken. The FASTA (and a bgzip .gzi)
+// are linked, not copied; returns the path to open instead of `fasta`.
+pub fn suffixed_index(fasta: &str, dir: &Path) -> Result<String, String> {
+    let fai_path = format!("{}.fai", fasta);
+    let index = std::fs::read_to_string(&fai_path).map_err(|e| format!("{}: {}", fai_path, e))?;
+    let mut taken: HashSet<String> = index.lines().filter_map(|line| line.split('\t').next()).map(str::to_string).collect();
+    let (mut seen, mut renamed) = (HashSet::new(), String::new());
+    for line in index.lines().filter(|line| !line.is_empty()) {
+        let (name, rest) = line.split_once('\t').ok_or_else(|| format!("{}: not an index line: {}", fai_path, line))?;
+        let mut unique = name.to_string();
+        if !seen.insert(name.to_string()) {
+            unique = (2..).map(|i| format!("{}_{}", name, i)).find(|candidate| !taken.contains(candidate)).unwrap();
+            taken.insert(unique.clone());
+        }
+        renamed.push_str(&format!("{}\t{}\n", unique, rest));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let source = std::fs::canonicalize(fasta).map_err(|e| format!("{}: {}", fasta, e))?;
+    let linked = dir.join(source.file_name().unwrap());
+    std::os::unix::fs::symlink(&source, &linked).map_err(|e| format!("{}: {}", linked.display(), e))?;
+    let gzi = format!("{}.gzi", source.display());
+    if Path::new(&gzi).exists() {
+        let linked_gzi = format!("{}.gzi", linked.display());
+        std::os::unix::fs::symlink(&gzi, &linked_gzi).map_err(|e| format!("{}: {}", linked_gzi, e))?;
+    }
+    let linked_fai = format!("{}.fai", linked.display());
+    std::fs::write(&linked_fai, renamed).map_err(|e| format!("{}: {}", linked_fai, e))?;
+    Ok(linked.to_str().unwrap().to_string())
+}
+
+// The length of each contig in the `.fai` of `path`.
+pub fn load_fai(path: &str) -> Result<HashMap<String, usize>, String> {
+    let fai_path = format!("{}.fai", path);
+    let file = File::open(&fai_path).map_err(|e| format!("{}: {}", fai_path, e))?;
+    let reader = BufReader::new(file);
+
+    let mut sequences = HashMap::new();
+
+    for (n, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("{}: {}", fai_path, e))?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let (name, length) = match parts[..] {
+            [] => continue,
+            [_] => return Err(format!("{}: line {}: expected a name and a length", fai_path, n + 1)),
+            [name, length, ..] => (name, length),
+        };
+        let length: usize = length.parse().map_err(|_| format!("{}: line {}: bad length {} of {}", fai_path, n + 1, length, name))?;
+        // faidx only reaches the first record of a name, see `DuplicateNames`
+        sequences.entry(name.to_string()).or_insert(length);
+    }
+
+    Ok(sequences)
+}
+
+pub fn hamming_distance(s1: &str, s2: &str) -> usize {
+    s1.chars().zip(s2.chars()).filter(|&(c1, c2)| c1 != c2).count()
+}
+
+// U and T are the same base for matching purposes.
+pub fn to_dna(seq: &str) -> String {
+    seq.replace('U', "T").replace('u', "t")
+}
+
+pub fn to_rna(seq: &str) -> String {
+    seq.replace('T', "U").replace('t', "u")
+}
+
+// Complements the full IUPAC alphabet, keeping soft-masked (lowercase) bases lowercase.
+pub fn get_reverse_complement(seq: &str) -> String {
+    let mut rev_comp = String::with_capacity(seq.len());
+    for nucleotide in seq.chars().rev() {
+        let complement = match nucleotide.to_ascii_uppercase() {
+            'A' => 'T',
+            'T' | 'U' => 'A',
+            'G' => 'C',
+            'C' => 'G',
+            'R' => 'Y',
+            'Y' => 'R',
+            'K' => 'M',
+            'M' => 'K',
+            'B' => 'V',
+            'V' => 'B',
+            'D' => 'H',
+            'H' => 'D',
+            // S, W and N are their own complements
+            c @ ('S' | 'W' | 'N') => c,
+            _ => 'N',
+        };
+        if nucleotide.is_ascii_lowercase() {
+            rev_comp.push(complement.to_ascii_lowercase());
+        } else {
+            rev_comp.push(complement);
+        }
+    }
+    rev_comp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fai_lengths_and_errors() {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-fai-{}.fa", std::process::id()));
+        let fasta = path.to_str().unwrap();
+        let fai = format!("{}.fai", fasta);
+        let load = |index: &str| {
+            std::fs::write(&fai, index).unwrap();
+            load_fai(fasta)
+        };
+        let lengths = load("chr1\t100\t6\t60\t61\nchr2\t5\t120\t60\t61\n\nchr1\t7\t200\t60\t61\n");
+        assert_eq!(lengths, Ok(HashMap::from([("chr1".to_string(), 100), ("chr2".to_string(), 5)])));
+        assert_eq!(load("chr1\t100\t6\t60\t61\nchr2\n"), Err(format!("{}: line 2: expected a name and a length", fai)));
+        assert_eq!(load("chr1\t-3\t6\t60\t61\n"), Err(format!("{}: line 1: bad length -3 of chr1", fai)));
+        std::fs::remove_file(&fai).unwrap();
+        assert!(load_fai(fasta).unwrap_err().starts_with(&format!("{}: ", fai)));
+    }
+
+    #[test]
+    fn reverse_complement_covers_iupac() {
+        let complements = [
+            ("A", "T"),
+            ("T", "A"),
+            ("G", "C"),
+            ("C", "G"),
+            ("R", "Y"),
+            ("Y", "R"),
+            ("K", "M"),
+            ("M", "K"),
+            ("B", "V"),
+            ("V", "B"),
+            ("D", "H"),
+            ("H", "D"),
+            ("S", "S"),
+            ("W", "W"),
+            ("N", "N"),
+            // RNA reads as DNA: U pairs with A, which complements to T
+            ("U", "A"),
+            ("u", "a"),
+            // soft-masking is kept
+            ("acgtrykmbvdhswn", "nwsdhbvkmryacgt"),
+            ("ACgtNn", "nNacGT"),
+            // anything else is unknown
+            ("X-*", "NNN"),
+            ("", ""),
+        ];
+        for (seq, complement) in complements {
+            assert_eq!(get_reverse_complement(seq), complement, "{:?}", seq);
+        }
+    }
+
+    #[test]
+    fn reverse_complement_twice_is_the_input() {
+        for seq in ["ACGTRYKMBVDHSWN", "acgtrykmbvdhswn", "AaCcGgTtNnRrYy", "GATTACA"] {
+            assert_eq!(get_reverse_complement(&get_reverse_complement(seq)), seq);
+        }
+        // U does not come back, as its complement is written as DNA
+        assert_eq!(get_reverse_complement(&get_reverse_complement("ACGU")), "ACGT");
+    }
+}
@@ -0,0 +1,183 @@
+// Guide libraries exported by common designers, read into named queries.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LibraryFormat {
+    // `name,sequence` per line, optionally with a header
+    Csv,
+    // CRISPick (GPP sgRNA designer) output TSV
+    Crispick,
+    // FlashFry `discover`/`score` output TSV
+    FlashFry,
+}
+
+impl FromStr for LibraryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LibraryFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(LibraryFormat::Csv),
+            "crispick" => Ok(LibraryFormat::Crispick),
+            "flashfry" => Ok(LibraryFormat::FlashFry),
+            _ => Err(format!("unknown library format {:?}, expected csv, crispick or flashfry", s)),
+        }
+    }
+}
+
+pub struct Guide {
+    pub name: String,
+    // protospacer only; the PAM is checked by the search when `cas9` is set
+    pub sequence: String,
+    pub cas9: bool,
+}
+
+fn is_sequence(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b"ACGTUNacgtun".contains(&b))
+}
+
+// Splits an NGG PAM off a target; other PAMs cannot be searched.
+fn split_pam(target: &str, line: &str) -> Result<String, String> {
+    let upper = target.to_ascii_uppercase();
+    if upper.len() > 3 && upper.ends_with("GG") {
+        Ok(upper[..upper.len() - 3].to_string())
+    } else {
+        Err(format!("only NGG PAMs are supported: {}", line))
+    }
+}
+
+// `cas9` applies to formats without PAM information (CSV). Errors name the file and line.
+pub fn load(path: &str, format: LibraryFormat, cas9: bool) -> Result<Vec<Guide>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut lines = BufReader::new(file).lines();
+    let at = |i: usize, error: String| format!("{}: line {}: {}", path, i + 1, error);
+    let mut guides = Vec::new();
+    if format == LibraryFormat::Csv {
+        for (i, line) in lines.enumerate() {
+            let line = line.map_err(|e| at(i, e.to_string()))?;
+            let fields: Vec<&str> = line.split([',', '\t']).map(str::trim).collect();
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if fields.len() < 2 {
+                return Err(at(i, format!("expected name,sequence: {}", line)));
+            }
+            // a header line has no sequence in its second column
+            if guides.is_empty() && !is_sequence(fields[1]) {
+                continue;
+            }
+            if !is_sequence(fields[1]) {
+                return Err(at(i, format!("not a nucleotide sequence: {}", line)));
+            }
+            guides.push(Guide { name: fields[0].to_string(), sequence: fields[1].to_string(), cas9 });
+        }
+        return Ok(guides);
+    }
+
+    let header = match lines.next() {
+        Some(line) => line.map_err(|e| at(0, e.to_string()))?,
+        None => return Ok(guides),
+    };
+    let columns: Vec<&str> = header.split('\t').map(str::trim).collect();
+    for (n, line) in lines.enumerate() {
+        // the header is line 1
+        let line = line.map_err(|e| at(n + 1, e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let guide = tsv_guide(format, &columns, &line, n, cas9).map_err(|e| at(n + 1, e))?;
+        if !is_sequence(&guide.sequence) {
+            return Err(at(n + 1, format!("not a nucleotide sequence: {}", line)));
+        }
+        guides.push(guide);
+    }
+    Ok(guides)
+}
+
+// The guide of the `n`th line after the header of a designer's TSV.
+fn tsv_guide(format: LibraryFormat, columns: &[&str], line: &str, n: usize, cas9: bool) -> Result<Guide, String> {
+    let column = |name: &str| columns.iter().position(|c| c.eq_ignore_ascii_case(name));
+    let required = |name: &str| column(name).ok_or_else(|| format!("missing column {:?}", name));
+    let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+    let field = |i: usize| fields.get(i).copied().ok_or_else(|| format!("truncated line: {}", line));
+    Ok(match format {
+        LibraryFormat::Crispick => {
+            let sequence = field(required("sgRNA Sequence")?)?;
+            let label = match column("Target Gene Symbol").or(column("Input")) {
+                Some(i) => field(i)?.to_string(),
+                None => format!("guide{}", n + 1),
+            };
+            let name = match column("Pick Order") {
+                Some(i) => format!("{}_{}", label, field(i)?),
+                None => label,
+            };
+            match column("PAM Sequence") {
+                Some(i) => {
+                    split_pam(&format!("{}{}", sequence, field(i)?), line)?;
+                    Guide { name, sequence: sequence.to_string(), cas9: true }
+                }
+                None => Guide { name, sequence: sequence.to_string(), cas9 },
+            }
+        }
+        LibraryFormat::FlashFry => {
+            // targets carry their PAM; name them by where the designer found them
+            let sequence = split_pam(field(required("target")?)?, line)?;
+            let mut name = format!("{}:{}-{}", field(required("contig")?)?, field(required("start")?)?, field(required("stop")?)?);
+            if let Some(i) = column("orientation") {
+                name = format!("{}:{}", name, field(i)?);
+            }
+            Guide { name, sequence, cas9: true }
+        }
+        LibraryFormat::Csv => unreachable!(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_text(name: &str, text: &str, format: LibraryFormat, cas9: bool) -> Result<Vec<(String, String, bool)>, String> {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-library-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let guides = load(path.to_str().unwrap(), format, cas9);
+        std::fs::remove_file(&path).unwrap();
+        // errors without the temporary path
+        guides.map(|guides| guides.into_iter().map(|g| (g.name, g.sequence, g.cas9)).collect()).map_err(|e| e.replacen(path.to_str().unwrap(), name, 1))
+    }
+
+    fn guide(name: &str, sequence: &str, cas9: bool) -> (String, String, bool) {
+        (name.to_string(), sequence.to_string(), cas9)
+    }
+
+    #[test]
+    fn formats_are_read() {
+        let csv = "name,sequence\n# a comment\n\ng1, ACGTACGTACGTACGTACGT\ng2\tacguacguacguacguacgu\n";
+        assert_eq!(load_text("a.csv", csv, LibraryFormat::Csv, true), Ok(vec![guide("g1", "ACGTACGTACGTACGTACGT", true), guide("g2", "acguacguacguacguacgu", true)]));
+        // without a header
+        assert_eq!(load_text("b.csv", "g1,ACGT\n", LibraryFormat::Csv, false), Ok(vec![guide("g1", "ACGT", false)]));
+        let crispick = "Input\tTarget Gene Symbol\tsgRNA Sequence\tPAM Sequence\tPick Order\nX\tGENE1\tAGTAGTTTGTCTTCGCG\tCGG\t1\n\nX\tGENE1\tACTGGACTTATTTCCCG\tAGG\t2\n";
+        assert_eq!(load_text("c.tsv", crispick, LibraryFormat::Crispick, false), Ok(vec![guide("GENE1_1", "AGTAGTTTGTCTTCGCG", true), guide("GENE1_2", "ACTGGACTTATTTCCCG", true)]));
+        // without PAM, gene or pick order columns
+        assert_eq!(load_text("d.tsv", "sgRNA Sequence\nACGTACGT\n", LibraryFormat::Crispick, false), Ok(vec![guide("guide1", "ACGTACGT", false)]));
+        let flashfry = "contig\tstart\tstop\ttarget\tcontext\toverflow\torientation\nchr1\t10\t33\tAGTAGTTTGTCTTCGCGCGG\tx\tOK\tFWD\n";
+        assert_eq!(load_text("e.tsv", flashfry, LibraryFormat::FlashFry, false), Ok(vec![guide("chr1:10-33:FWD", "AGTAGTTTGTCTTCGCG", true)]));
+        assert_eq!(load_text("f.tsv", "", LibraryFormat::FlashFry, false), Ok(vec![]));
+        assert_eq!("FlashFry".parse(), Ok(LibraryFormat::FlashFry));
+        assert_eq!("gpp".parse::<LibraryFormat>(), Err("unknown library format \"gpp\", expected csv, crispick or flashfry".to_string()));
+    }
+
+    #[test]
+    fn errors_name_the_file_and_line() {
+        let error = |name: &str, text: &str, format| load_text(name, text, format, false).unwrap_err();
+        assert_eq!(error("a.csv", "g1,ACGT\ng2\n", LibraryFormat::Csv), "a.csv: line 2: expected name,sequence: g2");
+        assert_eq!(error("a.csv", "g1,ACGT\ng2,ACXT\n", LibraryFormat::Csv), "a.csv: line 2: not a nucleotide sequence: g2,ACXT");
+        assert_eq!(error("c.tsv", "Input\tPAM Sequence\nX\tCGG\n", LibraryFormat::Crispick), "c.tsv: line 2: missing column \"sgRNA Sequence\"");
+        assert_eq!(error("c.tsv", "sgRNA Sequence\tPAM Sequence\nACGTACGT\tCAA\n", LibraryFormat::Crispick), "c.tsv: line 2: only NGG PAMs are supported: ACGTACGT\tCAA");
+        assert_eq!(error("c.tsv", "sgRNA Sequence\tPAM Sequence\nACGTACGT\n", LibraryFormat::Crispick), "c.tsv: line 2: truncated line: ACGTACGT");
+        assert_eq!(error("c.tsv", "sgRNA Sequence\nACGT\nAC-T\n", LibraryFormat::Crispick), "c.tsv: line 3: not a nucleotide sequence: AC-T");
+        assert_eq!(error("e.tsv", "contig\tstart\tstop\tcontext\nchr1\t1\t2\tx\n", LibraryFormat::FlashFry), "e.tsv: line 2: missing column \"target\"");
+        assert!(load("/nonexistent.csv", LibraryFormat::Csv, false).err().unwrap().starts_with("/nonexistent.csv: "));
+    }
+}
@@ -0,0 +1,254 @@
+// Projection of hit coordinates through pairwise alignments (UCSC chain, or PAF with
+// cg:Z CIGARs) from each assembly onto a reference.
+
+use crate::intervals::IntervalIndex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// an aligned block, indexed by its source interval
+struct Block {
+    dst_name: usize,
+    // dst = offset + src on the forward orientation, offset - src when reversed
+    offset: i64,
+    reverse: bool,
+}
+
+impl Block {
+    fn project(&self, pos: usize) -> usize {
+        if self.reverse {
+            (self.offset - pos as i64) as usize
+        } else {
+            (self.offset + pos as i64) as usize
+        }
+    }
+}
+
+// aligned blocks per source contig, as (source start, source end, block)
+type Blocks = HashMap<String, Vec<(usize, usize, Block)>>;
+
+pub struct Liftover {
+    dst_names: Vec<String>,
+    blocks: IntervalIndex<Block>,
+}
+
+pub struct Projection<'a> {
+    pub seq_name: &'a str,
+    pub strand: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Liftover {
+    pub fn from_path(path: &str) -> Result<Liftover, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let reader = BufReader::new(file);
+        let mut liftover = Liftover { dst_names: Vec::new(), blocks: IntervalIndex::new(HashMap::new()) };
+        let (mut dst_ids, mut blocks) = (HashMap::new(), HashMap::new());
+        if path.ends_with(".paf") {
+            liftover.read_paf(reader, &mut dst_ids, &mut blocks)?;
+        } else {
+            liftover.read_chain(reader, &mut dst_ids, &mut blocks)?;
+        }
+        liftover.blocks = IntervalIndex::new(blocks);
+        Ok(liftover)
+    }
+
+    fn dst_id(&mut self, dst_ids: &mut HashMap<String, usize>, name: &str) -> usize {
+        *dst_ids.entry(name.to_string()).or_insert_with(|| {
+            self.dst_names.push(name.to_string());
+            self.dst_names.len() - 1
+        })
+    }
+
+    fn read_chain<R: BufRead>(&mut self, reader: R, dst_ids: &mut HashMap<String, usize>, blocks: &mut Blocks) -> Result<(), String> {
+        // state of the chain being read: source contig, destination id, positions, strand, dest size
+        let mut current: Option<(String, usize, usize, usize, bool, usize)> = None;
+        for line in reader.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() || fields[0].starts_with('#') {
+                continue;
+            }
+            if fields[0] == "chain" {
+                if fields.len() < 12 {
+                    return Err(format!("malformed chain header: {}", line));
+                }
+                let parse = |s: &str| s.parse::<usize>().map_err(|_| format!("malformed chain header: {}", line));
+                let dst = self.dst_id(dst_ids, fields[7]);
+                current = Some((fields[2].to_string(), dst, parse(fields[5])?, parse(fields[10])?, fields[9] == "-", parse(fields[8])?));
+                continue;
+            }
+            let Some((src, dst, t, q, reverse, q_size)) = current.as_mut() else {
+                return Err(format!("chain block outside of a chain: {}", line));
+            };
+            let parse = |s: &str| s.parse::<usize>().map_err(|_| format!("malformed chain block: {}", line));
+            let size = parse(fields[0])?;
+            let offset = if *reverse {
+                // q positions are counted on the reverse complement of the destination
+                (*q_size - 1 - *q + *t) as i64
+            } else {
+                *q as i64 - *t as i64
+            };
+            blocks.entry(src.clone()).or_default().push((*t, *t + size, Block { dst_name: *dst, offset, reverse: *reverse }));
+            *t += size;
+            *q += size;
+            if fields.len() >= 3 {
+                *t += parse(fields[1])?;
+                *q += parse(fields[2])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_paf<R: BufRead>(&mut self, reader: R, dst_ids: &mut HashMap<String, usize>, blocks: &mut Blocks) -> Result<(), String> {
+        let mut skipped = 0;
+        for line in reader.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 12 {
+                continue;
+            }
+            let parse = |s: &str| s.parse::<usize>().map_err(|_| format!("malformed PAF record: {}", line));
+            let (q_len, q_start, q_end) = (parse(fields[1])?, parse(fields[2])?, parse(fields[3])?);
+            let (t_start, t_end) = (parse(fields[7])?, parse(fields[8])?);
+            let reverse = fields[4] == "-";
+            let cigar = fields[12..].iter().find_map(|tag| tag.strip_prefix("cg:Z:"));
+            let cigar = match cigar {
+                Some(cigar) => cigar.to_string(),
+                // without a CIGAR only an ungapped alignment can be projected exactly
+                None if q_end - q_start == t_end - t_start => format!("{}M", q_end - q_start),
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let dst = self.dst_id(dst_ids, fields[5]);
+            let blocks = blocks.entry(fields[0].to_string()).or_default();
+            // on the reverse strand the CIGAR walks the reverse complement of the source
+            let mut q = if reverse { q_len - q_end } else { q_start };
+            let mut t = t_start;
+            let mut count = 0;
+            for c in cigar.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    count = count * 10 + d as usize;
+                    continue;
+                }
+                match c {
+                    'M' | '=' | 'X' => {
+                        let block = if reverse {
+                            let src_start = q_len - q - count;
+                            (src_start, src_start + count, Block { dst_name: dst, offset: (t + q_len - 1 - q) as i64, reverse })
+                        } else {
+                            (q, q + count, Block { dst_name: dst, offset: t as i64 - q as i64, reverse })
+                        };
+                        blocks.push(block);
+                        q += count;
+                        t += count;
+                    }
+                    'I' => q += count,
+                    'D' => t += count,
+                    _ => return Err(format!("unsupported CIGAR operation '{}' in PAF record", c)),
+                }
+                count = 0;
+            }
+        }
+        if skipped > 0 {
+            eprintln!("warning: skipped {} gapped PAF records without a cg:Z tag", skipped);
+        }
+        Ok(())
+    }
+
+    fn find(&self, seq_name: &str, pos: usize) -> Option<&Block> {
+        // blocks may overlap when several alignments cover a region; take the last starting one
+        self.blocks.overlapping(seq_name, pos, pos + 1).last().map(|(_, _, block)| block)
+    }
+
+    // Projects [start, end) of a hit on `strand`; both ends must land in the same
+    // destination contig and orientation.
+    pub fn project(&self, seq_name: &str, strand: &str, start: usize, end: usize) -> Option<Projection<'_>> {
+        let first = self.find(seq_name, start)?;
+        let last = self.find(seq_name, end - 1)?;
+        if first.dst_name != last.dst_name || first.reverse != last.reverse {
+            return None;
+        }
+        let (a, b) = (first.project(start), last.project(end - 1));
+        let (start, end) = (a.min(b), a.max(b) + 1);
+        let strand = match (strand, first.reverse) {
+            ("+", false) | ("-", true) => "+",
+            _ => "-",
+        };
+        Some(Projection { seq_name: &self.dst_names[first.dst_name], strand, start, end })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Chains from asm1 onto the forward strand of ref1, with a gap of 5 bases in asm1 and 10 in
+    // ref1 between its blocks, and from asm2 onto the reverse strand of ref2 (50 bases long).
+    const CHAIN: &str = "\
+chain 100 asm1 100 + 10 60 ref1 200 + 20 75 1
+20 5 10
+25
+
+chain 100 asm2 100 + 0 30 ref2 50 - 5 40 2
+10 0 5
+20
+";
+
+    // asm3 onto ref3 forward and asm4 onto ref4 reversed, each as 10M5D10I10M, and an ungapped
+    // asm5 without a CIGAR.
+    const PAF: &str = "\
+asm3\t100\t10\t40\t+\tref3\t200\t50\t75\t20\t30\t60\tcg:Z:10M5D10I10M
+asm4\t100\t20\t50\t-\tref4\t200\t100\t125\t20\t30\t60\tcg:Z:10M5D10I10M
+asm5\t50\t0\t20\t+\tref5\t100\t30\t50\t20\t20\t60
+";
+
+    fn liftover(name: &str, text: &str) -> Liftover {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-liftover-{}-{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        let liftover = Liftover::from_path(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        liftover
+    }
+
+    fn project<'a>(liftover: &'a Liftover, seq_name: &str, strand: &str, start: usize, end: usize) -> Option<(&'a str, &'static str, usize, usize)> {
+        liftover.project(seq_name, strand, start, end).map(|p| (p.seq_name, p.strand, p.start, p.end))
+    }
+
+
+    #[test]
+    fn chain_projection() {
+        let chain = liftover("asm.chain", CHAIN);
+        // asm1 [10, 30) is ref1 [20, 40) and asm1 [35, 60) is ref1 [50, 75)
+        assert_eq!(project(&chain, "asm1", "+", 12, 18), Some(("ref1", "+", 22, 28)));
+        assert_eq!(project(&chain, "asm1", "-", 40, 44), Some(("ref1", "-", 55, 59)));
+        assert_eq!(project(&chain, "asm1", "+", 31, 34), None);
+        assert_eq!(project(&chain, "asm1", "+", 5, 15), None);
+        assert_eq!(project(&chain, "asm1", "+", 58, 62), None);
+        // asm2 [0, 10) is [5, 15) of ref2's reverse strand, ref2 [35, 45), so asm2 2 is ref2 42
+        assert_eq!(project(&chain, "asm2", "+", 2, 6), Some(("ref2", "-", 39, 43)));
+        // asm2 [10, 30) is [20, 40) of the reverse strand, ref2 [10, 30)
+        assert_eq!(project(&chain, "asm2", "-", 20, 25), Some(("ref2", "+", 15, 20)));
+        assert_eq!(project(&chain, "ref1", "+", 20, 25), None);
+    }
+
+    #[test]
+    fn paf_projection() {
+        let paf = liftover("asm.paf", PAF);
+        // asm3 [10, 20) is ref3 [50, 60); [20, 30) is inserted; [30, 40) is ref3 [65, 75)
+        assert_eq!(project(&paf, "asm3", "+", 12, 16), Some(("ref3", "+", 52, 56)));
+        assert_eq!(project(&paf, "asm3", "-", 32, 36), Some(("ref3", "-", 67, 71)));
+        assert_eq!(project(&paf, "asm3", "+", 22, 28), None);
+        assert_eq!(project(&paf, "asm3", "+", 18, 22), None);
+        // the CIGAR walks asm4 from its end: [40, 50) is ref4 [100, 110) reversed, [30, 40) is
+        // inserted and [20, 30) is ref4 [115, 125) reversed
+        assert_eq!(project(&paf, "asm4", "+", 42, 46), Some(("ref4", "-", 104, 108)));
+        assert_eq!(project(&paf, "asm4", "-", 22, 26), Some(("ref4", "+", 119, 123)));
+        assert_eq!(project(&paf, "asm4", "+", 32, 38), None);
+        assert_eq!(project(&paf, "asm5", "+", 5, 9), Some(("ref5", "+", 35, 39)));
+    }
+}
@@ -1,31 +1,152 @@
-use clap::Parser;
-use rust_htslib::faidx::Reader;
-use std::collections::HashMap;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use hamming_fasta::aliases::Aliases;
+use hamming_fasta::base_edit::{self, BaseEditor, EditWindow};
+use hamming_fasta::library::{self, Guide, LibraryFormat};
+use hamming_fasta::graph::GraphPaths;
+use hamming_fasta::homology::Homology;
+use hamming_fasta::liftover::Liftover;
+use hamming_fasta::matrix::ScoringMatrix;
+use hamming_fasta::offtarget;
+use hamming_fasta::population::Population;
+use hamming_fasta::probe::{self, ProbeConstraints};
+use hamming_fasta::repeats::Repeats;
+use hamming_fasta::rng::Rng;
+use hamming_fasta::shuffle;
+use hamming_fasta::stats::{self, Background, Composition, KmerCounts, PatternPos};
+use hamming_fasta::tm::TmModel;
+use hamming_fasta::validate;
+use hamming_fasta::search::QueryBudget;
+use hamming_fasta::{autotune, get_reverse_complement, pansn, search, to_rna, Alphabet, Backend, DuplicateNames, Hit, Query, SearchConfig, Searcher, Strand};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::io::Write;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+mod bench;
+mod config;
+mod diff;
+mod index;
+mod manifest;
+mod mutation_scan;
+mod preset;
+mod read_support;
+mod reference_stats;
+mod report;
+mod rescore;
+mod sinks;
+mod spool;
+mod subset;
+mod vcf;
+
+use bench::BenchArgs;
+use diff::DiffArgs;
+use index::IndexArgs;
+use manifest::Manifest;
+use mutation_scan::MutationScanArgs;
+use preset::Preset;
+use read_support::ReadSupportArgs;
+use reference_stats::StatsArgs;
+use report::Report;
+use rescore::RescoreArgs;
+use sinks::{Collapse, HitsFasta, Output, Partitions, Profiles, Quotas, Rows, Scatter};
+use subset::SubsetArgs;
+use vcf::Vcf;
 
 /// Search for a specific sequence in the human pangenome
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    search: Option<Args>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Measure throughput and correctness of the search on synthetic sequences
+    Bench(BenchArgs),
+    /// Compare the hits of one query in two references: gained, lost and changed hits
+    Diff(DiffArgs),
+    /// Build lookup structures next to the FASTA: --bloom k-mer filters and --minimizers sketches
+    Index(IndexArgs),
+    /// Search every single-base mutant of a query and report how hit counts change per position
+    MutationScan(MutationScanArgs),
+    /// Count the reads of a BAM carrying the reference, the query or an indel at each hit site
+    ReadSupport(ReadSupportArgs),
+    /// Report the best window of a query within each interval of a BED file, without a genome-wide scan
+    Rescore(RescoreArgs),
+    /// Write statistics of the reference: --background-model k-mer counts for --expectation
+    Stats(StatsArgs),
+    /// Copy named contigs, PanSN samples and regions into a small indexed FASTA, such as a test fixture
+    Subset(SubsetArgs),
+}
+
+#[derive(clap::Args, Debug)]
 struct Args {
-    /// Path to the FASTA file
+    /// Path to the FASTA file, or - to read a plain or gzipped FASTA from stdin without an index
     #[arg(short, long)]
     fasta: String,
 
+    /// TOML or YAML file of search options and queries with their own distance, cas9 or pam, in the flat subset of either the README describes; flags given on the command line override it
+    #[arg(long)]
+    config: Option<String>,
+
     /// Target sequence to search for
-    #[arg(short, long)]
-    sequence: String,
+    #[arg(short, long, required_unless_present_any = ["library", "query_region", "query_from", "config"], conflicts_with_all = ["library", "query_region", "query_from"])]
+    sequence: Option<String>,
+
+    /// Search for the reference bases of this region (chr:start-end, 1-based inclusive) and report its other copies
+    #[arg(long, conflicts_with = "library")]
+    query_region: Option<String>,
+
+    /// Search for the bases of a region of another indexed FASTA, given as other.fa:chr:start-end (1-based inclusive)
+    #[arg(long, conflicts_with_all = ["library", "query_region"])]
+    query_from: Option<String>,
+
+    /// Guide library to search instead of --sequence, one query per guide
+    #[arg(long)]
+    library: Option<String>,
+
+    /// Format of --library: csv (name,sequence), crispick or flashfry
+    #[arg(long, default_value = "csv")]
+    library_format: LibraryFormat,
 
     /// Prefix for the sequence names to search within
     #[arg(short = 'p', long, default_value = "")]
     prefix: String,
 
+    /// Only search sequences of at least this many bases, such as to skip short unplaced scaffolds
+    #[arg(long)]
+    min_contig_length: Option<usize>,
+
+    /// Only search sequences of at most this many bases
+    #[arg(long)]
+    max_contig_length: Option<usize>,
+
+    /// Alphabet of the FASTA and queries, dna or protein (one strand, distance over residues); detected from the FASTA when not given
+    #[arg(long)]
+    alphabet: Option<Alphabet>,
+
     /// Maximum number of mismatches allowed (Hamming distance)
     #[arg(short, long, default_value_t = 6)]
     distance: usize,
 
+    /// Maximum fraction of each query's length that may mismatch, instead of --distance (e.g. 0.1 allows 2 in a 20-mer and 3 in a 30-mer)
+    #[arg(long, conflicts_with_all = ["distance", "distance_plus", "distance_minus"])]
+    max_divergence: Option<f64>,
+
+    /// Maximum number of mismatches on the plus strand, instead of --distance
+    #[arg(long, conflicts_with_all = ["expectation", "canonical_strand"])]
+    distance_plus: Option<usize>,
+
+    /// Maximum number of mismatches on the minus strand, instead of --distance
+    #[arg(long, conflicts_with_all = ["expectation", "canonical_strand"])]
+    distance_minus: Option<usize>,
+
     /// Number of threads for parallel execution
     #[arg(short = 't', long, default_value = "0")]
     parallelism: usize,
@@ -33,98 +154,1760 @@ struct Args {
     /// Flag for the presence of a Cas9 PAM sequence
     #[arg(short = 'c', long, default_value = "false")]
     cas9: bool,
+
+    /// PAM required after the protospacer in --cas9 mode, N matching any base
+    #[arg(long, default_value = "NGG")]
+    pam: String,
+
+    /// The PAM lies 5' of the protospacer in --cas9 mode, as for Cas12a (e.g. --pam TTTN), instead of 3'
+    #[arg(long, default_value = "false", requires = "cas9", conflicts_with = "base_editor")]
+    pam_5prime: bool,
+
+    /// 1-based query positions that never count as mismatches, comma-separated (e.g. a forced 5' G of a U6 guide)
+    #[arg(long, value_delimiter = ',')]
+    ignore_positions: Vec<usize>,
+
+    /// IUPAC codes in the query match any base they stand for (e.g. the NNK codons of a back-translated peptide) instead of only themselves
+    #[arg(long, default_value = "false", conflicts_with = "min_tm")]
+    degenerate: bool,
+
+    /// Report the protospacer and the PAM of --cas9 hits in separate columns, each with its own mismatch count
+    #[arg(long, default_value = "false")]
+    split_pam: bool,
+
+    /// Non-canonical PAMs also accepted in --cas9 mode, comma-separated, adding a pam_class column
+    #[arg(long, value_delimiter = ',', conflicts_with = "expectation")]
+    alt_pams: Vec<String>,
+
+    /// Report each site once for palindromic queries (plus strand only) and for queries given in both orientations (the first only), adding a query_note column
+    #[arg(long, default_value = "false")]
+    canonical_strand: bool,
+
+    /// Suppress hits closer than this many bases to a better-scoring hit on the same contig
+    #[arg(long, default_value_t = 0)]
+    min_separation: usize,
+
+    /// Report only the hit with the fewest mismatches on each contig (ties go to the lowest start)
+    #[arg(long, default_value = "false")]
+    best_per_contig: bool,
+
+    /// Report at most this many hits of each query per PanSN sample, those with the fewest mismatches, so one repetitive assembly cannot crowd out the others
+    #[arg(long, conflicts_with_all = ["collapse_by_position", "invert", "homology", "output_dir", "scatter_output"])]
+    max_hits_per_sample: Option<usize>,
+
+    /// Minimum GC content (percent) of reported hits
+    #[arg(long)]
+    min_gc: Option<f64>,
+
+    /// Maximum GC content (percent) of reported hits
+    #[arg(long)]
+    max_gc: Option<f64>,
+
+    /// Maximum homopolymer run length of reported hits
+    #[arg(long)]
+    max_homopolymer: Option<usize>,
+
+    /// Drop hits containing TTTT (Pol III / U6 terminator)
+    #[arg(long = "no-polyT", default_value = "false")]
+    no_poly_t: bool,
+
+    /// Maximum length of a self-complementary stretch within reported hits
+    #[arg(long)]
+    max_self_complementarity: Option<usize>,
+
+    /// Report expected vs observed hit counts per mismatch level on stderr
+    #[arg(long, default_value = "false")]
+    expectation: bool,
+
+    /// Order of the background model fitted for --expectation (0 = base composition, 1 = Markov-1)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=1))]
+    background_order: u8,
+
+    /// Fit the --expectation background from the k-mer counts written by `stats --background-model` instead of counting the searched contigs; its order overrides --background-order
+    #[arg(long, requires = "expectation")]
+    background_model: Option<String>,
+
+    /// Also search this many dinucleotide shuffles of each query and report their hit counts on stderr as a background
+    #[arg(long, default_value_t = 0, conflicts_with = "min_tm")]
+    shuffled_controls: usize,
+
+    /// Seed of the --shuffled-controls shuffles
+    #[arg(long, default_value_t = 1)]
+    shuffle_seed: u64,
+
+    /// Report each hit with this probability (0 to 1), chosen reproducibly from --seed; the other reports still count every hit
+    #[arg(long, conflicts_with = "invert")]
+    sample_hits: Option<f64>,
+
+    /// Seed of --sample-hits
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// Report the consensus and per-position base frequencies of all reported hits on stderr
+    #[arg(long, default_value = "false")]
+    hits_consensus: bool,
+
+    /// Write the position counts and information of all reported hits of each query to this file, a matrix for sequence logos of where the sites vary
+    #[arg(long)]
+    hits_logo: Option<String>,
+
+    /// Also draw each --hits-logo matrix as a text logo, four rows to a bit
+    #[arg(long, default_value = "false", requires = "hits_logo")]
+    hits_logo_text: bool,
+
+    /// Report on stderr the fraction of PanSN haplotypes and samples with a hit at each mismatch level
+    #[arg(long, default_value = "false")]
+    haplotype_coverage: bool,
+
+    /// Write the mismatches of every window of the --raw-contigs to this file, one byte per position and strand
+    #[arg(long, requires = "raw_contigs", conflicts_with_all = ["library", "cas9", "min_tm"])]
+    raw_distances: Option<String>,
+
+    /// Contigs whose distances --raw-distances writes, comma-separated
+    #[arg(long, value_delimiter = ',', requires = "raw_distances")]
+    raw_contigs: Vec<String>,
+
+    /// Add sequence-context columns: DUST low-complexity score and longest homopolymer of the hit, and GC percent of the 50 bp on either side
+    #[arg(long, default_value = "false")]
+    context: bool,
+
+    /// Report the maximal intervals of each contig that no hit overlaps (seq_name, start, end) instead of the hits
+    #[arg(long, default_value = "false", conflicts_with_all = ["collapse_by_position", "report", "output_dir", "regions"])]
+    invert: bool,
+
+    /// Collapse hits at the same site across PanSN assemblies into one row per site
+    #[arg(long, default_value = "false")]
+    collapse_by_position: bool,
+
+    /// With --collapse-by-position, add a column per PanSN sample with the genotype of each site over its haplotypes, as GT:MM (e.g. 1|0:2,.)
+    #[arg(long, requires = "collapse_by_position")]
+    genotypes: bool,
+
+    /// Maximum coordinate difference between hits collapsed into one site
+    #[arg(long, default_value_t = 1000)]
+    collapse_tolerance: usize,
+
+    /// MiB of rows --collapse-by-position, --format vcf and --max-hits-per-sample each hold in memory before spilling them as sorted, compressed runs to temporary files (in TMPDIR)
+    #[arg(long, default_value_t = 1024)]
+    sort_memory: usize,
+
+    /// Chain or PAF (.paf) alignments of the searched assemblies to a reference, used to add projected coordinates
+    #[arg(long, conflicts_with = "collapse_by_position")]
+    liftover: Option<String>,
+
+    /// Chain or PAF (.paf) alignments between the searched assemblies, such as from wfmash, used to add a site_id column shared by hits at the same site in different haplotypes
+    #[arg(long, conflicts_with_all = ["collapse_by_position", "invert", "output_dir", "scatter_output"])]
+    homology: Option<String>,
+
+    /// GFA graph whose paths or walks spell the searched contigs, used to add node/offset coordinates
+    #[arg(long, conflicts_with = "collapse_by_position")]
+    gfa: Option<String>,
+
+    /// Substitution cost matrix (A, C, G, T, N) used to add a per-hit score column
+    #[arg(long)]
+    matrix: Option<String>,
+
+    /// Drop hits whose --matrix score exceeds this value
+    #[arg(long, requires = "matrix")]
+    max_score: Option<f64>,
+
+    /// Add mismatch_positions (1-based protospacer positions from its 5' end) and seed_mismatches (within the --seed-region bases next to the PAM) columns
+    #[arg(long, default_value = "false")]
+    mismatch_positions: bool,
+
+    /// Protospacer bases next to the PAM, or at the 3' end without one, counted by seed_mismatches and --max-seed-mismatches
+    #[arg(long, default_value_t = 12)]
+    seed_region: usize,
+
+    /// Drop hits with more mismatches than this within the --seed-region bases
+    #[arg(long)]
+    max_seed_mismatches: Option<usize>,
+
+    /// Add an mit column: the MIT off-target score (Hsu et al. 2013, 0 to 100) of 20-base guides with a 3' PAM, . for others
+    #[arg(long, default_value = "false")]
+    mit_score: bool,
+
+    /// Report on stderr a row per query with its hits at each mismatch level and, with --mit-score, its MIT specificity
+    #[arg(long, default_value = "false", conflicts_with = "min_tm")]
+    guide_summary: bool,
+
+    /// Turn on a set of options at once; options given on the command line or in --config still win. crispr: --cas9 -d 4 --split-pam --mismatch-positions --mit-score --context --guide-summary
+    #[arg(long)]
+    preset: Option<Preset>,
+
+    /// Add a hit_id column: a stable hash of query, contig, start and strand
+    #[arg(long, default_value = "false")]
+    hit_ids: bool,
+
+    /// Report windows whose predicted duplex Tm (°C) with the query reaches this value, instead of using --distance
+    #[arg(long, conflicts_with_all = ["cas9", "expectation"])]
+    min_tm: Option<f64>,
+
+    /// Total strand concentration (nM) for --min-tm
+    #[arg(long, default_value_t = 250.0)]
+    tm_strand_conc: f64,
+
+    /// Monovalent cation concentration (mM) for --min-tm
+    #[arg(long, default_value_t = 50.0)]
+    tm_sodium: f64,
+
+    /// Orientation of the sequence column: query (5'->3' like the query, reverse complemented on the - strand) or target (the + strand of the reference)
+    #[arg(long, default_value = "query")]
+    report_orientation: Orientation,
+
+    /// Print hit sequences in the RNA alphabet (U is always read as T in queries and references)
+    #[arg(long, default_value = "false")]
+    rna: bool,
+
+    /// Skip windows overlapping runs of at least this many Ns (default: the shortest run that rules out a hit; 0 disables)
+    #[arg(long)]
+    skip_n_runs: Option<usize>,
+
+    /// BED file of N runs (seq_name, start, end) to use instead of scanning each contig for them
+    #[arg(long)]
+    gaps: Option<String>,
+
+    /// Bgzipped, tabix-indexed BED of regions to search instead of whole contigs; hits must lie within a region
+    #[arg(long, conflicts_with_all = ["expectation", "skip_duplicate_contigs"])]
+    regions: Option<String>,
+
+    /// TSV of FASTA contig names and the names to report them under (e.g. without PanSN prefixes, or UCSC names for RefSeq ones); other files may use either name
+    #[arg(long)]
+    alias: Option<String>,
+
+    /// Scan contigs with identical sequence once and report their hits under every name
+    #[arg(long, default_value = "false")]
+    skip_duplicate_contigs: bool,
+
+    /// For names occurring more than once in the .fai: error, search only the first record of each (first), or rename the later ones <name>_2, <name>_3, ... (suffix)
+    #[arg(long, default_value = "error")]
+    on_duplicate_names: DuplicateNames,
+
+    /// Check the .fai against the FASTA first, and exit with every inconsistency listed instead of searching
+    #[arg(long, default_value = "false")]
+    validate: bool,
+
+    /// Skip contigs whose k-mer filter (built with `index --bloom`) shows they cannot contain a hit
+    #[arg(long, default_value = "false")]
+    bloom: bool,
+
+    /// Skip contigs whose minimizer sketch (built with `index --minimizers`) shows they cannot contain a hit
+    #[arg(long, default_value = "false")]
+    minimizers: bool,
+
+    /// Warn instead of refusing to search when a --bloom or --minimizers index was built from other contig sequences; hits on those contigs may be missed
+    #[arg(long, default_value = "false")]
+    allow_mismatched_index: bool,
+
+    /// Directory caching each query's hits; an identical query, settings and reference are answered from it without scanning
+    #[arg(long)]
+    cache_dir: Option<String>,
+
+    /// Stop the search after this many seconds, keeping the hits found so far, and exit with an error
+    #[arg(long)]
+    timeout: Option<f64>,
+
+    /// Abandon the scan of any query that has taken this many seconds to scan and to filter and write its hits, summed over threads, keeping its hits so far; a table on stderr gives each query's status
+    #[arg(long)]
+    per_query_timeout: Option<f64>,
+
+    /// Lossy fast mode: only compare windows sharing an exact --seed-length k-mer with the query at the same offset, which can miss hits near --distance
+    #[arg(long, default_value = "false")]
+    fast: bool,
+
+    /// Length of the exact k-mer --fast requires (1 to 32)
+    #[arg(long, default_value_t = 12, requires = "fast")]
+    seed_length: usize,
+
+    /// Only compare windows starting at every Nth base of each contig, for a quick coarse scan; hits elsewhere are missed
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), conflicts_with_all = ["expectation", "invert"])]
+    step: Option<usize>,
+
+    /// Fetch and scan contigs in chunks of this many bases instead of whole (default: whole contigs)
+    #[arg(long)]
+    chunk_size: Option<usize>,
+
+    /// Bytes of decompressed contig pieces to keep in memory for fetches that return to the same contigs, least recently used first out (default: 0, none)
+    #[arg(long, default_value_t = 0)]
+    cache_bytes: usize,
+
+    /// Read a plain FASTA on a local disk through htslib instead of memory-mapping it
+    #[arg(long)]
+    no_mmap: bool,
+
+    /// Read a piece of the FASTA again up to this many times when reading it fails or comes back short, as over NFS or from object storage; a contig still failing is skipped and listed at the end
+    #[arg(long, default_value_t = 3)]
+    fetch_retries: usize,
+
+    /// Seconds to wait before the first retry of a read, doubled for each next one
+    #[arg(long, default_value_t = 0.1)]
+    fetch_backoff: f64,
+
+    /// Scan each fetched contig or chunk in parallel blocks of this many bases, small enough to stay in cache (0: one pass per strand)
+    #[arg(long, default_value_t = 1 << 20)]
+    block_size: usize,
+
+    /// Search algorithm: naive compares every window, pigeonhole seeds every plain query with exact pieces, simd bit-slices the queries of up to 32 bases sharing a pass, and auto picks from query length, distance and the queries sharing a pass
+    #[arg(long, visible_alias = "algorithm", default_value = "auto", conflicts_with = "fast")]
+    backend: Backend,
+
+    /// Time the search on a sample of the reference with a few thread counts, chunk sizes and backends, then run with the fastest and report it on stderr
+    #[arg(long, default_value = "false", conflicts_with_all = ["chunk_size", "backend", "fast"])]
+    autotune: bool,
+
+    /// Seconds --autotune may spend on trials before it settles on the fastest so far
+    #[arg(long, default_value_t = 5.0, requires = "autotune")]
+    autotune_seconds: f64,
+
+    /// After a chunked search, rescan this many long contigs whole and fail if any hit differs
+    #[arg(long, requires = "chunk_size")]
+    self_check: Option<usize>,
+
+    /// Also write a self-contained HTML report: run parameters, mismatch histogram, hit density per contig and a sortable hit table
+    #[arg(long)]
+    report: Option<String>,
+
+    /// Also write the sequence of every reported hit to this FASTA file, each record named query|contig|start|strand|mismatches, for alignment or BLAST
+    #[arg(long, conflicts_with = "invert")]
+    hits_fasta: Option<String>,
+
+    /// Bases of the reference on either side of each --hits-fasta sequence, in lowercase
+    #[arg(long, default_value_t = 0, requires = "hits_fasta")]
+    hits_fasta_flank: usize,
+
+    /// Output format: tsv, or vcf with a record per hit whose INFO holds the other columns
+    #[arg(long, default_value = "tsv", conflicts_with_all = ["collapse_by_position", "invert", "homology", "report", "output_dir"])]
+    format: OutputFormat,
+
+    /// Write the output to this file instead of stdout; it only appears once the search has completed
+    #[arg(short, long, conflicts_with = "output_dir")]
+    output: Option<String>,
+
+    /// Exit with 3 when the search finds no hits, 2 for bad arguments, 4 for an unreadable or malformed query or annotation input, 5 for an unreadable reference and 1 for an incomplete search, instead of 0 or 1
+    #[arg(long, default_value = "false")]
+    strict_exit: bool,
+
+    /// Write a JSON manifest of inputs with checksums, parameters, output files and summary counts here when the run ends
+    #[arg(long)]
+    manifest: Option<String>,
+
+    /// Replace an existing --output file
+    #[arg(long, default_value = "false", requires = "output", conflicts_with = "append")]
+    force: bool,
+
+    /// Add the rows to an existing --output file, which keeps its header
+    #[arg(long, default_value = "false", requires = "output")]
+    append: bool,
+
+    /// Write hits into one TSV per partition in this directory instead of to stdout
+    #[arg(long, requires = "partition_by")]
+    output_dir: Option<String>,
+
+    /// Have each thread write its contigs' rows to temporary files (in TMPDIR), concatenated to stdout in index order at the end
+    #[arg(long, default_value = "false", conflicts_with_all = ["collapse_by_position", "output_dir"])]
+    scatter_output: bool,
+
+    /// What --output-dir files are split by: query, contig or sample (PanSN)
+    #[arg(long, requires = "output_dir", value_parser = ["query", "contig", "sample"])]
+    partition_by: Option<String>,
+
+    /// Annotate hits with the protospacer bases an ABE (A>G) or CBE (C>T) base editor can convert
+    #[arg(long)]
+    base_editor: Option<BaseEditor>,
+
+    /// Editing window of --base-editor as 1-based protospacer positions counted from the PAM-distal end
+    #[arg(long, default_value = "4-8")]
+    edit_window: EditWindow,
+
+    /// Protospacer position of the intended edit; reports whether it is editable and how many bystanders share the window
+    #[arg(long, requires = "base_editor")]
+    edit_position: Option<usize>,
+
+    /// RepeatMasker .out or BED (name = family) of repeats, used to add a repeat column
+    #[arg(long)]
+    repeats: Option<String>,
+
+    /// Drop hits overlapping any --repeats interval
+    #[arg(long, requires = "repeats", default_value = "false")]
+    exclude_repeats: bool,
+
+    /// Bgzipped, tabix-indexed multi-sample VCF, used to add the fraction of samples in which each hit site is intact
+    #[arg(long)]
+    population_vcf: Option<String>,
+}
+
+// 64-bit FNV-1a over the fields, so IDs are identical across runs, thread counts and platforms.
+fn hit_id(query: &str, seq_name: &str, start: usize, strand: Strand) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let start = start.to_string();
+    for field in [query, seq_name, start.as_str(), strand.as_str()] {
+        for b in field.bytes().chain(std::iter::once(0)) {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+// Whether --sample-hits keeps a hit, decided by a hash of the hit and the seed, so the same
+// hits are kept in every run whatever order the contigs are scanned in.
+fn sampled(query: &str, seq_name: &str, hit: &Hit, seed: u64, p: f64) -> bool {
+    let id = u64::from_str_radix(&hit_id(query, seq_name, hit.start, hit.strand), 16).unwrap();
+    Rng::new(id ^ seed).uniform() < p
+}
+
+// Visit hits from best to worst (fewest mismatches, then position) and drop any hit
+// starting within `min_separation` of one already kept; survivors keep their original order.
+fn apply_min_separation(hits: Vec<Hit>, min_separation: usize) -> Vec<Hit> {
+    let mut order: Vec<usize> = (0..hits.len()).collect();
+    order.sort_by_key(|&i| (hits[i].mismatches, hits[i].start));
+    let mut kept_starts = BTreeSet::new();
+    let mut keep = vec![false; hits.len()];
+    for i in order {
+        let start = hits[i].start;
+        let lo = start.saturating_sub(min_separation - 1);
+        if kept_starts.range(lo..start + min_separation).next().is_none() {
+            kept_starts.insert(start);
+            keep[i] = true;
+        }
+    }
+    hits.into_iter().zip(keep).filter(|(_, k)| *k).map(|(h, _)| h).collect()
+}
+
+// `--raw-distances`: the magic HFDIST1\0, the query length as u32, and for each contig its name
+// length (u32), name, window count n (u64), then n forward and n reverse distances, one byte
+// each by window start. Integers are little-endian.
+fn write_raw_distances(path: &str, searcher: &Searcher, query: &Query, contigs: &[String]) -> Result<(), String> {
+    let mut out = std::io::BufWriter::new(File::create(path).map_err(|e| format!("{}: {}", path, e))?);
+    let write_error = |e: std::io::Error| format!("{}: {}", path, e);
+    out.write_all(b"HFDIST1\0").map_err(write_error)?;
+    out.write_all(&(query.sequence.len() as u32).to_le_bytes()).map_err(write_error)?;
+    for seq_name in contigs {
+        let [forward, reverse] = searcher.window_distances(seq_name, query)?;
+        out.write_all(&(seq_name.len() as u32).to_le_bytes()).map_err(write_error)?;
+        out.write_all(seq_name.as_bytes()).map_err(write_error)?;
+        out.write_all(&(forward.len() as u64).to_le_bytes()).map_err(write_error)?;
+        out.write_all(&forward).map_err(write_error)?;
+        out.write_all(&reverse).map_err(write_error)?;
+    }
+    out.flush().map_err(write_error)
+}
+
+// The maximal intervals of a contig of `length` bases that no hit overlaps.
+fn free_intervals(hits: &[Hit], length: usize) -> Vec<(usize, usize)> {
+    let mut covered: Vec<(usize, usize)> = hits.iter().map(|hit| (hit.start, hit.end)).collect();
+    covered.sort_unstable();
+    let mut free = Vec::new();
+    let mut pos = 0;
+    for (start, end) in covered {
+        if start > pos {
+            free.push((pos, start));
+        }
+        pos = pos.max(end);
+    }
+    if pos < length {
+        free.push((pos, length));
+    }
+    free
+}
+
+#[derive(Default)]
+struct ExpectationTally {
+    composition: Composition,
+    windows: u64,
+    observed: Vec<u64>,
+}
+
+// Hits of the query per mismatch level against those of its shuffled controls, both counted
+// before any filter like --expectation.
+fn report_controls(observed: &[u64], controls: &[Vec<u64>], max_mismatches: usize) {
+    eprintln!("# {} dinucleotide-shuffled controls", controls.len());
+    eprintln!("mismatches\tobserved\tcontrol_mean\tcontrol_min\tcontrol_max\tcumulative_observed\tcumulative_control_mean\tempirical_p");
+    let mut cum_observed = 0;
+    let mut cum_controls = vec![0u64; controls.len()];
+    for k in 0..=max_mismatches {
+        cum_observed += observed[k];
+        for (cum, control) in cum_controls.iter_mut().zip(controls) {
+            *cum += control[k];
+        }
+        let at_k: Vec<u64> = controls.iter().map(|control| control[k]).collect();
+        let mean = |counts: &[u64]| counts.iter().sum::<u64>() as f64 / counts.len() as f64;
+        // controls with at least as many hits up to k, counting the query itself as one
+        let as_many = cum_controls.iter().filter(|&&cum| cum >= cum_observed).count();
+        let p_value = (as_many + 1) as f64 / (controls.len() + 1) as f64;
+        eprintln!(
+            "{}\t{}\t{:.2}\t{}\t{}\t{}\t{:.2}\t{:.4}",
+            k,
+            observed[k],
+            mean(&at_k),
+            at_k.iter().min().unwrap(),
+            at_k.iter().max().unwrap(),
+            cum_observed,
+            mean(&cum_controls),
+            p_value
+        );
+    }
+}
+
+// Fewest mismatches of any reported hit, per PanSN haplotype.
+fn report_coverage(best: &HashMap<String, usize>, seq_names: &[(String, usize)], max_mismatches: usize) {
+    let mut haplotypes: HashMap<String, String> = HashMap::new();
+    for (seq_name, _) in seq_names {
+        let name = pansn::parse(seq_name);
+        haplotypes.insert(name.haplotype_id(), name.sample.unwrap_or(name.contig).to_string());
+    }
+    let n_samples = haplotypes.values().collect::<BTreeSet<_>>().len();
+    eprintln!("mismatches	haplotypes	haplotypes_total	haplotype_fraction	samples	samples_total	sample_fraction");
+    for k in 0..=max_mismatches {
+        let covered: Vec<&String> = best.iter().filter(|(_, &m)| m <= k).map(|(haplotype, _)| haplotype).collect();
+        let samples = covered.iter().map(|haplotype| &haplotypes[*haplotype]).collect::<BTreeSet<_>>().len();
+        eprintln!(
+            "{}\t{}\t{}\t{:.4}\t{}\t{}\t{:.4}",
+            k,
+            covered.len(),
+            haplotypes.len(),
+            covered.len() as f64 / haplotypes.len().max(1) as f64,
+            samples,
+            n_samples,
+            samples as f64 / n_samples.max(1) as f64
+        );
+    }
+}
+
+fn report_expectation(tally: &ExpectationTally, query: &Query, model: Option<&KmerCounts>, order: u8, max_mismatches: usize) {
+    let (pam, ignored) = (query.pam_range(), query.ignored_in_target());
+    let pattern: Vec<PatternPos> = query
+        .target()
+        .bytes()
+        .enumerate()
+        .map(|(i, b)| {
+            if pam.contains(&i) {
+                if b == b'N' { PatternPos::Free } else { PatternPos::Required(b) }
+            } else if ignored.contains(&i) {
+                PatternPos::Free
+            } else if query.degenerate {
+                PatternPos::Degenerate(b)
+            } else {
+                PatternPos::Scored(b)
+            }
+        })
+        .collect();
+    let (forward, reverse) = match model {
+        Some(counts) => (Background::from_counts(counts, counts.order), Background::from_counts(&counts.reverse_complement(), counts.order)),
+        None => (Background::fit(&tally.composition, order as usize), Background::fit(&tally.composition.reverse_complement(), order as usize)),
+    };
+    let p_forward = stats::mismatch_distribution(&forward, &pattern, max_mismatches);
+    let p_reverse = stats::mismatch_distribution(&reverse, &pattern, max_mismatches);
+
+    eprintln!("# expectation over {} windows per strand, background order {}", tally.windows, forward.order());
+    eprintln!("mismatches\tobserved\texpected\tcumulative_observed\tcumulative_expected\tenrichment\tp_value");
+    let (mut cum_observed, mut cum_expected) = (0u64, 0.0);
+    for k in 0..=max_mismatches {
+        let observed = tally.observed.get(k).copied().unwrap_or(0);
+        let expected = tally.windows as f64 * (p_forward[k] + p_reverse[k]);
+        cum_observed += observed;
+        cum_expected += expected;
+        let enrichment = if cum_expected > 0.0 { cum_observed as f64 / cum_expected } else { f64::INFINITY };
+        let p_value = stats::poisson_upper_tail(cum_expected, cum_observed);
+        eprintln!("{}\t{}\t{:.4e}\t{}\t{:.4e}\t{:.3}\t{:.4e}", k, observed, expected, cum_observed, cum_expected, enrichment, p_value);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Orientation {
+    Query,
+    Target,
+}
+
+impl std::str::FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Orientation, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "query" => Ok(Orientation::Query),
+            "target" => Ok(Orientation::Target),
+            _ => Err(format!("unknown orientation {:?}, expected query or target", s)),
+        }
+    }
 }
 
-fn load_fai(path: &str) -> HashMap<String, usize> {
-    let fai_path = format!("{}.fai", path);
-    let file = File::open(&fai_path).unwrap();
-    let reader = BufReader::new(file);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Tsv,
+    Vcf,
+}
 
-    let mut sequences = HashMap::new();
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
 
-    for line in reader.lines() {
-        let line = line.unwrap();
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        let name = parts[0].to_string();
-        let length: usize = parts[1].parse().unwrap();
-        sequences.insert(name, length);
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "tsv" => Ok(OutputFormat::Tsv),
+            "vcf" => Ok(OutputFormat::Vcf),
+            _ => Err(format!("unknown format {:?}, expected tsv or vcf", s)),
+        }
     }
+}
 
-    sequences
+// bases on either side of a hit whose GC --context reports
+const CONTEXT_FLANK: usize = 50;
+
+// Per-hit columns beyond the fixed ones, shared by the per-hit and collapsed outputs.
+struct Columns<'a> {
+    queries: &'a [Query],
+    // leading query column, only when searching a library
+    names: Option<&'a [String]>,
+    // the scored part of each target for --matrix
+    scored_targets: Vec<String>,
+    matrix: Option<ScoringMatrix>,
+    liftover: Option<Liftover>,
+    graph: Option<GraphPaths>,
+    hit_ids: bool,
+    orientation: Orientation,
+    rna: bool,
+    base_editor: Option<(BaseEditor, EditWindow, Option<usize>)>,
+    repeats: Option<Repeats>,
+    population: Option<Population>,
+    // --population-vcf: perfect-match sites not intact in every sample, as (query, contig, start, fraction)
+    polymorphic_sites: Mutex<Vec<(usize, String, usize, f64)>>,
+    // --context and --hits-fasta-flank: per contig, the hits written without flanks that could not be read, and the last error
+    flank_failures: Mutex<BTreeMap<String, (usize, String)>>,
+    // --population-vcf: the same for hits whose sites could not be looked up
+    population_failures: Mutex<BTreeMap<String, (usize, String)>>,
+    // per query for --canonical-strand: palindromic, the later queries that are its reverse complement, or "."
+    query_notes: Option<Vec<String>>,
+    // --alt-pams: which PAM each hit ends in
+    pam_classes: bool,
+    // --context: flanks are fetched from the reference
+    context: Option<&'a Searcher>,
+    split_pam: bool,
+    // --mismatch-positions: the --seed-region length
+    seed_region: Option<usize>,
+    mit: bool,
+    // --genotypes: the PanSN samples searched, with their haplotypes in order and the contigs of each
+    genotypes: Option<Vec<Sample>>,
+    // --alias: rows name contigs by their output names, annotations may use either
+    aliases: Option<&'a Aliases>,
 }
 
-fn hamming_distance(s1: &str, s2: &str) -> usize {
-    s1.chars().zip(s2.chars()).filter(|&(c1, c2)| c1 != c2).count()
+// Counts a hit of a contig written without a column that could not be read, keeping the last error.
+fn note_failure(failures: &Mutex<BTreeMap<String, (usize, String)>>, hit: &Hit, error: String) {
+    let mut failures = failures.lock().unwrap();
+    let failure = failures.entry(hit.seq_name.to_string()).or_default();
+    *failure = (failure.0 + 1, error);
 }
 
-fn get_reverse_complement(seq: &str) -> String {
-    let mut rev_comp = String::new();
-    for nucleotide in seq.chars().rev() {
-        let complement = match nucleotide {
-            'A' | 'a' => 'T',
-            'T' | 't' => 'A',
-            'G' | 'g' => 'C',
-            'C' | 'c' => 'G',
-            _ => 'N',
+impl Columns<'_> {
+    // The FASTA name and the output name of a contig given by either, to look it up by in annotations.
+    fn lookup_names<'n>(&'n self, seq_name: &'n str) -> Vec<&'n str> {
+        let Some(aliases) = self.aliases else {
+            return vec![seq_name];
         };
-        rev_comp.push(complement);
+        let fasta_name = aliases.fasta_name(seq_name);
+        let mut names = vec![fasta_name, aliases.output(fasta_name)];
+        names.dedup();
+        names
+    }
+
+    fn repeat_families<'r>(&self, repeats: &'r Repeats, seq_name: &str, hit: &Hit) -> Vec<&'r str> {
+        self.lookup_names(seq_name).into_iter().map(|name| repeats.families(name, hit.start, hit.end)).find(|families| !families.is_empty()).unwrap_or_default()
+    }
+
+    fn score(&self, hit: &Hit) -> Option<f64> {
+        let scored_target = &self.scored_targets[hit.query];
+        self.matrix.as_ref().map(|matrix| matrix.score(scored_target, self.queries[hit.query].protospacer(&hit.sequence)))
+    }
+
+    // Hits are kept in query orientation; scores and edit windows are computed on that.
+    fn sequence(&self, hit: &Hit) -> String {
+        self.oriented(hit, &hit.sequence)
+    }
+
+    // Part of a hit's sequence as reported.
+    fn oriented(&self, hit: &Hit, bases: &str) -> String {
+        let bases = match (self.orientation, hit.strand) {
+            (Orientation::Target, Strand::Reverse) => get_reverse_complement(bases),
+            _ => bases.to_string(),
+        };
+        if self.rna {
+            to_rna(&bases)
+        } else {
+            bases
+        }
+    }
+
+    // The sequence and mismatch columns. With --split-pam the protospacer and the PAM get a
+    // column each, and so do their mismatches, those of the PAM counted against --pam.
+    fn write_sequence<W: Write>(&self, out: &mut W, hit: &Hit) {
+        let query = &self.queries[hit.query];
+        if !self.split_pam {
+            write!(out, "\t{}\t{}", self.sequence(hit), hit.mismatches).unwrap();
+        } else if !query.cas9 {
+            write!(out, "\t{}\t.\t{}\t.", self.sequence(hit), hit.mismatches).unwrap();
+        } else {
+            let pam = query.pam(&hit.sequence);
+            let pam_mismatches = pam.bytes().zip(query.pam.bytes()).filter(|&(b, p)| p != b'N' && b != p).count();
+            let protospacer = self.oriented(hit, query.protospacer(&hit.sequence));
+            write!(out, "\t{}\t{}\t{}\t{}", protospacer, self.oriented(hit, pam), hit.mismatches, pam_mismatches).unwrap();
+        }
+    }
+
+    fn write_name<W: Write>(&self, out: &mut W, hit: &Hit) {
+        if let Some(names) = self.names {
+            write!(out, "{}\t", names[hit.query]).unwrap();
+        }
+    }
+
+    fn write_base_edit<W: Write>(&self, out: &mut W, hit: &Hit) {
+        if let Some((editor, window, edit_position)) = self.base_editor {
+            // the PAM follows the protospacer in query orientation
+            let protospacer = self.queries[hit.query].protospacer(&hit.sequence);
+            let positions = base_edit::editable_positions(editor, window, protospacer);
+            let bases: Vec<String> = positions.iter().map(|pos| format!("{}{}", editor.substrate() as char, pos)).collect();
+            write!(out, "\t{}", if bases.is_empty() { ".".to_string() } else { bases.join(",") }).unwrap();
+            if let Some(edit_position) = edit_position {
+                let available = positions.contains(&edit_position);
+                write!(out, "\t{}\t{}", if available { "yes" } else { "no" }, positions.len() - available as usize).unwrap();
+            }
+        }
+    }
+
+    fn write_repeats<W: Write>(&self, out: &mut W, seq_name: &str, hit: &Hit) {
+        if let Some(repeats) = &self.repeats {
+            let families = self.repeat_families(repeats, seq_name, hit);
+            write!(out, "\t{}", if families.is_empty() { ".".to_string() } else { families.join(",") }).unwrap();
+        }
+    }
+
+    fn write_population<W: Write>(&self, out: &mut W, seq_name: &str, hit: &Hit) {
+        if let Some(population) = &self.population {
+            // the scanned contig, which collapsed rows name without their PanSN prefix
+            let names = self.lookup_names(&hit.seq_name);
+            let vcf_name = names.iter().find(|name| population.indexes(name)).unwrap_or(&names[0]);
+            // a failed lookup must not take the search down with it
+            let fraction = population.intact_fraction(vcf_name, hit.start, hit.end).unwrap_or_else(|error| {
+                note_failure(&self.population_failures, hit, error);
+                None
+            });
+            match fraction {
+                Some(fraction) => write!(out, "\t{:.3}", fraction).unwrap(),
+                None => write!(out, "\t.").unwrap(),
+            }
+            if let (0, Some(fraction)) = (hit.mismatches, fraction) {
+                if fraction < 1.0 {
+                    self.polymorphic_sites.lock().unwrap().push((hit.query, seq_name.to_string(), hit.start, fraction));
+                }
+            }
+        }
+    }
+
+    fn write_query_note<W: Write>(&self, out: &mut W, hit: &Hit) {
+        if let Some(notes) = &self.query_notes {
+            write!(out, "\t{}", notes[hit.query]).unwrap();
+        }
+    }
+
+    fn write_pam_class<W: Write>(&self, out: &mut W, hit: &Hit) {
+        if self.pam_classes {
+            let query = &self.queries[hit.query];
+            let window = &hit.sequence.as_bytes()[query.pam_range()];
+            // the canonical PAM wins where an alternative overlaps it
+            let class = match query.pams().iter().position(|pam| search::pam_matches(window, pam)) {
+                None => ".".to_string(),
+                Some(0) => "canonical".to_string(),
+                Some(i) => query.pams()[i].clone(),
+            };
+            write!(out, "\t{}", class).unwrap();
+        }
+    }
+
+    // --mismatch-positions and --mit-score
+    fn write_offtarget<W: Write>(&self, out: &mut W, hit: &Hit) {
+        if self.seed_region.is_none() && !self.mit {
+            return;
+        }
+        let query = &self.queries[hit.query];
+        let positions = offtarget::mismatch_positions(query, &hit.sequence);
+        if let Some(region) = self.seed_region {
+            let listed: Vec<String> = positions.iter().map(|i| (i + 1).to_string()).collect();
+            write!(out, "\t{}\t{}", if listed.is_empty() { ".".to_string() } else { listed.join(",") }, offtarget::seed_mismatches(query, &positions, region)).unwrap();
+        }
+        if self.mit {
+            match offtarget::mit_score(query, &positions) {
+                Some(score) => write!(out, "\t{:.2}", score).unwrap(),
+                None => write!(out, "\t.").unwrap(),
+            }
+        }
     }
-    rev_comp
+
+    // The `flank` bases of the + strand left and right of a hit, or None, counted for the summary,
+    // when they cannot be read.
+    fn flanks(&self, searcher: &Searcher, hit: &Hit, flank: usize) -> Option<(String, String)> {
+        // the scanned contig, which collapsed rows name without their PanSN prefix
+        let length = searcher.length(&hit.seq_name).unwrap();
+        let fetch = |start: usize, end: usize| if start < end { searcher.fetch(&hit.seq_name, start, end) } else { Ok(String::new()) };
+        match fetch(hit.start.saturating_sub(flank), hit.start).and_then(|left| Ok((left, fetch(hit.end, (hit.end + flank).min(length))?))) {
+            Ok(flanks) => Some(flanks),
+            Err(error) => {
+                note_failure(&self.flank_failures, hit, error);
+                None
+            }
+        }
+    }
+
+    fn write_context<W: Write>(&self, out: &mut W, hit: &Hit) {
+        if let Some(searcher) = self.context {
+            let flanks = self.flanks(searcher, hit, CONTEXT_FLANK).map_or_else(String::new, |(left, right)| left + &right);
+            let flank_gc = if flanks.is_empty() { ".".to_string() } else { format!("{:.1}", probe::gc_percent(&flanks)) };
+            write!(out, "\t{:.3}\t{}\t{}", probe::dust_score(&hit.sequence), probe::longest_homopolymer(&hit.sequence), flank_gc).unwrap();
+        }
+    }
+
+    fn write_hit<W: Write>(&self, out: &mut W, seq_name: &str, hit: &Hit) {
+        self.write_name(out, hit);
+        write!(out, "{}\t{}\t{}\t{}", seq_name, hit.strand, hit.start, hit.end).unwrap();
+        self.write_sequence(out, hit);
+        if let Some(tm) = hit.tm {
+            write!(out, "\t{:.1}", tm).unwrap();
+        }
+        if let Some(score) = self.score(hit) {
+            write!(out, "\t{}", score).unwrap();
+        }
+        self.write_offtarget(out, hit);
+        self.write_base_edit(out, hit);
+        self.write_repeats(out, seq_name, hit);
+        self.write_population(out, seq_name, hit);
+        if let Some(liftover) = &self.liftover {
+            match self.lookup_names(seq_name).into_iter().find_map(|name| liftover.project(name, hit.strand.as_str(), hit.start, hit.end)) {
+                Some(p) => write!(out, "\t{}\t{}\t{}\t{}", p.seq_name, p.strand, p.start, p.end).unwrap(),
+                None => write!(out, "\t.\t.\t.\t.").unwrap(),
+            }
+        }
+        if let Some(graph) = &self.graph {
+            match self.lookup_names(seq_name).into_iter().find_map(|name| graph.project(name, hit.strand == Strand::Reverse, hit.start, hit.end)) {
+                Some(g) => {
+                    let (first, last) = (g.first, g.last);
+                    write!(out, "\t{},{},{}\t{},{},{}\t{}", first.node, first.offset, first.strand, last.node, last.offset, last.strand, g.walk).unwrap()
+                }
+                None => write!(out, "\t.\t.\t.").unwrap(),
+            }
+        }
+        if self.hit_ids {
+            write!(out, "\t{}", hit_id(&self.queries[hit.query].sequence, seq_name, hit.start, hit.strand)).unwrap();
+        }
+        self.write_query_note(out, hit);
+        self.write_pam_class(out, hit);
+        self.write_context(out, hit);
+        writeln!(out).unwrap();
+    }
+}
+
+// --guide-summary: the hits of a query by mismatches, and the MIT scores of the imperfect ones
+struct GuideSummary {
+    hits: Vec<u64>,
+    off_target_mit: f64,
+}
+
+// --per-query-timeout: the time taken to write a contig's rows, shared among their queries by
+// the number of rows of each.
+fn charge_rows(searched: &[Query], hits: &[Hit], elapsed: Duration) {
+    let mut rows: BTreeMap<usize, usize> = BTreeMap::new();
+    for hit in hits {
+        *rows.entry(hit.query).or_default() += 1;
+    }
+    for (q, n) in rows {
+        if let Some(budget) = &searched[q].budget {
+            budget.charge(elapsed.mul_f64(n as f64 / hits.len() as f64));
+        }
+    }
+}
+
+// A PanSN sample and its haplotypes in numeric order, each with its contigs.
+type Sample = (String, Vec<(String, HashSet<String>)>);
+
+// The PanSN samples among `names`; names outside the spec have no sample.
+fn pansn_samples<'a>(names: impl Iterator<Item = &'a str>) -> Vec<Sample> {
+    let mut samples: BTreeMap<String, BTreeMap<_, HashSet<String>>> = BTreeMap::new();
+    for name in names {
+        if let pansn::PanSn { sample: Some(sample), haplotype: Some(haplotype), contig } = pansn::parse(name) {
+            samples.entry(sample.to_string()).or_default().entry((haplotype.parse::<usize>().ok(), haplotype.to_string())).or_default().insert(contig.to_string());
+        }
+    }
+    samples.into_iter().map(|(sample, haplotypes)| (sample, haplotypes.into_iter().map(|((_, haplotype), contigs)| (haplotype, contigs)).collect())).collect()
+}
+
+// --genotypes: per sample whether each haplotype carries the site, phased like a VCF GT, and
+// the mismatches of its best hit there, such as 1|1:0,2 on both haplotypes or 0|0:.,. on
+// neither. A haplotype without the site's contig is missing, `.`, rather than 0.
+fn write_genotypes<W: Write>(out: &mut W, site: &[NamedHit], samples: &[Sample]) {
+    let contig = pansn::parse(&site[0].0).contig;
+    let mut best: HashMap<(&str, &str), usize> = HashMap::new();
+    for (name, hit) in site {
+        if let pansn::PanSn { sample: Some(sample), haplotype: Some(haplotype), .. } = pansn::parse(name) {
+            let mismatches = best.entry((sample, haplotype)).or_insert(hit.mismatches);
+            *mismatches = hit.mismatches.min(*mismatches);
+        }
+    }
+    for (sample, haplotypes) in samples {
+        let (mut gt, mut mm) = (Vec::new(), Vec::new());
+        for (haplotype, contigs) in haplotypes {
+            let mismatches = best.get(&(sample.as_str(), haplotype.as_str()));
+            gt.push(match mismatches {
+                Some(_) => "1",
+                None if contigs.contains(contig) => "0",
+                None => ".",
+            });
+            mm.push(mismatches.map_or(".".to_string(), usize::to_string));
+        }
+        write!(out, "\t{}:{}", gt.join("|"), mm.join(",")).unwrap();
+    }
+}
+
+// A hit and the name it is reported under.
+type NamedHit = (String, Hit);
+
+// `chr:start-end`, 1-based and inclusive like samtools, as 0-based half-open coordinates.
+fn parse_region(region: &str) -> Result<(String, usize, usize), String> {
+    let (seq_name, range) = region.rsplit_once(':').ok_or_else(|| format!("region {:?} is not of the form chr:start-end", region))?;
+    let (start, end) = range.split_once('-').ok_or_else(|| format!("region {:?} is not of the form chr:start-end", region))?;
+    let start: usize = start.replace(',', "").parse().map_err(|_| format!("bad region start {:?}", start))?;
+    let end: usize = end.replace(',', "").parse().map_err(|_| format!("bad region end {:?}", end))?;
+    if start == 0 || start > end {
+        return Err(format!("region {:?} must satisfy 1 <= start <= end", region));
+    }
+    Ok((seq_name.to_string(), start - 1, end))
+}
+
+// The bases of `--query-from other.fa:chr:start-end`, uppercased, since soft-masking in
+// another assembly says nothing about the searched one.
+fn fetch_query_from(source: &str) -> Result<String, String> {
+    let (fasta, region) = source.split_once(':').ok_or_else(|| format!("query source {:?} is not of the form other.fa:chr:start-end", source))?;
+    let (seq_name, start, end) = parse_region(region)?;
+    let other = Searcher::new(fasta, SearchConfig::default())?;
+    let length = other.length(&seq_name).ok_or_else(|| format!("{}: no sequence {}", fasta, seq_name))?;
+    if end > length {
+        return Err(format!("{} lies beyond the end of {} in {} ({} bp)", region, seq_name, fasta, length));
+    }
+    Ok(other.fetch(&seq_name, start, end)?.to_ascii_uppercase())
+}
+
+// --alias: the name a contig is reported under, and the FASTA name of one given by either.
+fn output_name<'a>(searcher: &'a Searcher, seq_name: &'a str) -> &'a str {
+    searcher.aliases().map_or(seq_name, |aliases| aliases.output(seq_name))
+}
+
+fn fasta_name<'a>(searcher: &'a Searcher, seq_name: &'a str) -> &'a str {
+    searcher.aliases().map_or(seq_name, |aliases| aliases.fasta_name(seq_name))
+}
+
+// The queries given on the command line: one --sequence, --query-region or --query-from, or every guide of a --library.
+fn load_queries(args: &Args, searcher: &Searcher) -> (Vec<String>, Vec<Query>) {
+    // per-query distance and PAM of the config file's queries
+    let mut overrides = Vec::new();
+    let guides = match (&args.library, &args.query_region, &args.query_from, &args.sequence) {
+        (Some(path), _, _, _) => or_exit(library::load(path, args.library_format, args.cas9), EXIT_INPUT),
+        (None, Some(region), _, _) => {
+            let (seq_name, start, end) = or_exit(parse_region(region), EXIT_INPUT);
+            let sequence = or_exit(searcher.fetch(fasta_name(searcher, &seq_name), start, end).map_err(|e| format!("--query-region {}: {}", region, e)), EXIT_INPUT);
+            vec![Guide { name: region.clone(), sequence, cas9: args.cas9 }]
+        }
+        (None, None, Some(source), _) => {
+            let sequence = or_exit(fetch_query_from(source), EXIT_INPUT);
+            vec![Guide { name: source.clone(), sequence, cas9: args.cas9 }]
+        }
+        (None, None, None, Some(sequence)) => vec![Guide { name: sequence.clone(), sequence: sequence.clone(), cas9: args.cas9 }],
+        (None, None, None, None) => {
+            let queries = or_exit(config::load(args.config.as_ref().unwrap()), EXIT_INPUT).queries;
+            if queries.is_empty() {
+                eprintln!("error: no queries; give --sequence, --library, --query-region or --query-from, or list queries in the config file");
+                exit_with(EXIT_INPUT);
+            }
+            queries
+                .into_iter()
+                .map(|query| {
+                    overrides.push((query.distance, query.pam));
+                    Guide { name: query.name, sequence: query.sequence, cas9: query.cas9.unwrap_or(args.cas9) }
+                })
+                .collect()
+        }
+    };
+    let [plus, minus] = strand_distances(args);
+    let queries = guides
+        .iter()
+        .enumerate()
+        .map(|(q, guide)| {
+            let (distance, pam) = overrides.get(q).cloned().unwrap_or_default();
+            let distance = distance.or_else(|| args.max_divergence.map(|divergence| divergence_distance(divergence, guide.sequence.len())));
+            let [plus, minus] = distance.map_or([plus, minus], |distance| [distance, distance]);
+            let mut query = Query::new(&guide.sequence, plus.max(minus));
+            if args.alphabet == Some(Alphabet::Protein) {
+                // U is selenocysteine, not uracil
+                query.sequence = guide.sequence.clone();
+            }
+            query.strand_max_mismatches = (plus != minus).then_some([plus, minus]);
+            query.cas9 = guide.cas9;
+            query.pam = pam.as_deref().unwrap_or(&args.pam).to_ascii_uppercase();
+            query.alt_pams = args.alt_pams.iter().map(|pam| pam.to_ascii_uppercase()).collect();
+            query.pam_5prime = args.pam_5prime;
+            query.ignored = args.ignore_positions.iter().map(|pos| pos - 1).collect::<BTreeSet<usize>>().into_iter().collect();
+            query.min_tm = args.min_tm;
+            query.degenerate = args.degenerate;
+            query
+        })
+        .collect();
+    (guides.into_iter().map(|guide| guide.name).collect(), queries)
+}
+
+// --max-divergence: the mismatches allowed in `length` bases, rounded down; the slack keeps a
+// product such as 0.29 * 100 from falling just short of a whole number.
+fn divergence_distance(divergence: f64, length: usize) -> usize {
+    (divergence * length as f64 + 1e-9).floor() as usize
+}
+
+// The mismatch limits on the plus and minus strand.
+fn strand_distances(args: &Args) -> [usize; 2] {
+    [args.distance_plus, args.distance_minus].map(|distance| distance.unwrap_or(args.distance))
+}
+
+// The flags of the search arguments with these ids, as clap spells them.
+fn long_flags<'a>(ids: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let command = Cli::command();
+    ids.map(|id| {
+        let arg = command.get_arguments().find(|arg| arg.get_id() == id).unwrap_or_else(|| panic!("no argument {}", id));
+        format!("--{}", arg.get_long().unwrap())
+    })
+    .collect()
+}
+
+// The options given that only apply to nucleotides, which a protein search refuses.
+fn nucleotide_options(args: &Args) -> Vec<String> {
+    let given = [
+        ("cas9", args.cas9),
+        ("degenerate", args.degenerate),
+        ("min_tm", args.min_tm.is_some()),
+        ("distance_plus", args.distance_plus.is_some()),
+        ("distance_minus", args.distance_minus.is_some()),
+        ("canonical_strand", args.canonical_strand),
+        ("min_gc", args.min_gc.is_some()),
+        ("max_gc", args.max_gc.is_some()),
+        ("no_poly_t", args.no_poly_t),
+        ("max_self_complementarity", args.max_self_complementarity.is_some()),
+        ("expectation", args.expectation),
+        ("shuffled_controls", args.shuffled_controls > 0),
+        ("raw_distances", args.raw_distances.is_some()),
+        ("context", args.context),
+        ("rna", args.rna),
+        ("hits_logo", args.hits_logo.is_some()),
+        ("mit_score", args.mit_score),
+        ("skip_n_runs", args.skip_n_runs.is_some()),
+        ("gaps", args.gaps.is_some()),
+        ("bloom", args.bloom),
+        ("minimizers", args.minimizers),
+        ("fast", args.fast),
+        ("base_editor", args.base_editor.is_some()),
+        ("population_vcf", args.population_vcf.is_some()),
+    ];
+    long_flags(given.into_iter().filter(|(_, given)| *given).map(|(id, _)| id))
+}
+
+// The options given that need the FASTA's index, which a search of stdin refuses.
+fn indexed_options(args: &Args) -> Vec<String> {
+    let given = [
+        ("query_region", args.query_region.is_some()),
+        ("context", args.context),
+        ("raw_distances", args.raw_distances.is_some()),
+        ("haplotype_coverage", args.haplotype_coverage),
+        ("gfa", args.gfa.is_some()),
+        ("report", args.report.is_some()),
+        ("scatter_output", args.scatter_output),
+        ("validate", args.validate),
+        ("self_check", args.self_check.is_some()),
+        ("autotune", args.autotune),
+        ("manifest", args.manifest.is_some()),
+        ("hits_fasta_flank", args.hits_fasta_flank > 0),
+        ("genotypes", args.genotypes),
+    ];
+    long_flags(given.into_iter().filter(|(_, given)| *given).map(|(id, _)| id))
 }
 
-fn search_sequence(fasta: &str, target: &str, prefix: &str, max_mismatches: usize, cas9: bool) {
-    let reader = Reader::from_path(fasta).unwrap();
-    let n_seqs = reader.n_seqs();
-    let seq_lengths = load_fai(fasta);
+// `--autotune`: sets the thread count, chunk size and backend of the search to the fastest
+// on a sample of the reference, reporting every trial on stderr.
+fn tune(args: &mut Args) {
+    let config = || SearchConfig { prefix: args.prefix.clone(), min_contig_length: args.min_contig_length, max_contig_length: args.max_contig_length, skip_n_runs: args.skip_n_runs, gaps: args.gaps.clone(), step: args.step, block_size: (args.block_size > 0).then_some(args.block_size), on_duplicate_names: args.on_duplicate_names, alphabet: args.alphabet.unwrap_or_default(), ..SearchConfig::default() };
+    let (_, queries) = load_queries(args, &or_exit(Searcher::new(&args.fasta, config()), EXIT_REFERENCE));
+    let max_threads = if args.parallelism > 0 { args.parallelism } else { std::thread::available_parallelism().map_or(1, usize::from) };
+    let (best, trials) = autotune::autotune(&args.fasta, config, &queries, max_threads, Duration::from_secs_f64(args.autotune_seconds)).unwrap();
+    let chunk_size = |tuning: &autotune::Tuning| tuning.chunk_size.map_or("whole".to_string(), |size| size.to_string());
+    eprintln!("# autotune over {} sampled bases", trials[0].bases);
+    eprintln!("threads\tchunk_size\tbackend\tseconds\tmbases_per_second");
+    for trial in &trials {
+        eprintln!("{}\t{}\t{}\t{:.3}\t{:.2}", trial.tuning.threads, chunk_size(&trial.tuning), trial.tuning.backend, trial.seconds, trial.bases as f64 / trial.seconds / 1e6);
+    }
+    let chunk_arg = best.chunk_size.map_or(String::new(), |size| format!(" --chunk-size {}", size));
+    eprintln!("# autotune chose --parallelism {}{} --backend {}", best.threads, chunk_arg, best.backend);
+    (args.parallelism, args.chunk_size, args.backend) = (best.threads, best.chunk_size, best.backend);
+}
+
+// Runs the search and returns the number of hits reported.
+fn search_sequence(args: &Args, parameters: Vec<(String, Vec<String>)>) -> u64 {
+    let started = std::time::Instant::now();
+    let constraints = ProbeConstraints {
+        min_gc: args.min_gc,
+        max_gc: args.max_gc,
+        max_homopolymer: args.max_homopolymer,
+        no_poly_t: args.no_poly_t,
+        max_self_complementarity: args.max_self_complementarity,
+    };
+    let config = SearchConfig {
+        prefix: args.prefix.clone(),
+        min_contig_length: args.min_contig_length,
+        max_contig_length: args.max_contig_length,
+        skip_n_runs: args.skip_n_runs,
+        gaps: args.gaps.clone(),
+        regions: args.regions.clone(),
+        aliases: args.alias.clone(),
+        cache_bytes: (args.cache_bytes > 0).then_some(args.cache_bytes),
+        no_mmap: args.no_mmap,
+        fetch_retries: args.fetch_retries,
+        fetch_backoff_ms: (args.fetch_backoff.max(0.0) * 1000.0) as u64,
+        skip_duplicate_contigs: args.skip_duplicate_contigs,
+        tm_model: TmModel { strand_conc: args.tm_strand_conc * 1e-9, sodium: args.tm_sodium * 1e-3 },
+        cancel: args.timeout.map(|_| Arc::new(AtomicBool::new(false))),
+        chunk_size: args.chunk_size,
+        block_size: (args.block_size > 0).then_some(args.block_size),
+        cache_dir: args.cache_dir.clone(),
+        bloom: args.bloom,
+        minimizers: args.minimizers,
+        composition: args.expectation && args.background_model.is_none(),
+        seed_length: args.fast.then_some(args.seed_length),
+        step: args.step,
+        on_duplicate_names: args.on_duplicate_names,
+        allow_mismatched_index: args.allow_mismatched_index,
+        backend: args.backend,
+        alphabet: args.alphabet.unwrap_or_default(),
+    };
+    if let (Some(timeout), Some(cancel)) = (args.timeout, config.cancel.clone()) {
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs_f64(timeout));
+            cancel.store(true, Ordering::Relaxed);
+        });
+    }
+    if args.validate {
+        let problems = or_exit(validate::validate_fai(&args.fasta), EXIT_REFERENCE);
+        if !problems.is_empty() {
+            eprintln!("# {} problems with {}.fai", problems.len(), args.fasta);
+            for problem in &problems {
+                eprintln!("#   {}", problem);
+            }
+            exit_with(EXIT_REFERENCE);
+        }
+    }
+    for pam in std::iter::once(&args.pam).chain(&args.alt_pams) {
+        if pam.is_empty() || !pam.bytes().all(|b| b"ACGTN".contains(&b.to_ascii_uppercase())) || pam.len() != args.pam.len() {
+            eprintln!("error: PAM {:?} must be ACGT or N, as long as --pam", pam);
+            exit_with(EXIT_USAGE);
+        }
+    }
+    if args.sample_hits.is_some_and(|p| !(p > 0.0 && p <= 1.0)) {
+        eprintln!("error: --sample-hits must be above 0 and at most 1");
+        exit_with(EXIT_USAGE);
+    }
+    if args.max_divergence.is_some_and(|divergence| !(0.0..1.0).contains(&divergence)) {
+        eprintln!("error: --max-divergence must be at least 0 and below 1");
+        exit_with(EXIT_USAGE);
+    }
+    if args.ignore_positions.contains(&0) {
+        eprintln!("error: --ignore-positions are 1-based");
+        exit_with(EXIT_USAGE);
+    }
+    let searcher = or_exit(Searcher::new(&args.fasta, config), EXIT_REFERENCE);
+    for mismatch in searcher.index_mismatches() {
+        eprintln!("warning: {}", mismatch);
+    }
+    let (query_names, queries) = load_queries(args, &searcher);
+    // config file queries may have their own distance
+    let max_mismatches = queries.iter().map(|query| query.max_mismatches).max().unwrap();
+    let manifest = args.manifest.as_ref().map(|path| {
+        let optional = [&args.config, &args.library, &args.gaps, &args.regions, &args.alias, &args.matrix, &args.repeats, &args.liftover, &args.homology, &args.gfa, &args.population_vcf];
+        let inputs = [args.fasta.clone(), format!("{}.fai", args.fasta)].into_iter().chain(optional.into_iter().flatten().cloned()).collect();
+        Manifest::new(path, parameters, inputs)
+    });
+    let hits_reported = AtomicU64::new(0);
+    if let Some((q, query)) = queries.iter().enumerate().find(|(_, query)| query.ignored.iter().any(|&i| i >= query.sequence.len())) {
+        eprintln!("error: --ignore-positions lie beyond query {} ({} bases)", query_names[q], query.sequence.len());
+        exit_with(EXIT_USAGE);
+    }
+    // the --query-region locus itself is not one of its copies
+    let source = args.query_region.as_ref().map(|region| or_exit(parse_region(region), EXIT_INPUT)).map(|(seq_name, start, end)| (fasta_name(&searcher, &seq_name).to_string(), start, end));
+    // searched in the same pass after the queries, never reported as hits
+    let mut rng = Rng::new(args.shuffle_seed);
+    let controls: Vec<Query> = queries
+        .iter()
+        .flat_map(|query| std::iter::repeat_n(query, args.shuffled_controls))
+        .map(|query| Query { sequence: shuffle::dinucleotide_shuffle(&query.sequence.to_ascii_uppercase(), &mut rng), ..query.clone() })
+        .collect();
+    let mut searched: Vec<Query> = queries.iter().chain(&controls).cloned().collect();
+    if let Some(seconds) = args.per_query_timeout {
+        for query in &mut searched {
+            query.budget = Some(Arc::new(QueryBudget::new(Duration::from_secs_f64(seconds))));
+        }
+    }
+    let raw_counts = Mutex::new(vec![vec![0u64; max_mismatches + 1]; searched.len()]);
+    let targets: Vec<String> = queries.iter().map(Query::target).collect();
+    // a palindromic query hits every site on both strands at the same coordinates, and a query
+    // that is the reverse complement of an earlier one hits that one's sites on the other strand
+    let same_search = |a: &Query, b: &Query| a.max_mismatches == b.max_mismatches && a.pams() == b.pams() && a.pam_5prime == b.pam_5prime && a.ignored == b.ignored && a.min_tm == b.min_tm && a.degenerate == b.degenerate;
+    let palindromic: Vec<bool> = targets.iter().map(|target| get_reverse_complement(target).eq_ignore_ascii_case(target)).collect();
+    let mirror_of: Vec<Option<usize>> = (0..queries.len())
+        .map(|q| {
+            let reverse = get_reverse_complement(&targets[q]);
+            (0..q).find(|&first| !palindromic[q] && same_search(&queries[first], &queries[q]) && targets[first].eq_ignore_ascii_case(&reverse))
+        })
+        .collect();
 
-    let stdout_lock = Arc::new(Mutex::new(std::io::stdout()));
+    let (output, sink): (Option<Output>, Box<dyn Write + Send>) = match &args.output {
+        Some(path) => {
+            let (output, file) = or_exit(Output::create(path, args.force, args.append), EXIT_INCOMPLETE);
+            (Some(output), Box::new(BufWriter::new(file)))
+        }
+        None => (None, Box::new(std::io::stdout())),
+    };
+    let background_model = args.background_model.as_ref().map(|path| {
+        std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|json| KmerCounts::from_json(&json)).unwrap_or_else(|e| {
+            eprintln!("error: {}: {}", path, e);
+            exit_with(EXIT_INPUT);
+        })
+    });
+    let tallies: Vec<Mutex<ExpectationTally>> = queries.iter().map(|_| Mutex::default()).collect();
+    let profiles = Profiles::new(queries.len());
+    let summaries: Vec<Mutex<GuideSummary>> = queries.iter().map(|_| Mutex::new(GuideSummary { hits: vec![0; max_mismatches + 1], off_target_mit: 0.0 })).collect();
+    let coverages: Vec<Mutex<HashMap<String, usize>>> = queries.iter().map(|_| Mutex::default()).collect();
 
-    let mut target = target.to_string();
-    if cas9 {
-        // add the PAM sequence to the target
-        target.push_str("NGG");
-        println!("seq_name\tstrand\tstart\tend\tsequence\tmismatches.nonpam");
+    // library and config file queries are named in a query column
+    let named_queries = args.sequence.is_none() && args.query_region.is_none() && args.query_from.is_none();
+    let mut header = String::new();
+    if named_queries {
+        header.push_str("query\t");
+    }
+    header.push_str("seq_name\tstrand\tstart\tend");
+    if args.split_pam {
+        header.push_str("\tprotospacer\tpam\tmismatches.protospacer\tmismatches.pam");
+    } else if queries.iter().any(|query| query.cas9) {
+        header.push_str("\tsequence\tmismatches.nonpam");
     } else {
-        // print a header line in tsv
-        println!("seq_name\tstrand\tstart\tend\tsequence\tmismatches");
+        header.push_str("\tsequence\tmismatches");
+    }
+    if args.min_tm.is_some() {
+        header.push_str("\ttm");
+    }
+    if args.collapse_by_position {
+        header.push_str("\tn_haplotypes\thaplotypes");
+    }
+    let columns = Columns {
+        queries: &queries,
+        names: named_queries.then_some(query_names.as_slice()),
+        aliases: searcher.aliases(),
+        // the PAM is not part of the score, like it is not part of the mismatch count
+        scored_targets: queries.iter().map(|query| query.sequence.clone()).collect(),
+        matrix: args.matrix.as_ref().map(|path| or_exit(ScoringMatrix::from_path(path), EXIT_INPUT)),
+        liftover: args.liftover.as_ref().map(|path| or_exit(Liftover::from_path(path), EXIT_INPUT)),
+        graph: args.gfa.as_ref().map(|path| or_exit(GraphPaths::from_gfa(path, searcher.contigs()), EXIT_INPUT)),
+        hit_ids: args.hit_ids,
+        orientation: args.report_orientation,
+        rna: args.rna,
+        base_editor: args.base_editor.map(|editor| (editor, args.edit_window, args.edit_position)),
+        repeats: args.repeats.as_ref().map(|path| or_exit(Repeats::from_path(path), EXIT_INPUT)),
+        population: args.population_vcf.as_ref().map(|path| or_exit(Population::from_path(path), EXIT_INPUT)),
+        polymorphic_sites: Mutex::new(Vec::new()),
+        flank_failures: Mutex::default(),
+        population_failures: Mutex::default(),
+        query_notes: args.canonical_strand.then(|| {
+            (0..queries.len())
+                .map(|q| {
+                    let mirrors: Vec<&str> = (0..queries.len()).filter(|&m| mirror_of[m] == Some(q)).map(|m| query_names[m].as_str()).collect();
+                    if palindromic[q] {
+                        "palindromic".to_string()
+                    } else if !mirrors.is_empty() {
+                        // the mirrored queries report nothing themselves
+                        format!("reverse_complement={}", mirrors.join(","))
+                    } else {
+                        ".".to_string()
+                    }
+                })
+                .collect()
+        }),
+        pam_classes: !args.alt_pams.is_empty(),
+        context: args.context.then_some(&searcher),
+        split_pam: args.split_pam,
+        seed_region: args.mismatch_positions.then_some(args.seed_region),
+        mit: args.mit_score,
+        genotypes: args.genotypes.then(|| pansn_samples(searcher.contigs().iter().map(|(name, _)| output_name(&searcher, name)))),
+    };
+    if columns.matrix.is_some() {
+        header.push_str("\tscore");
+    }
+    if columns.seed_region.is_some() {
+        header.push_str("\tmismatch_positions\tseed_mismatches");
+    }
+    if columns.mit {
+        header.push_str("\tmit");
+    }
+    if let Some((editor, _, edit_position)) = columns.base_editor {
+        header.push_str(&format!("\tedit_bases.{}>{}", editor.substrate() as char, editor.product() as char));
+        if edit_position.is_some() {
+            header.push_str("\tedit_available\tbystanders");
+        }
+    }
+    if columns.repeats.is_some() {
+        header.push_str("\trepeat");
+    }
+    if columns.population.is_some() {
+        header.push_str("\tpopulation_intact_fraction");
+    }
+    if columns.liftover.is_some() {
+        header.push_str("\tlift_seq_name\tlift_strand\tlift_start\tlift_end");
+    }
+    if columns.graph.is_some() {
+        header.push_str("\tgraph_start\tgraph_end\tgraph_walk");
+    }
+    if columns.hit_ids {
+        header.push_str("\thit_id");
+    }
+    if columns.query_notes.is_some() {
+        header.push_str("\tquery_note");
+    }
+    if columns.pam_classes {
+        header.push_str("\tpam_class");
+    }
+    if columns.context.is_some() {
+        header.push_str("\tdust\thomopolymer\tflank_gc");
+    }
+    for (sample, _) in columns.genotypes.iter().flatten() {
+        header.push_str(&format!("\t{}", sample));
+    }
+    let homology = args.homology.as_ref().map(|path| or_exit(Homology::from_path(path), EXIT_INPUT));
+    if homology.is_some() {
+        header.push_str("\tsite_id");
     }
+    let vcf = (args.format == OutputFormat::Vcf).then(|| {
+        let contigs = searcher.contigs().iter().map(|(name, length)| (output_name(&searcher, name).to_string(), *length)).collect();
+        Vcf::new(&header, contigs, args.sort_memory << 20)
+    });
+    if args.partition_by.as_deref() == Some("sample") && args.collapse_by_position {
+        eprintln!("error: collapsed sites span samples and cannot be partitioned by sample");
+        exit_with(EXIT_USAGE);
+    }
+    let partitions = args.output_dir.as_ref().map(|dir| or_exit(Partitions::create(dir, args.partition_by.as_deref().unwrap(), &header), EXIT_INCOMPLETE));
+    let rows = Rows {
+        columns: &columns,
+        query_names: &query_names,
+        out: Mutex::new(sink),
+        out_name: output.as_ref().map_or_else(|| "stdout".to_string(), |output| output.partial.display().to_string()),
+        partitions: partitions.as_ref(),
+        scatter: args.scatter_output.then(|| Scatter::new(searcher.contigs())),
+        vcf,
+        report: args.report.as_ref().map(|_| Mutex::new(Report::default())),
+        hits_fasta: args.hits_fasta.as_ref().map(|path| or_exit(HitsFasta::create(path, args.hits_fasta_flank, &searcher, &query_names), EXIT_USAGE)),
+    };
+    // rows appended to an --output file follow its existing header
+    if output.as_ref().is_none_or(|output| output.needs_header) {
+        let written = if args.invert { rows.write_lines(None, b"seq_name\tstart\tend\n") } else { rows.write_header(&header, &args.fasta) };
+        or_exit(written, EXIT_INCOMPLETE);
+    }
+    let collapse = args.collapse_by_position.then(|| Collapse::new(args.sort_memory << 20, args.collapse_tolerance));
+    // --homology: every reported row, written once the sites are known
+    let homologous = Mutex::new(Vec::new());
+    // --sample-hits: hits found and hits kept
+    let sample_counts = [AtomicU64::new(0), AtomicU64::new(0)];
+    let quotas = args.max_hits_per_sample.map(|max| Quotas::new(max, args.sort_memory << 20));
 
-    (0..n_seqs).into_par_iter().for_each(|i| {
-        let reader = Reader::from_path(fasta).unwrap(); // Re-create the reader for thread safety
-        let seq_name = reader.seq_name(i as i32).unwrap();
-        if !prefix.is_empty() && !seq_name.starts_with(prefix) {
-            return;
+    searcher.for_each_contig(&searched, |contig| {
+        // identical contigs share the hits found on the first of them; rows name them as --alias has it
+        let names: Vec<&str> = std::iter::once(contig.seq_name).chain(contig.aliases.iter().map(String::as_str)).map(|name| output_name(&searcher, name)).collect();
+        let mut contig_hits = contig.hits;
+        if args.shuffled_controls > 0 {
+            let mut raw_counts = raw_counts.lock().unwrap();
+            for (q, hits) in contig_hits.iter().enumerate() {
+                for hit in hits {
+                    raw_counts[q][hit.mismatches] += names.len() as u64;
+                }
+            }
         }
-        let seq_length = seq_lengths.get(&seq_name).unwrap();
-        let sequence_str = reader.fetch_seq_string(&seq_name, 0, *seq_length).unwrap();
-        let rev_sequence_str = get_reverse_complement(&sequence_str);
+        contig_hits.truncate(queries.len());
+        let mut reported = Vec::new();
+        for (q, mut hits) in contig_hits.into_iter().enumerate() {
+            let filtering = searched[q].budget.as_ref().map(|budget| (budget, Instant::now()));
+            if args.expectation {
+                let mut tally = tallies[q].lock().unwrap();
+                tally.observed.resize(max_mismatches + 1, 0);
+                for _ in &names {
+                    tally.composition.merge(&contig.composition);
+                    tally.windows += (contig.length + 1).saturating_sub(targets[q].len()) as u64;
+                    for hit in &hits {
+                        tally.observed[hit.mismatches] += 1;
+                    }
+                }
+            }
 
-        for sequence in [(sequence_str, "+"), (rev_sequence_str, "-")].iter() {
-            for (idx, window) in sequence.0.as_bytes().windows(target.len()).enumerate() {
-                let window_str = std::str::from_utf8(window).unwrap();
-                if cas9 && !window_str.ends_with("GG") {
-                    continue;
+            if let Some((seq_name, start, end)) = &source {
+                hits.retain(|hit| contig.seq_name != seq_name || queries[q].protospacer_interval(hit) != (*start..*end));
+            }
+            if args.canonical_strand {
+                if mirror_of[q].is_some() {
+                    hits.clear();
+                } else if palindromic[q] {
+                    hits.retain(|hit| hit.strand == Strand::Forward);
                 }
-                let mut distance = hamming_distance(window_str, &target);
-                if cas9 {
-                    // because we have N in the query sequence, we need to subtract 1 from the distance
-                    distance -= 1;
+            }
+            if constraints.is_active() {
+                hits.retain(|hit| constraints.accepts(&hit.sequence));
+            }
+            if let (true, Some(repeats)) = (args.exclude_repeats, &columns.repeats) {
+                hits.retain(|hit| columns.repeat_families(repeats, contig.seq_name, hit).is_empty());
+            }
+            if let Some(max_score) = args.max_score {
+                hits.retain(|hit| columns.score(hit).unwrap() <= max_score);
+            }
+            if let Some(max) = args.max_seed_mismatches {
+                hits.retain(|hit| offtarget::seed_mismatches(&queries[q], &offtarget::mismatch_positions(&queries[q], &hit.sequence), args.seed_region) <= max);
+            }
+            if args.min_separation > 0 {
+                hits = apply_min_separation(hits, args.min_separation);
+            }
+            if args.best_per_contig {
+                hits = hits.into_iter().min_by_key(|hit| (hit.mismatches, hit.start)).into_iter().collect();
+            }
+
+            if args.hits_consensus || args.hits_logo.is_some() {
+                profiles.add(q, names.len(), &hits);
+            }
+            if args.guide_summary {
+                let mut summary = summaries[q].lock().unwrap();
+                for hit in &hits {
+                    summary.hits[hit.mismatches] += names.len() as u64;
+                    // perfect matches, the guide's own site among them, are not off-targets
+                    if args.mit_score && hit.mismatches > 0 {
+                        let mit = offtarget::mit_score(&queries[q], &offtarget::mismatch_positions(&queries[q], &hit.sequence));
+                        summary.off_target_mit += mit.unwrap_or(0.0) * names.len() as f64;
+                    }
+                }
+            }
+
+            if args.haplotype_coverage {
+                if let Some(fewest) = hits.iter().map(|hit| hit.mismatches).min() {
+                    let mut coverage = coverages[q].lock().unwrap();
+                    for name in &names {
+                        let best = coverage.entry(pansn::parse(name).haplotype_id()).or_insert(fewest);
+                        *best = fewest.min(*best);
+                    }
+                }
+            }
+            if let Some(p) = args.sample_hits {
+                sample_counts[0].fetch_add((hits.len() * names.len()) as u64, Ordering::Relaxed);
+                hits.retain(|hit| sampled(&queries[q].sequence, contig.seq_name, hit, args.seed, p));
+                sample_counts[1].fetch_add((hits.len() * names.len()) as u64, Ordering::Relaxed);
+            }
+            reported.extend(hits);
+            if let Some((budget, started)) = filtering {
+                budget.charge(started.elapsed());
+            }
+        }
+        // counted once the quotas are known
+        if let Some(quotas) = &quotas {
+            or_exit(quotas.add(&names, &reported), EXIT_INCOMPLETE);
+            return;
+        }
+        hits_reported.fetch_add((reported.len() * names.len()) as u64, Ordering::Relaxed);
+
+        if args.invert {
+            let free = free_intervals(&reported, contig.length);
+            let mut lines = Vec::new();
+            for name in &names {
+                for (start, end) in &free {
+                    writeln!(lines, "{}\t{}\t{}", name, start, end).unwrap();
                 }
-                if distance <= max_mismatches {
-                    let mut stdout = stdout_lock.lock().unwrap();
+            }
+            or_exit(rows.write_lines(Some(contig.seq_name), &lines), EXIT_INCOMPLETE);
+            return;
+        }
 
-                    let start = if sequence.1 == "-" { *seq_length - (idx + window.len()) } else { idx };
-                    let end = if sequence.1 == "-" { *seq_length - idx } else { idx + window.len() };
+        if let Some(collapse) = &collapse {
+            or_exit(collapse.add(&names, &reported), EXIT_INCOMPLETE);
+            return;
+        }
+
+        if homology.is_some() {
+            let mut homologous = homologous.lock().unwrap();
+            for name in &names {
+                homologous.extend(reported.iter().map(|hit| (name.to_string(), hit.clone())));
+            }
+            return;
+        }
+
+        let writing = args.per_query_timeout.is_some().then(Instant::now);
+        or_exit(rows.add(contig.seq_name, &names, &reported), EXIT_INCOMPLETE);
+        if let Some(writing) = writing {
+            charge_rows(&searched, &reported, writing.elapsed());
+        }
+    });
+    if let Some(collapse) = collapse {
+        or_exit(collapse.finish(&rows), EXIT_INCOMPLETE);
+    }
+
+    if let Some(homology) = &homology {
+        let homologous = homologous.into_inner().unwrap();
+        let named: Vec<(&str, &Hit)> = homologous.iter().map(|(name, hit)| (name.as_str(), hit)).collect();
+        let mut batch = rows.batch();
+        for (&(name, hit), leader) in named.iter().zip(homology.sites(&named)) {
+            let mut row = Vec::new();
+            columns.write_hit(&mut row, name, hit);
+            // the site is named by the hit_id of its leading row, and ends the row
+            row.pop();
+            let (leader_name, leader) = named[leader];
+            writeln!(row, "\t{}", hit_id(&queries[leader.query].sequence, leader_name, leader.start, leader.strand)).unwrap();
+            or_exit(rows.stream(&mut batch, name, hit, &row), EXIT_INCOMPLETE);
+        }
+        or_exit(rows.write(&mut batch, None), EXIT_INCOMPLETE);
+    }
+
+    // --max-hits-per-sample: the (query, sample) pairs over the quota, noted on stderr below
+    let mut capped = Vec::new();
+    if let Some(quotas) = quotas {
+        let sorted;
+        (sorted, capped) = or_exit(quotas.finish(), EXIT_INCOMPLETE);
+        let mut batch = rows.batch();
+        for named in sorted {
+            let (name, hit) = &or_exit(named, EXIT_INCOMPLETE);
+            hits_reported.fetch_add(1, Ordering::Relaxed);
+            let mut row = Vec::new();
+            columns.write_hit(&mut row, name, hit);
+            or_exit(rows.stream(&mut batch, name, hit, &row), EXIT_INCOMPLETE);
+        }
+        or_exit(rows.write(&mut batch, None), EXIT_INCOMPLETE);
+    }
+
+    let report = or_exit(rows.finish(), EXIT_INCOMPLETE);
+    if let Some((cached, read)) = searcher.contig_cache_stats() {
+        eprintln!("# --cache-bytes: {} of {} contig chunks fetched from memory", cached, cached + read);
+    }
+    if let (Some(path), Some(report)) = (&args.report, &report) {
+        let contigs: Vec<(String, usize)> = searcher.contigs().iter().map(|(name, length)| (output_name(&searcher, name).to_string(), *length)).collect();
+        or_exit(report.write(path, &header, &contigs), EXIT_INCOMPLETE);
+    }
 
-                    writeln!(stdout, "{}\t{}\t{}\t{}\t{}\t{}", seq_name, sequence.1, start, end, window_str, distance).unwrap();
+    // stderr reports, one block per query
+    for (q, query) in queries.iter().enumerate() {
+        if queries.len() > 1 && (args.expectation || args.haplotype_coverage || args.hits_consensus || args.shuffled_controls > 0) {
+            eprintln!("# query {}", query_names[q]);
+        }
+        if args.expectation {
+            report_expectation(&tallies[q].lock().unwrap(), query, background_model.as_ref(), args.background_order, max_mismatches);
+        }
+        if args.haplotype_coverage {
+            report_coverage(&coverages[q].lock().unwrap(), searcher.contigs(), max_mismatches);
+        }
+        if args.hits_consensus {
+            profiles.get(q).write(&mut std::io::stderr(), &targets[q]).unwrap();
+        }
+        if args.shuffled_controls > 0 {
+            let raw_counts = raw_counts.lock().unwrap();
+            let first = queries.len() + q * args.shuffled_controls;
+            report_controls(&raw_counts[q], &raw_counts[first..first + args.shuffled_controls], max_mismatches);
+        }
+    }
+    if let Some(path) = &args.hits_logo {
+        or_exit(profiles.write_logo(path, &query_names, &targets, args.hits_logo_text), EXIT_INCOMPLETE);
+    }
+    if args.guide_summary {
+        let levels: Vec<String> = (0..=max_mismatches).map(|k| format!("mismatches.{}", k)).collect();
+        eprintln!("query\t{}{}", levels.join("\t"), if args.mit_score { "\tmit_specificity" } else { "" });
+        for (q, summary) in summaries.iter().enumerate() {
+            let summary = summary.lock().unwrap();
+            let counts: Vec<String> = summary.hits.iter().map(u64::to_string).collect();
+            let specificity = match offtarget::mit_score(&queries[q], &[]) {
+                Some(_) if args.mit_score => format!("\t{:.1}", offtarget::mit_specificity(summary.off_target_mit)),
+                _ if args.mit_score => "\t.".to_string(),
+                _ => String::new(),
+            };
+            eprintln!("{}\t{}{}", query_names[q], counts.join("\t"), specificity);
+        }
+    }
+    if args.per_query_timeout.is_some() {
+        eprintln!("query\tstatus\tseconds");
+        for (q, query) in searched[..queries.len()].iter().enumerate() {
+            let budget = query.budget.as_ref().unwrap();
+            eprintln!("{}\t{}\t{:.3}", query_names[q], if budget.is_exhausted() { "timed_out" } else { "complete" }, budget.spent().as_secs_f64());
+        }
+    }
+    let mut polymorphic_sites = std::mem::take(&mut *columns.polymorphic_sites.lock().unwrap());
+    polymorphic_sites.sort_by(|a, b| (a.0, &a.1, a.2).cmp(&(b.0, &b.1, b.2)));
+    for (query, seq_name, start, fraction) in polymorphic_sites {
+        eprintln!("warning: {}: perfect-match site {}:{} is intact in only {:.3} of samples", query_names[query], seq_name, start, fraction);
+    }
+    if let Some(p) = args.sample_hits {
+        let [found, kept] = sample_counts.map(AtomicU64::into_inner);
+        eprintln!("# sampled {} of {} hits with p = {}; estimated total from the sample: {:.0}", kept, found, p, kept as f64 / p);
+    }
+    for (query, sample, found) in capped {
+        eprintln!("# {}: {} of {} hits in sample {} reported (--max-hits-per-sample)", query_names[query], args.max_hits_per_sample.unwrap(), found, sample);
+    }
+    let failures = searcher.failures();
+    if !failures.is_empty() {
+        eprintln!("# {} contigs skipped after errors", failures.len());
+        for (seq_name, error) in &failures {
+            eprintln!("#   {}: {}", seq_name, error);
+        }
+    }
+    for (failures, without) in [(&columns.flank_failures, "flanks"), (&columns.population_failures, "a population_intact_fraction")] {
+        let failures = std::mem::take(&mut *failures.lock().unwrap());
+        if !failures.is_empty() {
+            eprintln!("# {} hits written without {} after errors", failures.values().map(|(hits, _)| hits).sum::<usize>(), without);
+            for (seq_name, (hits, error)) in &failures {
+                eprintln!("#   {}: {} hits: {}", seq_name, hits, error);
+            }
+        }
+    }
+    if searcher.retried_fetches() > 0 {
+        eprintln!("# {} reads of the FASTA succeeded after retrying", searcher.retried_fetches());
+    }
+    if let Some(path) = &args.raw_distances {
+        or_exit(write_raw_distances(path, &searcher, &queries[0], &args.raw_contigs), EXIT_INCOMPLETE);
+    }
+    if let Some(samples) = args.self_check {
+        match searcher.self_check(&queries, samples) {
+            Ok(compared) => eprintln!("self-check passed: {} hits identical to whole-contig scans", compared),
+            Err(difference) => {
+                eprintln!("self-check failed: {}", difference);
+                if let Some(manifest) = &manifest {
+                    or_exit(manifest.write("self_check_failed", &[], &[]), EXIT_INCOMPLETE);
                 }
+                exit_with(EXIT_INCOMPLETE);
             }
         }
+    }
+    // the files this run wrote, with --output under its temporary name unless it completed
+    let write_manifest = |status: &str| {
+        let Some(manifest) = &manifest else {
+            return;
+        };
+        let outputs = sinks::written(output.as_ref(), partitions.as_ref(), status == "complete", &[&args.report, &args.raw_distances, &args.hits_fasta, &args.hits_logo]);
+        let summary = [
+            ("queries", queries.len().to_string()),
+            ("contigs_searched", searcher.contigs().len().to_string()),
+            ("contigs_failed", failures.len().to_string()),
+            ("queries_timed_out", searched[..queries.len()].iter().filter(|query| query.budget.as_ref().is_some_and(|budget| budget.is_exhausted())).count().to_string()),
+            ("hits", hits_reported.load(Ordering::Relaxed).to_string()),
+            ("elapsed_seconds", format!("{:.3}", started.elapsed().as_secs_f64())),
+        ];
+        or_exit(manifest.write(status, &outputs, &summary), EXIT_INCOMPLETE);
+    };
+    if searcher.is_cancelled() {
+        match &output {
+            Some(output) => eprintln!("search timed out after {}s; partial results left in {}", args.timeout.unwrap(), output.partial.display()),
+            None => eprintln!("search timed out after {}s; results are partial", args.timeout.unwrap()),
+        }
+        write_manifest("timed_out");
+        exit_with(EXIT_INCOMPLETE);
+    }
+    if let Some(output) = &output {
+        if !failures.is_empty() {
+            eprintln!("{} is incomplete and left as {}", output.path.display(), output.partial.display());
+            write_manifest("incomplete");
+            exit_with(EXIT_INCOMPLETE);
+        }
+        or_exit(output.finish(), EXIT_INCOMPLETE);
+    }
+    write_manifest(if failures.is_empty() { "complete" } else { "incomplete" });
+    hits_reported.into_inner()
+}
+
+// Exit statuses of a search under --strict-exit; without it every error exits with 1, and a
+// search that finds nothing with 0.
+const EXIT_INCOMPLETE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NO_HITS: i32 = 3;
+// a query, library, config or annotation file that cannot be read or parsed
+const EXIT_INPUT: i32 = 4;
+// the FASTA, its index or what is read along with them (--gaps, --regions, --alias, k-mer indexes)
+const EXIT_REFERENCE: i32 = 5;
+
+static STRICT_EXIT: AtomicBool = AtomicBool::new(false);
+
+fn exit_with(status: i32) -> ! {
+    std::process::exit(if STRICT_EXIT.load(Ordering::Relaxed) { status } else { 1 })
+}
+
+// The value of an input that loaded, or the error and an exit with `status`.
+fn or_exit<T>(loaded: Result<T, String>, status: i32) -> T {
+    loaded.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        exit_with(status)
+    })
+}
+
+// The command line with the options of a search's `--config` file inserted before the given flags.
+fn with_config(argv: Vec<String>) -> Vec<String> {
+    let command = Cli::command();
+    if argv.get(1).is_some_and(|first| command.find_subcommand(first).is_some()) {
+        return argv;
+    }
+    let path = argv.iter().enumerate().find_map(|(i, token)| match token.strip_prefix("--config") {
+        Some("") => argv.get(i + 1).cloned(),
+        Some(rest) => rest.strip_prefix('=').map(str::to_string),
+        None => None,
     });
+    let Some(path) = path else {
+        return argv;
+    };
+    let flags = config::load(&path).and_then(|config| config::command_line(&config, &command, &argv[1..]).map_err(|e| format!("{}: {}", path, e)));
+    if flags.is_err() {
+        // the arguments are not parsed yet
+        STRICT_EXIT.store(argv.iter().any(|token| token == "--strict-exit"), Ordering::Relaxed);
+    }
+    let flags = or_exit(flags, EXIT_INPUT);
+    argv[..1].iter().cloned().chain(flags).chain(argv[1..].iter().cloned()).collect()
 }
 
 fn main() {
-    let args = Args::parse();
+    let matches = Cli::command().get_matches_from(preset::with_preset(with_config(std::env::args().collect())));
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // every search argument with its value, for --manifest
+    let parameters = Cli::command()
+        .get_arguments()
+        .filter_map(|arg| {
+            let values = matches.get_raw(arg.get_id().as_str())?;
+            Some((arg.get_id().to_string(), values.map(|value| value.to_string_lossy().into_owned()).collect()))
+        })
+        .collect();
+    let mut args = match cli.command {
+        Some(Command::Bench(bench_args)) => return bench::run(&bench_args),
+        Some(Command::Diff(diff_args)) => return diff::run(&diff_args),
+        Some(Command::Index(index_args)) => return index::run(&index_args),
+        Some(Command::MutationScan(scan_args)) => return mutation_scan::run(&scan_args),
+        Some(Command::ReadSupport(support_args)) => return read_support::run(&support_args),
+        Some(Command::Rescore(rescore_args)) => return rescore::run(&rescore_args),
+        Some(Command::Stats(stats_args)) => return reference_stats::run(&stats_args),
+        Some(Command::Subset(subset_args)) => return subset::run(&subset_args),
+        None => cli.search.unwrap(),
+    };
+    STRICT_EXIT.store(args.strict_exit, Ordering::Relaxed);
+    let indexed_only = indexed_options(&args);
+    if args.fasta == search::STDIN && !indexed_only.is_empty() {
+        eprintln!("error: the FASTA is read from stdin without an index, which rules out {}", indexed_only.join(", "));
+        exit_with(EXIT_USAGE);
+    }
+    // stdin cannot be looked at before it is searched
+    if args.alphabet.is_none() && args.fasta != search::STDIN {
+        args.alphabet = Some(or_exit(search::detect_alphabet(&args.fasta), EXIT_REFERENCE));
+    }
+    let nucleotide_only = nucleotide_options(&args);
+    if args.alphabet == Some(Alphabet::Protein) && !nucleotide_only.is_empty() {
+        eprintln!("error: {} is searched as protein, which rules out {}", args.fasta, nucleotide_only.join(", "));
+        exit_with(EXIT_USAGE);
+    }
+    if args.autotune {
+        tune(&mut args);
+    }
     // If parallelism is set to 0, use the default (number of available CPU cores)
     if args.parallelism > 0 {
         rayon::ThreadPoolBuilder::new()
@@ -132,5 +1915,25 @@ fn main() {
             .build_global()
             .unwrap();
     }
-    search_sequence(&args.fasta, &args.sequence, &args.prefix, args.distance, args.cas9);
+    let hits = search_sequence(&args, parameters);
+    if args.strict_exit && hits == 0 {
+        exit_with(EXIT_NO_HITS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Cli::try_parse_from(["hamming-fasta", "--fasta", "ref.fa", "--sequence", "ACGT"].iter().chain(flags)).unwrap().search.unwrap()
+    }
+
+    #[test]
+    fn refused_options_are_named_as_given() {
+        let given = args(&["--no-polyT", "--cas9", "--context", "--manifest", "run.json", "--skip-n-runs", "10"]);
+        assert_eq!(nucleotide_options(&given), ["--cas9", "--no-polyT", "--context", "--skip-n-runs"]);
+        assert_eq!(indexed_options(&given), ["--context", "--manifest"]);
+        assert!(nucleotide_options(&args(&[])).is_empty());
+    }
 }
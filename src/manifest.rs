@@ -0,0 +1,95 @@
+// `--manifest`: a JSON record of a run's inputs, parameters, outputs and summary counts for
+// workflow engines tracking provenance. It is written once the run has ended, however it ended.
+
+use hamming_fasta::md5;
+use std::fmt::Write as _;
+use std::path::Path;
+
+pub struct Manifest {
+    path: String,
+    // every argument with its value, whether given or defaulted
+    parameters: Vec<(String, Vec<String>)>,
+    inputs: Vec<String>,
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<String>>().join(", "))
+}
+
+// Path, size and MD5 of a file, or just its path when it cannot be read.
+fn file(path: &str) -> String {
+    match (std::fs::metadata(path), md5::file_md5(path)) {
+        (Ok(metadata), Ok(digest)) => format!("{{\"path\": {}, \"bytes\": {}, \"md5\": \"{}\"}}", escape(path), metadata.len(), md5::hex(&digest)),
+        _ => format!("{{\"path\": {}}}", escape(path)),
+    }
+}
+
+impl Manifest {
+    pub fn new(path: &str, parameters: Vec<(String, Vec<String>)>, inputs: Vec<String>) -> Manifest {
+        Manifest { path: path.to_string(), parameters, inputs }
+    }
+
+    // `status` tells a complete run from a timed-out or failed one; `summary` holds counts.
+    pub fn write(&self, status: &str, outputs: &[String], summary: &[(&str, String)]) -> Result<(), String> {
+        let mut json = String::from("{\n");
+        writeln!(json, "  \"tool\": \"hamming-fasta\",\n  \"version\": {},", escape(env!("CARGO_PKG_VERSION"))).unwrap();
+        writeln!(json, "  \"command_line\": {},", list(std::env::args().map(|arg| escape(&arg)))).unwrap();
+        writeln!(json, "  \"status\": {},", escape(status)).unwrap();
+        writeln!(json, "  \"inputs\": {},", list(self.inputs.iter().map(|path| file(path)))).unwrap();
+        let parameters = self.parameters.iter().map(|(name, values)| match values.as_slice() {
+            [value] => format!("{}: {}", escape(name), escape(value)),
+            values => format!("{}: {}", escape(name), list(values.iter().map(|value| escape(value)))),
+        });
+        writeln!(json, "  \"parameters\": {{{}}},", parameters.collect::<Vec<String>>().join(", ")).unwrap();
+        writeln!(json, "  \"outputs\": {},", list(outputs.iter().map(|path| file(path)))).unwrap();
+        let summary = summary.iter().map(|(name, value)| format!("{}: {}", escape(name), value));
+        writeln!(json, "  \"summary\": {{{}}}\n}}", summary.collect::<Vec<String>>().join(", ")).unwrap();
+        // renamed into place, so a reader never sees half a manifest
+        let partial = format!("{}.partial", self.path);
+        std::fs::write(&partial, json).map_err(|e| format!("{}: {}", partial, e))?;
+        std::fs::rename(&partial, Path::new(&self.path)).map_err(|e| format!("{}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_listed_with_their_size_and_md5() {
+        let dir = std::env::temp_dir().join(format!("hamming-fasta-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, path) = (dir.join("in.fa"), dir.join("run.json"));
+        std::fs::write(&input, "abc").unwrap();
+        let parameters = vec![("distance".to_string(), vec!["2".to_string()]), ("alias".to_string(), vec!["a.tsv".to_string(), "b\"c.tsv".to_string()])];
+        let manifest = Manifest::new(path.to_str().unwrap(), parameters, vec![input.display().to_string()]);
+        manifest.write("timed_out", &[dir.join("missing.tsv").display().to_string()], &[("hits", "7".to_string())]).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        let partial_left = dir.join("run.json.partial").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!partial_left);
+        assert!(json.contains("\"status\": \"timed_out\","));
+        // the MD5 of "abc", as RFC 1321 has it
+        assert!(json.contains(&format!("\"inputs\": [{{\"path\": {}, \"bytes\": 3, \"md5\": \"900150983cd24fb0d6963f7d28e17f72\"}}],", escape(&input.display().to_string()))));
+        assert!(json.contains("\"parameters\": {\"distance\": \"2\", \"alias\": [\"a.tsv\", \"b\\\"c.tsv\"]},"));
+        assert!(json.contains(&format!("\"outputs\": [{{\"path\": {}}}],", escape(&dir.join("missing.tsv").display().to_string()))));
+        assert!(json.ends_with("  \"summary\": {\"hits\": 7}\n}\n"));
+    }
+}
@@ -0,0 +1,181 @@
+// Plain FASTAs on a local disk are memory-mapped and read through their .fai directly: a
+// range is copied out of the page cache in one pass that drops the line ends, instead of
+// being read by htslib into a buffer and copied again into a String. It is one copy, not zero:
+// the scanner needs the bases without line ends, which the mapping does not hold contiguously
+// for a range spanning lines. Compressed FASTAs, those on network filesystems, where a mapping
+// can fault when the server goes away, and anything that cannot be mapped are read through
+// htslib as before.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::AsRawFd;
+
+// A record of the .fai: where its bases start and how its lines are laid out.
+struct Record {
+    length: usize,
+    offset: usize,
+    line_bases: usize,
+    line_width: usize,
+}
+
+pub struct MappedFasta {
+    data: *const u8,
+    len: usize,
+    records: HashMap<String, Record>,
+}
+
+// the mapping is read-only and lives as long as the struct
+unsafe impl Send for MappedFasta {}
+unsafe impl Sync for MappedFasta {}
+
+// Filesystems a mapping should not depend on, by statfs magic.
+#[cfg(target_os = "linux")]
+fn is_remote(path: &str) -> bool {
+    const REMOTE: [i64; 8] = [0x6969, 0x517b, 0xff534d42u32 as i64, 0xfe534d42u32 as i64, 0x65735546, 0x5346414f, 0x00c36400, 0x0bd00bd0];
+    let Ok(path) = std::ffi::CString::new(path) else {
+        return true;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return true;
+    }
+    REMOTE.contains(&(stat.f_type as i64))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_remote(_: &str) -> bool {
+    false
+}
+
+impl MappedFasta {
+    // The mapping of `fasta`, or None where it is read through htslib.
+    pub fn open(fasta: &str) -> Option<MappedFasta> {
+        let file = File::open(fasta).ok()?;
+        let len = file.metadata().ok()?.len() as usize;
+        if len < 2 || is_remote(fasta) {
+            return None;
+        }
+        let mut records = HashMap::new();
+        for line in BufReader::new(File::open(format!("{}.fai", fasta)).ok()?).lines() {
+            let line = line.ok()?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [name, length, offset, line_bases, line_width, ..] = fields[..] else {
+                return None;
+            };
+            let record = Record { length: length.parse().ok()?, offset: offset.parse().ok()?, line_bases: line_bases.parse().ok()?, line_width: line_width.parse().ok()? };
+            if record.line_bases == 0 || record.line_width < record.line_bases || record.offset > len {
+                return None;
+            }
+            // faidx only reaches the first record of a name, see `DuplicateNames`
+            records.entry(name.to_string()).or_insert(record);
+        }
+        let data = unsafe { libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0) };
+        if data == libc::MAP_FAILED {
+            return None;
+        }
+        let mapped = MappedFasta { data: data as *const u8, len, records };
+        // BGZF and gzip start with the gzip magic
+        if mapped.bytes()[..2] == [0x1f, 0x8b] {
+            return None;
+        }
+        Some(mapped)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+    }
+
+    // [start, end) of `seq_name`, as htslib would return it.
+    pub fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        let record = self.records.get(seq_name).ok_or_else(|| format!("no sequence {}", seq_name))?;
+        let end = end.min(record.length);
+        let bytes = self.bytes();
+        let mut bases = Vec::with_capacity(end.saturating_sub(start));
+        let mut pos = start;
+        while pos < end {
+            let in_line = pos % record.line_bases;
+            let at = record.offset + pos / record.line_bases * record.line_width + in_line;
+            let take = (record.line_bases - in_line).min(end - pos);
+            let line = bytes.get(at..at + take).ok_or_else(|| format!("{} is truncated", seq_name))?;
+            // lines the index does not describe, which htslib would read past
+            if !line.iter().all(u8::is_ascii_graphic) {
+                return Err(format!("{}: lines do not match the index", seq_name));
+            }
+            bases.extend_from_slice(line);
+            pos += take;
+        }
+        Ok(String::from_utf8(bases).unwrap())
+    }
+}
+
+impl Drop for MappedFasta {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.data as *mut libc::c_void, self.len) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_htslib::faidx;
+
+    // A FASTA of `(name, bases, bases per line, line end)` records and its index.
+    fn write_fasta(path: &str, records: &[(&str, &str, usize, &str)]) {
+        let (mut fasta, mut fai) = (String::new(), String::new());
+        for &(name, seq, line_bases, line_end) in records {
+            fasta.push_str(&format!(">{} description{}", name, line_end));
+            fai.push_str(&format!("{}\t{}\t{}\t{}\t{}\n", name, seq.len(), fasta.len(), line_bases, line_bases + line_end.len()));
+            for line in seq.as_bytes().chunks(line_bases) {
+                fasta.push_str(std::str::from_utf8(line).unwrap());
+                fasta.push_str(line_end);
+            }
+        }
+        std::fs::write(path, fasta).unwrap();
+        std::fs::write(format!("{}.fai", path), fai).unwrap();
+    }
+
+    #[test]
+    fn ranges_match_faidx() {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-mapped-{}.fa", std::process::id()));
+        let path = path.to_str().unwrap();
+        let seq = "ACGTNacgtnRYKMACGTTGCAAGGCCTTAAGGCTAGCTAGGATCCAGTAC";
+        // a short last line, CRLF line ends, lines of one base, and a last line that is full
+        let records = [("odd", &seq[..50], 7, "\n"), ("crlf", &seq[3..26], 5, "\r\n"), ("single", &seq[..9], 1, "\n"), ("full", &seq[10..34], 6, "\n")];
+        write_fasta(path, &records);
+        let mapped = MappedFasta::open(path).unwrap();
+        let reader = faidx::Reader::from_path(path).unwrap();
+        for (name, bases, _, _) in records {
+            for start in 0..bases.len() {
+                // up to and past the end of the contig
+                for end in start + 1..bases.len() + 4 {
+                    let expected = std::str::from_utf8(reader.fetch_seq(name, start, end - 1).unwrap()).unwrap().to_string();
+                    assert_eq!(mapped.bases(name, start, end), Ok(expected), "{} [{}, {})", name, start, end);
+                    assert_eq!(mapped.bases(name, start, end).unwrap(), bases[start..end.min(bases.len())], "{} [{}, {})", name, start, end);
+                }
+            }
+        }
+        assert_eq!(mapped.bases("odd", 10, 10), Ok(String::new()));
+        assert_eq!(mapped.bases("missing", 0, 1), Err("no sequence missing".to_string()));
+        drop(mapped);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(format!("{}.fai", path)).unwrap();
+    }
+
+    #[test]
+    fn lines_the_index_does_not_describe_are_refused() {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-unmapped-{}.fa", std::process::id()));
+        let path = path.to_str().unwrap();
+        // the index claims 8 bases per line, the file has 6
+        std::fs::write(path, ">c\nACGTAC\nGTACGT\n").unwrap();
+        std::fs::write(format!("{}.fai", path), "c\t12\t3\t8\t9\n").unwrap();
+        let mapped = MappedFasta::open(path).unwrap();
+        assert_eq!(mapped.bases("c", 0, 6), Ok("ACGTAC".to_string()));
+        assert_eq!(mapped.bases("c", 0, 8), Err("c: lines do not match the index".to_string()));
+        // and one past the end of the file
+        std::fs::write(format!("{}.fai", path), "c\t20\t3\t6\t7\n").unwrap();
+        assert_eq!(MappedFasta::open(path).unwrap().bases("c", 12, 20), Err("c is truncated".to_string()));
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(format!("{}.fai", path)).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+// Substitution costs for scoring hits by mismatch identity rather than count.
+//
+// The matrix file is whitespace separated: a header row naming the target bases and one
+// row per query base, e.g.
+//
+//     A   C   G   T   N
+//   A 0   1   0.5 1   1
+//   ...
+//
+// Lines starting with '#' are ignored. Bases without a row or column cost 1 when different
+// and 0 when equal.
+
+use std::fs;
+
+const ALPHABET: [u8; 5] = [b'A', b'C', b'G', b'T', b'N'];
+
+fn index(b: u8) -> usize {
+    match b.to_ascii_uppercase() {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => 4,
+    }
+}
+
+pub struct ScoringMatrix {
+    cost: [[f64; 5]; 5],
+}
+
+impl ScoringMatrix {
+    pub fn from_path(path: &str) -> Result<ScoringMatrix, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut cost = [[1.0; 5]; 5];
+        for (i, row) in cost.iter_mut().enumerate() {
+            row[i] = 0.0;
+        }
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+        let columns: Vec<usize> = lines
+            .next()
+            .ok_or_else(|| format!("{}: empty scoring matrix", path))?
+            .split_whitespace()
+            .map(|c| column_index(c, path))
+            .collect::<Result<_, _>>()?;
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let row = column_index(fields.next().unwrap(), path)?;
+            let values: Vec<&str> = fields.collect();
+            if values.len() != columns.len() {
+                return Err(format!("{}: row '{}' has {} values, expected {}", path, line, values.len(), columns.len()));
+            }
+            for (&column, value) in columns.iter().zip(values) {
+                cost[row][column] = value.parse().map_err(|_| format!("{}: invalid cost '{}'", path, value))?;
+            }
+        }
+        Ok(ScoringMatrix { cost })
+    }
+
+    // Total substitution cost of aligning `target` (the reported hit) against `query`.
+    pub fn score(&self, query: &str, target: &str) -> f64 {
+        query.bytes().zip(target.bytes()).map(|(q, t)| self.cost[index(q)][index(t)]).sum()
+    }
+}
+
+fn column_index(name: &str, path: &str) -> Result<usize, String> {
+    match name.as_bytes() {
+        [b] if ALPHABET.contains(&b.to_ascii_uppercase()) => Ok(index(*b)),
+        _ => Err(format!("{}: unknown base '{}' in scoring matrix", path, name)),
+    }
+}
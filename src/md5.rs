@@ -0,0 +1,190 @@
+// MD5 (RFC 1321) of contig sequences, stored in the indexes built by `index` to tell whether
+// the FASTA they were built from has changed. Like the M5 of SAM/CRAM headers and VCF contig
+// lines, it is taken over the uppercase bases, so it can be compared with `samtools dict`.
+
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16,
+    23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+// The table of RFC 1321: the integer part of 2^32 * |sin(i + 1)|, written out rather than
+// computed, as `f64::sin` need not round the same on every platform.
+const SINES: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+fn block(state: &mut [u32; 4], chunk: &[u8]) {
+    let words: [u32; 16] = std::array::from_fn(|i| u32::from_le_bytes(chunk[4 * i..4 * i + 4].try_into().unwrap()));
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let rotated = a.wrapping_add(f).wrapping_add(SINES[i]).wrapping_add(words[g]).rotate_left(SHIFTS[i]);
+        (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+    }
+    for (s, x) in state.iter_mut().zip([a, b, c, d]) {
+        *s = s.wrapping_add(x);
+    }
+}
+
+// The MD5 of a sequence given in pieces. By default its bases are uppercased first, the M5
+// convention, so it is not the checksum of the bytes themselves; `Md5::of_bytes` hashes them
+// as given, as `md5sum` does.
+pub struct Md5 {
+    state: [u32; 4],
+    uppercase: bool,
+    // bases not yet making up a whole block
+    pending: Vec<u8>,
+    length: u64,
+}
+
+impl Default for Md5 {
+    fn default() -> Md5 {
+        Md5 { state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476], uppercase: true, pending: Vec::with_capacity(64), length: 0 }
+    }
+}
+
+impl Md5 {
+    pub fn of_bytes() -> Md5 {
+        Md5 { uppercase: false, ..Md5::default() }
+    }
+
+    // Adds the next piece, uppercased unless made by `of_bytes`.
+    pub fn update(&mut self, mut seq: &[u8]) {
+        let upper = |bytes: &[u8]| if self.uppercase { bytes.to_ascii_uppercase() } else { bytes.to_vec() };
+        self.length += seq.len() as u64;
+        if !self.pending.is_empty() {
+            let n = (64 - self.pending.len()).min(seq.len());
+            self.pending.extend(upper(&seq[..n]));
+            seq = &seq[n..];
+            if self.pending.len() < 64 {
+                return;
+            }
+            block(&mut self.state, &self.pending);
+            self.pending.clear();
+        }
+        let mut chunks = seq.chunks_exact(64);
+        let mut copy = [0u8; 64];
+        for chunk in &mut chunks {
+            copy.copy_from_slice(chunk);
+            if self.uppercase {
+                copy.make_ascii_uppercase();
+            }
+            block(&mut self.state, &copy);
+        }
+        self.pending.extend(upper(chunks.remainder()));
+    }
+
+    pub fn finish(mut self) -> [u8; 16] {
+        let mut tail = std::mem::take(&mut self.pending);
+        tail.push(0x80);
+        while tail.len() % 64 != 56 {
+            tail.push(0);
+        }
+        tail.extend(self.length.wrapping_mul(8).to_le_bytes());
+        for chunk in tail.chunks_exact(64) {
+            block(&mut self.state, chunk);
+        }
+        let mut digest = [0u8; 16];
+        for (bytes, s) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&s.to_le_bytes());
+        }
+        digest
+    }
+}
+
+pub fn sequence_md5(seq: &[u8]) -> [u8; 16] {
+    let mut md5 = Md5::default();
+    md5.update(seq);
+    md5.finish()
+}
+
+// The checksum of a file's bytes as they are, as `md5sum` gives it.
+pub fn file_md5(path: &str) -> Result<[u8; 16], String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut md5 = Md5::of_bytes();
+    loop {
+        let n = std::io::Read::read(&mut file, &mut buffer).map_err(|e| format!("{}: {}", path, e))?;
+        if n == 0 {
+            break;
+        }
+        md5.update(&buffer[..n]);
+    }
+    Ok(md5.finish())
+}
+
+pub fn hex(digest: &[u8; 16]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hashed in pieces of 1, 2, 3, ... bytes, so pieces start and end anywhere in a block.
+    fn in_pieces(mut md5: Md5, bytes: &[u8]) -> String {
+        let (mut at, mut n) = (0, 1);
+        while at < bytes.len() {
+            let end = (at + n).min(bytes.len());
+            md5.update(&bytes[at..end]);
+            (at, n) = (end, n % 70 + 1);
+        }
+        hex(&md5.finish())
+    }
+
+    #[test]
+    fn rfc_1321_vectors() {
+        let digits = "1234567890".repeat(8);
+        let vectors = [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            ("abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+            ("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", "d174ab98d277d9f5a5611c2c9f419d9f"),
+            (digits.as_str(), "57edf4a22be3c955ac49da2e2107b67a"),
+        ];
+        for (input, digest) in vectors {
+            let mut md5 = Md5::of_bytes();
+            md5.update(input.as_bytes());
+            assert_eq!(hex(&md5.finish()), digest, "{:?}", input);
+            assert_eq!(in_pieces(Md5::of_bytes(), input.as_bytes()), digest, "{:?} in pieces", input);
+        }
+        assert_eq!(in_pieces(Md5::of_bytes(), &[b'a'; 1_000_000]), "7707d6ae4e027c70eea2a935c2296f21");
+    }
+
+    #[test]
+    fn sequences_are_hashed_in_uppercase() {
+        let seq = b"acgtNNNNacgtACGTrykm".repeat(13);
+        let mut upper = Md5::of_bytes();
+        upper.update(&seq.to_ascii_uppercase());
+        let upper = hex(&upper.finish());
+        assert_eq!(hex(&sequence_md5(&seq)), upper);
+        assert_eq!(in_pieces(Md5::default(), &seq), upper);
+        // `samtools dict` gives this M5 for a contig of ACGT
+        assert_eq!(hex(&sequence_md5(b"acgt")), "f1f8f4bf413b16ad135722aa4591043e");
+    }
+
+    #[test]
+    fn files_are_hashed_as_they_are() {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-md5-{}.fa", std::process::id()));
+        std::fs::write(&path, b">chr1\nacgt\n").unwrap();
+        let digest = file_md5(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        // md5sum of the file
+        assert_eq!(hex(&digest.unwrap()), "3934f8dc5d04615fcf4a6fcc37b9c9de");
+        assert!(file_md5("/nonexistent/chr1.fa").unwrap_err().starts_with("/nonexistent/chr1.fa: "));
+    }
+}
@@ -0,0 +1,112 @@
+// Per-contig minimizer sketches, built by `index --minimizers`. Every window of w consecutive
+// k-mers has one minimizer, so a contig holding a query piece exactly holds all the piece's
+// minimizers; a contig missing one from every pigeonhole piece cannot hold a hit.
+
+use crate::search::{Pieces, Prefilter, Query};
+use crate::sketch::{self, canonical_kmers, mix, MAX_K};
+use std::collections::{HashMap, VecDeque};
+
+const MAGIC: &[u8; 8] = b"HFMINIM2";
+
+pub struct MinimizerSketch {
+    k: usize,
+    w: usize,
+    // per contig: indexed length, MD5 and sorted distinct minimizers
+    contigs: HashMap<String, (usize, [u8; 16], Vec<u64>)>,
+}
+
+pub fn sketch_path(fasta: &str) -> String {
+    format!("{}.minimizers", fasta)
+}
+
+// Canonical k-mers that are the smallest (by hash) of some run of `w` consecutive k-mers, so
+// both strands of a sequence have the same minimizers.
+fn minimizers(seq: &[u8], k: usize, w: usize, mut f: impl FnMut(u64)) {
+    // candidates (hash, k-mer, position) of the current window, increasing by hash
+    let mut window: VecDeque<(u64, u64, usize)> = VecDeque::new();
+    let (mut run_start, mut last_pos, mut last_reported) = (0, None, None);
+    canonical_kmers(seq, k, |pos, kmer| {
+        // k-mers interrupted by a base other than ACGT start a new run
+        if last_pos.is_none_or(|last| last + 1 != pos) {
+            window.clear();
+            run_start = pos;
+        }
+        last_pos = Some(pos);
+        let candidate = (mix(kmer), kmer, pos);
+        while window.back().is_some_and(|&(hash, kmer, _)| (hash, kmer) >= (candidate.0, candidate.1)) {
+            window.pop_back();
+        }
+        window.push_back(candidate);
+        while window.front().is_some_and(|&(_, _, start)| start + w <= pos) {
+            window.pop_front();
+        }
+        if pos + 1 >= run_start + w {
+            let &(_, kmer, start) = window.front().unwrap();
+            if last_reported != Some(start) {
+                f(kmer);
+                last_reported = Some(start);
+            }
+        }
+    });
+}
+
+// Builds sketches of `k`-mer minimizers over windows of `w` k-mers and writes them next to the FASTA.
+pub fn build(fasta: &str, contigs: &[(String, usize)], k: usize, w: usize) -> Result<(), String> {
+    if k == 0 || k > MAX_K || w == 0 {
+        return Err(format!("minimizer k must be between 1 and {} and w at least 1", MAX_K));
+    }
+    sketch::build(fasta, &sketch_path(fasta), MAGIC, [k as u32, w as u32], contigs, |seq| sketch_of(seq, k, w))
+}
+
+// Adds sketches of the `contigs` of `added` to those of `fasta`, with the k and w they were built with.
+pub fn add(fasta: &str, added: &str, contigs: &[(String, usize)]) -> Result<(), String> {
+    let path = sketch_path(fasta);
+    let [k, w] = sketch::params(&path, MAGIC, "index --minimizers")?;
+    sketch::append(added, &path, contigs, |seq| sketch_of(seq, k as usize, w as usize))
+}
+
+// The sorted distinct minimizers of a contig.
+fn sketch_of(seq: &[u8], k: usize, w: usize) -> Vec<u64> {
+    let mut sketch = Vec::new();
+    minimizers(seq, k, w, |kmer| sketch.push(kmer));
+    sketch.sort_unstable();
+    sketch.dedup();
+    sketch
+}
+
+impl MinimizerSketch {
+    pub fn load(fasta: &str) -> Result<MinimizerSketch, String> {
+        let ([k, w], contigs) = sketch::load(&sketch_path(fasta), MAGIC, "index --minimizers")?;
+        Ok(MinimizerSketch { k: k as usize, w: w as usize, contigs })
+    }
+}
+
+impl Prefilter for MinimizerSketch {
+    fn length(&self, seq_name: &str) -> Option<usize> {
+        self.contigs.get(seq_name).map(|(length, _, _)| *length)
+    }
+
+    fn md5(&self, seq_name: &str) -> Option<[u8; 16]> {
+        self.contigs.get(seq_name).map(|(_, md5, _)| *md5)
+    }
+
+    // The minimizers of each pigeonhole piece; pieces must span at least one window.
+    fn pieces(&self, query: &Query) -> Option<Pieces> {
+        let pieces = sketch::pigeonhole_pieces(query, self.k + self.w - 1)?
+            .into_iter()
+            .map(|piece| {
+                let mut kmers = Vec::new();
+                minimizers(piece, self.k, self.w, |kmer| kmers.push(kmer));
+                kmers
+            })
+            .collect();
+        Some(pieces)
+    }
+
+    fn may_contain(&self, seq_name: &str, pieces: &[Vec<u64>]) -> bool {
+        match self.contigs.get(seq_name) {
+            Some((_, _, sketch)) => pieces.iter().any(|kmers| kmers.iter().all(|kmer| sketch.binary_search(kmer).is_ok())),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// `mutation-scan`: search every single-base mutant of the query in one pass and report how the
+// hit counts change, showing which query positions carry its specificity.
+
+use hamming_fasta::{Query, SearchConfig, Searcher};
+use std::sync::Mutex;
+
+#[derive(clap::Args, Debug)]
+pub struct MutationScanArgs {
+    /// Path to the FASTA file
+    #[arg(short, long)]
+    fasta: String,
+
+    /// Target sequence to mutate and search for
+    #[arg(short, long)]
+    sequence: String,
+
+    /// Prefix for the sequence names to search within
+    #[arg(short = 'p', long, default_value = "")]
+    prefix: String,
+
+    /// Maximum number of mismatches allowed (Hamming distance)
+    #[arg(short, long, default_value_t = 6)]
+    distance: usize,
+
+    /// Number of threads for parallel execution
+    #[arg(short = 't', long, default_value = "0")]
+    parallelism: usize,
+
+    /// Flag for the presence of a Cas9 PAM sequence; only the protospacer is mutated
+    #[arg(short = 'c', long, default_value = "false")]
+    cas9: bool,
+}
+
+pub fn run(args: &MutationScanArgs) {
+    if args.parallelism > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.parallelism).build_global().unwrap();
+    }
+    let original = Query::new(&args.sequence, args.distance).sequence.to_ascii_uppercase();
+    // the unmutated query first, then every alternative base at every position
+    let mut mutants = vec![(0, '.', '.', original.clone())];
+    for (i, from) in original.char_indices() {
+        for to in ['A', 'C', 'G', 'T'].into_iter().filter(|&to| to != from) {
+            let mut mutant = original.clone();
+            mutant.replace_range(i..i + 1, &to.to_string());
+            mutants.push((i + 1, from, to, mutant));
+        }
+    }
+    let queries: Vec<Query> = mutants
+        .iter()
+        .map(|(_, _, _, sequence)| {
+            let mut query = Query::new(sequence, args.distance);
+            query.cas9 = args.cas9;
+            query
+        })
+        .collect();
+
+    let config = SearchConfig { prefix: args.prefix.clone(), ..SearchConfig::default() };
+    let searcher = Searcher::new(&args.fasta, config).unwrap();
+    // hit counts per query and mismatch level
+    let counts = Mutex::new(vec![vec![0u64; args.distance + 1]; queries.len()]);
+    searcher.for_each_contig(&queries, |contig| {
+        let copies = 1 + contig.aliases.len() as u64;
+        let mut counts = counts.lock().unwrap();
+        for (q, hits) in contig.hits.iter().enumerate() {
+            for hit in hits {
+                counts[q][hit.mismatches] += copies;
+            }
+        }
+    });
+    let counts = counts.into_inner().unwrap();
+
+    let levels: Vec<String> = (0..=args.distance).map(|k| format!("\tmismatches.{}", k)).collect();
+    println!("position\tfrom\tto\thits\thits_change{}", levels.concat());
+    let original_hits: u64 = counts[0].iter().sum();
+    for ((position, from, to, _), counts) in mutants.iter().zip(&counts) {
+        let hits: u64 = counts.iter().sum();
+        let by_level: Vec<String> = counts.iter().map(|count| format!("\t{}", count)).collect();
+        println!("{}\t{}\t{}\t{}\t{:+}\t{}", position, from, to, hits, hits as i64 - original_hits as i64, by_level.concat().trim_start());
+    }
+}
@@ -0,0 +1,52 @@
+// Where a Cas9 off-target differs from its guide, and how likely it is to be cut for that.
+
+use crate::search::{degenerate_matches, Query};
+
+// Hsu et al. (2013) weights of a mismatch at each protospacer position, PAM-distal first.
+const MIT_WEIGHTS: [f64; 20] = [0.0, 0.0, 0.014, 0.0, 0.0, 0.395, 0.317, 0.0, 0.389, 0.079, 0.445, 0.508, 0.613, 0.851, 0.732, 0.828, 0.615, 0.804, 0.685, 0.583];
+
+// 0-based protospacer positions, counted from its 5' end, where `window` (in query
+// orientation) differs from the query as the mismatch count sees it: ignored positions never
+// differ, and with --degenerate a code matches the bases it stands for.
+pub fn mismatch_positions(query: &Query, window: &str) -> Vec<usize> {
+    let protospacer = query.protospacer(window).as_bytes();
+    protospacer
+        .iter()
+        .zip(query.sequence.as_bytes())
+        .enumerate()
+        .filter(|&(i, (&w, &q))| !query.ignored.contains(&i) && w != q && !(query.degenerate && degenerate_matches(w, q)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Mismatches among the `region` protospacer bases next to the PAM, or at the 3' end of a query
+// without one.
+pub fn seed_mismatches(query: &Query, positions: &[usize], region: usize) -> usize {
+    let length = query.sequence.len();
+    positions.iter().filter(|&&i| if query.cas9 && query.pam_5prime { i < region } else { i + region >= length }).count()
+}
+
+// The MIT off-target score, 0 to 100, of a 20-base protospacer with a 3' PAM mismatched at
+// `positions`: the weights of the mismatched positions, lowered further for mismatches close
+// together and for many of them. None for other guides, for which the weights are not known.
+pub fn mit_score(query: &Query, positions: &[usize]) -> Option<f64> {
+    if query.sequence.len() != MIT_WEIGHTS.len() || (query.cas9 && query.pam_5prime) {
+        return None;
+    }
+    let weights: f64 = positions.iter().map(|&i| 1.0 - MIT_WEIGHTS[i]).product();
+    let closeness = match positions {
+        [first, .., last] => {
+            let mean_distance = (last - first) as f64 / (positions.len() - 1) as f64;
+            1.0 / ((19.0 - mean_distance) / 19.0 * 4.0 + 1.0)
+        }
+        _ => 1.0,
+    };
+    let count = 1.0 / (positions.len().max(1) as f64).powi(2);
+    Some(100.0 * weights * closeness * count)
+}
+
+// The MIT specificity of a guide from the scores of its off-targets: 100 with none, lower the
+// more likely they are to be cut.
+pub fn mit_specificity(off_target_scores: f64) -> f64 {
+    100.0 * 100.0 / (100.0 + off_target_scores)
+}
@@ -0,0 +1,27 @@
+// PanSN-spec sequence names: `sample#haplotype#contig`.
+
+const DELIMITER: char = '#';
+
+pub struct PanSn<'a> {
+    pub sample: Option<&'a str>,
+    pub haplotype: Option<&'a str>,
+    pub contig: &'a str,
+}
+
+pub fn parse(name: &str) -> PanSn<'_> {
+    let mut parts = name.splitn(3, DELIMITER);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(sample), Some(haplotype), Some(contig)) => PanSn { sample: Some(sample), haplotype: Some(haplotype), contig },
+        _ => PanSn { sample: None, haplotype: None, contig: name },
+    }
+}
+
+impl PanSn<'_> {
+    // `sample#haplotype`, or the bare contig name for names outside the spec
+    pub fn haplotype_id(&self) -> String {
+        match (self.sample, self.haplotype) {
+            (Some(sample), Some(haplotype)) => format!("{}{}{}", sample, DELIMITER, haplotype),
+            _ => self.contig.to_string(),
+        }
+    }
+}
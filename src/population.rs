@@ -0,0 +1,71 @@
+// Screening of hit sites against a population VCF (bgzipped, with a tabix index): the fraction
+// of samples carrying only reference alleles over a whole site, PAM included.
+
+use rust_htslib::tbx::{self, Read};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+pub struct Population {
+    // lookups come from the threads writing hits
+    reader: Mutex<tbx::Reader>,
+    indexed: HashSet<String>,
+    samples: usize,
+}
+
+impl Population {
+    pub fn from_path(path: &str) -> Result<Population, String> {
+        let reader = tbx::Reader::from_path(path).map_err(|e| format!("{}: {}", path, e))?;
+        let columns = reader.header().iter().rev().find(|line| line.starts_with("#CHROM")).ok_or_else(|| format!("{}: no #CHROM header line", path))?;
+        let samples = columns.split('\t').count().saturating_sub(9);
+        if samples == 0 {
+            return Err(format!("{}: no samples", path));
+        }
+        let indexed = reader.seqnames().into_iter().collect();
+        Ok(Population { reader: Mutex::new(reader), indexed, samples })
+    }
+
+    // Whether the VCF has records of `seq_name`.
+    pub fn indexes(&self, seq_name: &str) -> bool {
+        self.indexed.contains(seq_name)
+    }
+
+    // Of the samples with a called genotype at every variant overlapping [start, end), the
+    // fraction without an alternative allele there; None when no sample is called throughout.
+    // A contig absent from the VCF has no known variants, so its sites are intact in all.
+    pub fn intact_fraction(&self, seq_name: &str, start: usize, end: usize) -> Result<Option<f64>, String> {
+        if !self.indexed.contains(seq_name) {
+            return Ok(Some(1.0));
+        }
+        let mut reader = self.reader.lock().unwrap();
+        let tid = reader.tid(seq_name).map_err(|e| e.to_string())?;
+        reader.fetch(tid, start as u64, end as u64).map_err(|e| e.to_string())?;
+        let (mut altered, mut missing) = (vec![false; self.samples], vec![false; self.samples]);
+        for record in reader.records() {
+            let record = record.map_err(|e| e.to_string())?;
+            let line = String::from_utf8_lossy(&record);
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 9 + self.samples {
+                return Err(format!("malformed VCF record: {}", line));
+            }
+            let pos: usize = fields[1].parse().map_err(|_| format!("malformed VCF record: {}", line))?;
+            let (ref_start, ref_end) = (pos - 1, pos - 1 + fields[3].len());
+            // a deletion starting before the site still removes bases of it
+            if ref_end <= start || ref_start >= end {
+                continue;
+            }
+            for (i, sample) in fields[9..9 + self.samples].iter().enumerate() {
+                let genotype = sample.split(':').next().unwrap();
+                for allele in genotype.split(['/', '|']) {
+                    match allele {
+                        "0" => {}
+                        "." => missing[i] = true,
+                        _ => altered[i] = true,
+                    }
+                }
+            }
+        }
+        let intact = (0..self.samples).filter(|&i| !altered[i] && !missing[i]).count();
+        let called = intact + altered.iter().filter(|&&a| a).count();
+        Ok((called > 0).then(|| intact as f64 / called as f64))
+    }
+}
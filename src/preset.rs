@@ -0,0 +1,50 @@
+// `--preset`: a named set of search options for a common task. They are inserted into the
+// command line like the options of a config file, so any given there still win.
+
+use crate::config::{self, RunConfig, Value};
+use clap::CommandFactory;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    // off-targets of Cas9 guides, with what the usual design tools report about them
+    Crispr,
+}
+
+impl std::str::FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "crispr" => Ok(Preset::Crispr),
+            _ => Err(format!("unknown preset '{}': expected crispr", s)),
+        }
+    }
+}
+
+impl Preset {
+    fn options(self) -> Vec<(String, Value)> {
+        let options = match self {
+            Preset::Crispr => [("cas9", None), ("distance", Some("4")), ("split_pam", None), ("mismatch_positions", None), ("mit_score", None), ("context", None), ("guide_summary", None)],
+        };
+        options.into_iter().map(|(key, value)| (key.to_string(), value.map_or(Value::Bool(true), |value| Value::Scalar(value.to_string())))).collect()
+    }
+}
+
+// The command line with the options of its `--preset` inserted before the given flags.
+pub fn with_preset(argv: Vec<String>) -> Vec<String> {
+    let command = crate::Cli::command();
+    if argv.get(1).is_some_and(|first| command.find_subcommand(first).is_some()) {
+        return argv;
+    }
+    let name = argv.iter().enumerate().find_map(|(i, token)| match token.strip_prefix("--preset") {
+        Some("") => argv.get(i + 1).cloned(),
+        Some(rest) => rest.strip_prefix('=').map(str::to_string),
+        None => None,
+    });
+    // an unknown name is left for the argument parser to reject
+    let Some(preset) = name.and_then(|name| name.parse::<Preset>().ok()) else {
+        return argv;
+    };
+    let flags = config::command_line(&RunConfig { options: preset.options(), queries: Vec::new() }, &command, &argv[1..]).unwrap();
+    argv[..1].iter().cloned().chain(flags).chain(argv[1..].iter().cloned()).collect()
+}
@@ -0,0 +1,104 @@
+// Per-hit sequence constraints used when designing FISH probes and guides.
+
+use std::collections::HashMap;
+
+pub struct ProbeConstraints {
+    pub min_gc: Option<f64>,
+    pub max_gc: Option<f64>,
+    pub max_homopolymer: Option<usize>,
+    pub no_poly_t: bool,
+    pub max_self_complementarity: Option<usize>,
+}
+
+impl ProbeConstraints {
+    pub fn is_active(&self) -> bool {
+        self.min_gc.is_some()
+            || self.max_gc.is_some()
+            || self.max_homopolymer.is_some()
+            || self.no_poly_t
+            || self.max_self_complementarity.is_some()
+    }
+
+    pub fn accepts(&self, seq: &str) -> bool {
+        let gc = gc_percent(seq);
+        if self.min_gc.is_some_and(|min| gc < min) || self.max_gc.is_some_and(|max| gc > max) {
+            return false;
+        }
+        if self.max_homopolymer.is_some_and(|max| longest_homopolymer(seq) > max) {
+            return false;
+        }
+        if self.no_poly_t && has_poly_t(seq) {
+            return false;
+        }
+        if self.max_self_complementarity.is_some_and(|max| self_complementarity(seq) > max) {
+            return false;
+        }
+        true
+    }
+}
+
+pub fn gc_percent(seq: &str) -> f64 {
+    if seq.is_empty() {
+        return 0.0;
+    }
+    let gc = seq.bytes().filter(|b| matches!(b, b'G' | b'C' | b'g' | b'c')).count();
+    100.0 * gc as f64 / seq.len() as f64
+}
+
+pub fn longest_homopolymer(seq: &str) -> usize {
+    let bytes = seq.as_bytes();
+    let mut longest = 0;
+    let mut run = 0;
+    for i in 0..bytes.len() {
+        if i > 0 && bytes[i].eq_ignore_ascii_case(&bytes[i - 1]) {
+            run += 1;
+        } else {
+            run = 1;
+        }
+        longest = longest.max(run);
+    }
+    longest
+}
+
+// DUST low-complexity score: over the sequence's triplets, the sum of c * (c - 1) / 2 for each
+// distinct triplet occurring c times, divided by the number of triplets less one. Random
+// sequence scores near 0, and simple repeats score high.
+pub fn dust_score(seq: &str) -> f64 {
+    let seq = seq.to_ascii_uppercase();
+    if seq.len() < 4 {
+        return 0.0;
+    }
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for triplet in seq.as_bytes().windows(3) {
+        *counts.entry(triplet).or_default() += 1;
+    }
+    let pairs: usize = counts.values().map(|&c| c * (c - 1) / 2).sum();
+    pairs as f64 / (seq.len() - 3) as f64
+}
+
+// A run of four or more T terminates Pol III (U6) transcription.
+pub fn has_poly_t(seq: &str) -> bool {
+    seq.to_ascii_uppercase().contains("TTTT")
+}
+
+// Length of the longest stretch whose reverse complement also occurs in the sequence,
+// a cheap proxy for hairpin and self-dimer potential.
+pub fn self_complementarity(seq: &str) -> usize {
+    let seq = seq.to_ascii_uppercase();
+    let rev_comp = crate::get_reverse_complement(&seq);
+    let (a, b) = (seq.as_bytes(), rev_comp.as_bytes());
+    // longest common substring of the sequence and its reverse complement
+    let mut best = 0;
+    let mut prev = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        let mut cur = vec![0; b.len() + 1];
+        for j in 1..=b.len() {
+            if a[i - 1] == b[j - 1] && a[i - 1] != b'N' {
+                cur[j] = prev[j - 1] + 1;
+                best = best.max(cur[j]);
+            }
+        }
+        prev = cur;
+    }
+    best
+}
@@ -0,0 +1,172 @@
+// `read-support`: counts of the sequencing reads at each hit site of an earlier search, such
+// as GUIDE-seq or amplicon reads, to validate predicted off-targets. A read spanning a site
+// carries its reference bases, the query itself, an insertion or deletion, or other bases.
+
+use hamming_fasta::{get_reverse_complement, search, Query, SearchConfig, Searcher};
+use rayon::prelude::*;
+use rust_htslib::bam::{self, record::Cigar, Read};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+#[derive(clap::Args, Debug)]
+pub struct ReadSupportArgs {
+    /// Path to the FASTA file the reads are aligned to
+    #[arg(short, long)]
+    fasta: String,
+
+    /// Coordinate-sorted BAM of the reads, with an index
+    #[arg(short, long)]
+    bam: String,
+
+    /// Hits written by a search, with seq_name, strand, start and end columns
+    #[arg(long)]
+    hits: String,
+
+    /// Query the hits were searched with
+    #[arg(short, long)]
+    sequence: String,
+
+    /// Flag for the presence of a Cas9 PAM sequence; a read carries the query when its PAM matches --pam
+    #[arg(short = 'c', long, default_value = "false")]
+    cas9: bool,
+
+    /// PAM sequence in --cas9 mode, N matching any base
+    #[arg(long, default_value = "NGG")]
+    pam: String,
+
+    /// The PAM lies 5' of the protospacer in --cas9 mode, as for Cas12a, instead of 3'
+    #[arg(long, default_value = "false")]
+    pam_5prime: bool,
+
+    /// Reads aligned with a lower mapping quality are not counted
+    #[arg(long, default_value_t = 20)]
+    min_mapq: u8,
+
+    /// Number of threads for parallel execution
+    #[arg(short = 't', long, default_value = "0")]
+    parallelism: usize,
+}
+
+#[derive(Default)]
+struct Support {
+    spanning: usize,
+    reference: usize,
+    query: usize,
+    indel: usize,
+}
+
+// The bases a read aligns to [start, end) of the reference, or None when it does not cover the
+// whole site. `indel` tells whether it has an insertion or deletion within the site.
+fn site_bases(record: &bam::Record, start: usize, end: usize) -> Option<(Vec<u8>, bool)> {
+    let seq = record.seq().as_bytes();
+    let (mut ref_pos, mut read_pos) = (record.pos() as usize, 0);
+    let (mut bases, mut indel) = (Vec::new(), false);
+    for op in record.cigar().iter() {
+        match *op {
+            Cigar::Match(n) | Cigar::Equal(n) | Cigar::Diff(n) => {
+                for i in 0..n as usize {
+                    if (start..end).contains(&(ref_pos + i)) {
+                        bases.push(seq[read_pos + i].to_ascii_uppercase());
+                    }
+                }
+                (ref_pos, read_pos) = (ref_pos + n as usize, read_pos + n as usize);
+            }
+            Cigar::Ins(n) => {
+                indel |= ref_pos > start && ref_pos < end;
+                read_pos += n as usize;
+            }
+            Cigar::Del(n) => {
+                // deleted bases of the site count as the site's, so the read still spans it
+                let deleted = (ref_pos.max(start)..(ref_pos + n as usize).min(end)).len();
+                indel |= deleted > 0;
+                bases.extend(std::iter::repeat_n(b'-', deleted));
+                ref_pos += n as usize;
+            }
+            // a spliced read does not show what lies at a site it skips
+            Cigar::RefSkip(n) => {
+                if ref_pos < end && ref_pos + n as usize > start {
+                    return None;
+                }
+                ref_pos += n as usize;
+            }
+            Cigar::SoftClip(n) => read_pos += n as usize,
+            Cigar::HardClip(_) | Cigar::Pad(_) => {}
+        }
+    }
+    (bases.len() == end - start).then_some((bases, indel))
+}
+
+fn count(reader: &mut bam::IndexedReader, query: &Query, reference: &str, site: (&str, bool, usize, usize), min_mapq: u8) -> Result<Support, String> {
+    let (seq_name, reverse, start, end) = site;
+    let mut support = Support::default();
+    let Some(tid) = reader.header().tid(seq_name.as_bytes()) else {
+        return Ok(support);
+    };
+    reader.fetch((tid as i32, start as i64, end as i64)).map_err(|e| e.to_string())?;
+    let target = query.target();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        if record.is_unmapped() || record.is_secondary() || record.is_duplicate() || record.is_quality_check_failed() || record.mapq() < min_mapq {
+            continue;
+        }
+        let Some((bases, indel)) = site_bases(&record, start, end) else {
+            continue;
+        };
+        support.spanning += 1;
+        if indel {
+            support.indel += 1;
+            continue;
+        }
+        let bases = String::from_utf8(bases).unwrap();
+        support.reference += (bases == reference) as usize;
+        // in query orientation, protospacer exactly and PAM as --pam allows
+        let window = if reverse { get_reverse_complement(&bases) } else { bases };
+        let pam_ok = !query.cas9 || search::pam_matches(query.pam(&window).as_bytes(), &query.pam);
+        support.query += (pam_ok && query.protospacer(&window) == query.protospacer(&target)) as usize;
+    }
+    Ok(support)
+}
+
+pub fn run(args: &ReadSupportArgs) {
+    if args.parallelism > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.parallelism).build_global().unwrap();
+    }
+    let fail = |error: String| -> ! {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    };
+    let mut query = Query::new(&args.sequence.to_ascii_uppercase(), 0);
+    (query.cas9, query.pam, query.pam_5prime) = (args.cas9, args.pam.to_ascii_uppercase(), args.pam_5prime);
+    let searcher = Searcher::new(&args.fasta, SearchConfig::default()).unwrap_or_else(|e| fail(e));
+    let file = File::open(&args.hits).unwrap_or_else(|e| fail(format!("{}: {}", args.hits, e)));
+    let mut lines = BufReader::new(file).lines().map(|line| line.unwrap()).filter(|line| !line.starts_with('#'));
+    let header = lines.next().unwrap_or_else(|| fail(format!("{}: empty", args.hits)));
+    let columns: Vec<&str> = header.split('\t').collect();
+    let column = |name: &str| columns.iter().position(|&c| c == name).unwrap_or_else(|| fail(format!("{}: no {} column", args.hits, name)));
+    let (name_col, strand_col, start_col, end_col) = (column("seq_name"), column("strand"), column("start"), column("end"));
+    let rows: Vec<String> = lines.collect();
+    // a reader per batch of rows rather than per row
+    let counted: Vec<Result<String, String>> = rows
+        .par_iter()
+        .map_init(
+            || bam::IndexedReader::from_path(&args.bam).map_err(|e| format!("{}: {}", args.bam, e)),
+            |reader, row| {
+                let reader = reader.as_mut().map_err(|e| e.clone())?;
+                let fields: Vec<&str> = row.split('\t').collect();
+                let field = |i: usize| fields.get(i).copied().ok_or_else(|| format!("{}: short line: {}", args.hits, row));
+                let parse = |i: usize| field(i)?.parse::<usize>().map_err(|_| format!("{}: bad coordinate in {}", args.hits, row));
+                let (seq_name, reverse, start, end) = (field(name_col)?, field(strand_col)? == "-", parse(start_col)?, parse(end_col)?);
+                if end.checked_sub(start) != Some(query.target().len()) {
+                    return Err(format!("{}: the site is not as long as the query in {}", args.hits, row));
+                }
+                let reference = searcher.fetch(seq_name, start, end)?.to_ascii_uppercase();
+                let support = count(reader, &query, &reference, (seq_name, reverse, start, end), args.min_mapq)?;
+                Ok(format!("{}\t{}\t{}\t{}\t{}", row, support.spanning, support.reference, support.query, support.indel))
+            },
+        )
+        .collect();
+    println!("{}\treads_spanning\treads_reference\treads_query\treads_indel", header);
+    for row in counted {
+        println!("{}", row.unwrap_or_else(|e| fail(e)));
+    }
+}
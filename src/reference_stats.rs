@@ -0,0 +1,61 @@
+// `stats`: statistics of the reference itself. `--background-model` writes the counts of its
+// k-mers as JSON, a Markov model of order k - 1 that `--expectation` searches and other tools
+// can fit their background from without reading the reference again.
+
+use hamming_fasta::stats::{KmerCounts, MAX_ORDER};
+use hamming_fasta::{SearchConfig, Searcher};
+use rayon::prelude::*;
+
+// Bases fetched at a time from a contig.
+const PIECE_LENGTH: usize = 8 << 20;
+
+#[derive(clap::Args, Debug)]
+pub struct StatsArgs {
+    /// Path to the FASTA file
+    #[arg(short, long)]
+    fasta: String,
+
+    /// Write the (order + 1)-mer counts of the reference and the transitions fitted from them to this JSON file, for --background-model searches
+    #[arg(long)]
+    background_model: String,
+
+    /// Order of the Markov model: the bases each base is conditioned on
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=MAX_ORDER as i64))]
+    order: u8,
+
+    /// Number of threads for parallel execution
+    #[arg(short = 't', long, default_value = "0")]
+    parallelism: usize,
+}
+
+pub fn run(args: &StatsArgs) {
+    if args.parallelism > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.parallelism).build_global().unwrap();
+    }
+    let fail = |error: String| -> ! {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    };
+    let order = args.order as usize;
+    let searcher = Searcher::new(&args.fasta, SearchConfig::default()).unwrap_or_else(|e| fail(e));
+    let counted: Result<Vec<KmerCounts>, String> = searcher
+        .contigs()
+        .par_iter()
+        .map(|(seq_name, length)| {
+            let mut counts = KmerCounts::new(order);
+            // pieces overlap by `order` bases, so the k-mers across their ends are counted
+            for start in (0..*length).step_by(PIECE_LENGTH) {
+                let seq = searcher.fetch(seq_name, start, (start + PIECE_LENGTH + order).min(*length))?;
+                counts.add(seq.as_bytes(), PIECE_LENGTH);
+            }
+            Ok(counts)
+        })
+        .collect();
+    let mut counts = KmerCounts::new(order);
+    for contig in counted.unwrap_or_else(|e| fail(e)) {
+        counts.merge(&contig);
+    }
+    std::fs::write(&args.background_model, counts.to_json()).unwrap_or_else(|e| fail(format!("{}: {}", args.background_model, e)));
+    let bases: u64 = searcher.contigs().iter().map(|(_, length)| *length as u64).sum();
+    eprintln!("wrote {}: order {} over {} contigs, {} bases", args.background_model, order, searcher.contigs().len(), bases);
+}
@@ -0,0 +1,46 @@
+// Regions to search instead of whole contigs, from a bgzipped BED file with a tabix index.
+// Only the searched contigs are read from it, so a genome-wide region set costs nothing for
+// contigs outside `--prefix`.
+
+use crate::aliases::Aliases;
+use rust_htslib::tbx::{self, Read};
+use std::collections::HashMap;
+
+// Per contig: sorted, disjoint (start, end) intervals, with overlapping and adjacent regions merged.
+pub fn load_regions(path: &str, contigs: &[(String, usize)], aliases: Option<&Aliases>) -> Result<HashMap<String, Vec<(usize, usize)>>, String> {
+    let mut reader = tbx::Reader::from_path(path).map_err(|e| format!("{}: {}", path, e))?;
+    let indexed: Vec<String> = reader.seqnames();
+    let mut regions = HashMap::new();
+    for (seq_name, seq_length) in contigs {
+        // the BED may name a contig by its alias instead
+        let Some(bed_name) = [Some(seq_name.as_str()), aliases.map(|aliases| aliases.output(seq_name))].into_iter().flatten().find(|name| indexed.iter().any(|indexed| indexed == name)) else {
+            continue;
+        };
+        let tid = reader.tid(bed_name).map_err(|e| format!("{}: {}", path, e))?;
+        reader.fetch(tid, 0, *seq_length as u64).map_err(|e| format!("{}: {}", path, e))?;
+        let mut intervals = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| format!("{}: {}", path, e))?;
+            let line = String::from_utf8_lossy(&record);
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 3 {
+                return Err(format!("{}: not a BED line: {}", path, line));
+            }
+            let start: usize = fields[1].parse().map_err(|_| format!("{}: bad start in {}", path, line))?;
+            let end: usize = fields[2].parse().map_err(|_| format!("{}: bad end in {}", path, line))?;
+            if start < end.min(*seq_length) {
+                intervals.push((start, end.min(*seq_length)));
+            }
+        }
+        intervals.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in intervals {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        regions.insert(seq_name.clone(), merged);
+    }
+    Ok(regions)
+}
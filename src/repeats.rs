@@ -0,0 +1,61 @@
+// Repeat annotation (RepeatMasker .out, or BED with the family in the name column) used to
+// flag or drop hits landing in interspersed repeats.
+
+use crate::intervals::IntervalIndex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+pub struct Repeats {
+    families: Vec<String>,
+    // the family id of every repeat
+    repeats: IntervalIndex<usize>,
+}
+
+impl Repeats {
+    pub fn from_path(path: &str) -> Result<Repeats, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let (mut families, mut family_ids) = (Vec::new(), HashMap::new());
+        let mut repeats: HashMap<String, Vec<(usize, usize, usize)>> = HashMap::new();
+        let rmsk = path.ends_with(".out");
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() || fields[0].starts_with('#') || fields[0] == "track" || fields[0] == "browser" {
+                continue;
+            }
+            // RepeatMasker: score div del ins query begin end (left) strand repeat class/family ...
+            // with 1-based inclusive coordinates and a three-line header
+            let (seq_name, start, end, family) = if rmsk {
+                if fields.len() < 11 || fields[0].parse::<u64>().is_err() {
+                    continue;
+                }
+                let parse = |s: &str| s.parse::<usize>().map_err(|_| format!("malformed RepeatMasker line: {}", line));
+                (fields[4], parse(fields[5])? - 1, parse(fields[6])?, fields[10])
+            } else {
+                if fields.len() < 3 {
+                    return Err(format!("malformed BED line: {}", line));
+                }
+                let parse = |s: &str| s.parse::<usize>().map_err(|_| format!("malformed BED line: {}", line));
+                (fields[0], parse(fields[1])?, parse(fields[2])?, fields.get(3).copied().unwrap_or("repeat"))
+            };
+            let family = *family_ids.entry(family.to_string()).or_insert_with(|| {
+                families.push(family.to_string());
+                families.len() - 1
+            });
+            repeats.entry(seq_name.to_string()).or_default().push((start, end, family));
+        }
+        Ok(Repeats { families, repeats: IntervalIndex::new(repeats) })
+    }
+
+    // Distinct families of the repeats overlapping [start, end), in order of position.
+    pub fn families(&self, seq_name: &str, start: usize, end: usize) -> Vec<&str> {
+        let mut families: Vec<&str> = Vec::new();
+        for &(_, _, family) in self.repeats.overlapping(seq_name, start, end) {
+            if !families.contains(&self.families[family].as_str()) {
+                families.push(&self.families[family]);
+            }
+        }
+        families
+    }
+}
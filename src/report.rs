@@ -0,0 +1,150 @@
+// `--report`: a self-contained HTML summary of a search for readers without a TSV toolchain.
+
+use hamming_fasta::Hit;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+// Beyond this, rows still count towards the plots but are left out of the table.
+const MAX_TABLE_ROWS: usize = 10_000;
+const DENSITY_BINS: usize = 100;
+// contigs with the most hits get a density plot
+const MAX_DENSITY_PLOTS: usize = 50;
+
+#[derive(Default)]
+pub struct Report {
+    rows: Vec<String>,
+    hits: u64,
+    // hits per mismatch level
+    histogram: Vec<u64>,
+    // hit starts per contig
+    starts: HashMap<String, Vec<usize>>,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Bars of height proportional to `counts`, labelled with `labels` when given.
+fn bar_chart(counts: &[u64], labels: Option<&[String]>, width: usize, height: usize) -> String {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let bar = width as f64 / counts.len().max(1) as f64;
+    let plot = if labels.is_some() { height - 16 } else { height };
+    let mut svg = format!("<svg width=\"{}\" height=\"{}\">", width, height);
+    for (i, &count) in counts.iter().enumerate() {
+        let h = count as f64 / max as f64 * plot as f64;
+        let x = i as f64 * bar;
+        write!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{}</title></rect>", x, plot as f64 - h, (bar - 1.0).max(1.0), h, count).unwrap();
+        if let Some(labels) = labels {
+            write!(svg, "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>", x + bar / 2.0, height - 3, escape(&labels[i])).unwrap();
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+impl Report {
+    // `row` is the hit's TSV line as written to stdout.
+    pub fn add(&mut self, hit: &Hit, seq_name: &str, row: &[u8]) {
+        self.hits += 1;
+        if self.histogram.len() <= hit.mismatches {
+            self.histogram.resize(hit.mismatches + 1, 0);
+        }
+        self.histogram[hit.mismatches] += 1;
+        self.starts.entry(seq_name.to_string()).or_default().push(hit.start);
+        if self.rows.len() < MAX_TABLE_ROWS {
+            self.rows.push(String::from_utf8_lossy(row).trim_end().to_string());
+        }
+    }
+
+    pub fn write(&self, path: &str, header: &str, contig_lengths: &[(String, usize)]) -> Result<(), String> {
+        let lengths: HashMap<&str, usize> = contig_lengths.iter().map(|(name, length)| (name.as_str(), *length)).collect();
+        let command: Vec<String> = std::env::args().collect();
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>hamming-fasta report</title>\n<style>\n");
+        html.push_str("body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;font-size:13px}th,td{border:1px solid #ccc;padding:2px 6px}");
+        html.push_str("th{cursor:pointer;background:#eee}td{font-family:monospace}rect{fill:#3b6ea5}text{font-size:11px}.contig{margin:4px 0}\n</style></head><body>\n");
+        html.push_str("<h1>hamming-fasta report</h1>\n<h2>Run</h2>\n");
+        writeln!(html, "<p><code>{}</code></p>", escape(&command.join(" "))).unwrap();
+        writeln!(html, "<p>Contigs searched: {}. Hits: {}.</p>", contig_lengths.len(), self.hits).unwrap();
+
+        html.push_str("<h2>Mismatches</h2>\n");
+        let levels: Vec<String> = (0..self.histogram.len()).map(|k| k.to_string()).collect();
+        writeln!(html, "{}", bar_chart(&self.histogram, Some(&levels), 40 * self.histogram.len().max(1), 140)).unwrap();
+
+        html.push_str("<h2>Hit density</h2>\n");
+        let mut by_hits: Vec<(&String, &Vec<usize>)> = self.starts.iter().collect();
+        by_hits.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(b.0)));
+        if by_hits.len() > MAX_DENSITY_PLOTS {
+            writeln!(html, "<p>The {} of {} contigs with the most hits.</p>", MAX_DENSITY_PLOTS, by_hits.len()).unwrap();
+        }
+        let plotted: BTreeMap<&String, &Vec<usize>> = by_hits.into_iter().take(MAX_DENSITY_PLOTS).collect();
+        for (seq_name, starts) in plotted {
+            // collapsed sites are named without their PanSN prefix and have no indexed length
+            let length = lengths.get(seq_name.as_str()).copied().unwrap_or_else(|| starts.iter().max().unwrap() + 1).max(1);
+            let mut bins = vec![0u64; DENSITY_BINS];
+            for &start in starts {
+                bins[(start * DENSITY_BINS / length).min(DENSITY_BINS - 1)] += 1;
+            }
+            writeln!(html, "<div class=\"contig\">{} ({} bp, {} hits)<br>{}</div>", escape(seq_name), length, starts.len(), bar_chart(&bins, None, 600, 40)).unwrap();
+        }
+
+        html.push_str("<h2>Hits</h2>\n");
+        if self.hits as usize > self.rows.len() {
+            writeln!(html, "<p>The first {} of {} hits; the TSV output has all of them.</p>", self.rows.len(), self.hits).unwrap();
+        }
+        html.push_str("<table id=\"hits\"><thead><tr>");
+        for column in header.split('\t') {
+            write!(html, "<th>{}</th>", escape(column)).unwrap();
+        }
+        html.push_str("</tr></thead><tbody>\n");
+        for row in &self.rows {
+            html.push_str("<tr>");
+            for cell in row.split('\t') {
+                write!(html, "<td>{}</td>", escape(cell)).unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody></table>\n");
+        // click a header to sort by it, numerically where both cells are numbers
+        html.push_str(
+            "<script>\ndocument.querySelectorAll('#hits th').forEach((th, i) => th.addEventListener('click', () => {\n\
+             const body = document.querySelector('#hits tbody');\n\
+             const up = th.dataset.up !== '1'; th.dataset.up = up ? '1' : '0';\n\
+             const key = tr => tr.children[i].textContent;\n\
+             const rows = Array.from(body.rows).sort((a, b) => {\n\
+             const x = key(a), y = key(b), nx = parseFloat(x), ny = parseFloat(y);\n\
+             const c = !isNaN(nx) && !isNaN(ny) ? nx - ny : x.localeCompare(y);\n\
+             return up ? c : -c; });\n\
+             rows.forEach(tr => body.appendChild(tr)); }));\n</script>\n",
+        );
+        html.push_str("</body></html>\n");
+        std::fs::write(path, html).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn cells_are_escaped_and_hits_counted_by_mismatches() {
+        let path = std::env::temp_dir().join(format!("hamming-fasta-report-{}.html", std::process::id()));
+        let mut report = Report::default();
+        for (start, mismatches) in [(5, 0), (90, 2), (91, 2)] {
+            let hit = Hit { query: 0, seq_name: Arc::from("chr<1>"), strand: hamming_fasta::Strand::Forward, start, end: start + 4, sequence: "ACGT".to_string(), mismatches, tm: None };
+            report.add(&hit, "chr<1>", format!("chr<1>\t{}\t{}\n", start, mismatches).as_bytes());
+        }
+        report.write(path.to_str().unwrap(), "seq_name\tstart\tmismatches", &[("chr<1>".to_string(), 100)]).unwrap();
+        let html = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(html.contains("Contigs searched: 1. Hits: 3."));
+        assert!(html.contains("<tr><td>chr&lt;1&gt;</td><td>90</td><td>2</td></tr>"));
+        assert!(html.contains("chr&lt;1&gt; (100 bp, 3 hits)"));
+        // one bar per mismatch level, none at 1
+        assert!(html.contains("<title>1</title></rect><text x=\"20.0\" y=\"137\" text-anchor=\"middle\">0</text>"));
+        assert!(html.contains("<title>0</title></rect><text x=\"60.0\" y=\"137\" text-anchor=\"middle\">1</text>"));
+        assert!(html.contains("<title>2</title></rect><text x=\"100.0\" y=\"137\" text-anchor=\"middle\">2</text>"));
+        assert!(report.write("/nonexistent/report.html", "", &[]).unwrap_err().starts_with("/nonexistent/report.html: "));
+    }
+}
@@ -0,0 +1,128 @@
+// `rescore`: the best window of a query within each interval of a BED file, such as sites
+// reported by an earlier search, without scanning the rest of the genome.
+
+use hamming_fasta::search::{scan_windows, ScanParams};
+use hamming_fasta::{get_reverse_complement, Query, SearchConfig, Searcher};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+#[derive(clap::Args, Debug)]
+pub struct RescoreArgs {
+    /// Path to the FASTA file
+    #[arg(short, long)]
+    fasta: String,
+
+    /// BED file of intervals, 0-based half-open; the best window lying entirely within each is reported
+    #[arg(short, long)]
+    bed: String,
+
+    /// Target sequence to score
+    #[arg(short, long)]
+    sequence: String,
+
+    /// Number of threads for parallel execution
+    #[arg(short = 't', long, default_value = "0")]
+    parallelism: usize,
+
+    /// Flag for the presence of a Cas9 PAM sequence; windows without the PAM are not scored
+    #[arg(short = 'c', long, default_value = "false")]
+    cas9: bool,
+
+    /// PAM sequence in --cas9 mode, N matching any base
+    #[arg(long, default_value = "NGG")]
+    pam: String,
+
+    /// The PAM lies 5' of the protospacer in --cas9 mode, as for Cas12a, instead of 3'
+    #[arg(long, default_value = "false")]
+    pam_5prime: bool,
+}
+
+// The best window of an interval: start and end on the contig, strand, window in query
+// orientation and mismatches.
+type Best = (usize, usize, &'static str, String, usize);
+
+// The window with the fewest mismatches, the leftmost plus-strand one among equals.
+fn best_window(query: &Query, seq: &str, start: usize) -> Option<Best> {
+    let (target, pams, ignored) = (query.target(), query.pams(), query.ignored_in_target());
+    // every window is reported, whatever its distance
+    let params = ScanParams { target: &target, pams: &pams, pam_5prime: query.pam_5prime, ignored: &ignored, degenerate: query.degenerate, max_mismatches: target.len(), tm: None, cancel: None, budget: None, seed_length: None, step: 1, phase: 0 };
+    let mut best: Option<Best> = None;
+    let mut consider = |window_start: usize, strand, window: &str, mismatches: usize| {
+        if best.as_ref().is_none_or(|best| mismatches < best.4) {
+            best = Some((start + window_start, start + window_start + target.len(), strand, window.to_string(), mismatches));
+        }
+    };
+    scan_windows(seq, &params, &[], |idx, window, mismatches, _| consider(idx, "+", window, mismatches));
+    let reverse = get_reverse_complement(seq);
+    let mut reverse_windows = Vec::new();
+    scan_windows(&reverse, &params, &[], |idx, window, mismatches, _| reverse_windows.push((seq.len() - idx - target.len(), window.to_string(), mismatches)));
+    // minus-strand windows from left to right on the contig
+    for (window_start, window, mismatches) in reverse_windows.into_iter().rev() {
+        consider(window_start, "-", &window, mismatches);
+    }
+    best
+}
+
+// A row per window position: `.` where the window matches the query, its base where it does
+// not, and the window's bases at the PAM.
+fn alignment(query: &Query, window: &str) -> String {
+    let (target, pam) = (query.target(), query.pam_range());
+    window.bytes().zip(target.bytes()).enumerate().map(|(i, (w, t))| if w == t && !pam.contains(&i) { '.' } else { w as char }).collect()
+}
+
+pub fn run(args: &RescoreArgs) {
+    if args.parallelism > 0 {
+        rayon::ThreadPoolBuilder::new().num_threads(args.parallelism).build_global().unwrap();
+    }
+    let mut query = Query::new(&args.sequence.to_ascii_uppercase(), 0);
+    (query.cas9, query.pam, query.pam_5prime) = (args.cas9, args.pam.to_ascii_uppercase(), args.pam_5prime);
+    let searcher = Searcher::new(&args.fasta, SearchConfig::default()).unwrap();
+    let file = File::open(&args.bed).unwrap_or_else(|e| {
+        eprintln!("error: {}: {}", args.bed, e);
+        std::process::exit(1);
+    });
+    let mut intervals = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.unwrap();
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields[0].is_empty() || fields[0].starts_with('#') || fields[0] == "track" || fields[0] == "browser" {
+            continue;
+        }
+        let parsed = match fields[..] {
+            [seq_name, start, end, ..] => start.parse::<usize>().ok().zip(end.parse::<usize>().ok()).map(|(start, end)| (seq_name.to_string(), start, end)),
+            _ => None,
+        };
+        let Some((seq_name, start, end)) = parsed.filter(|(_, start, end)| start <= end) else {
+            eprintln!("error: {}: malformed BED line: {}", args.bed, line);
+            std::process::exit(1);
+        };
+        intervals.push((seq_name, start, end, fields.get(3).unwrap_or(&".").to_string()));
+    }
+    let rows: Vec<Result<String, String>> = intervals
+        .par_iter()
+        .map(|(seq_name, start, end, name)| {
+            let length = searcher.length(seq_name).ok_or_else(|| format!("{} is not in {}", seq_name, args.fasta))?;
+            if *end > length {
+                return Err(format!("{}:{}-{} lies beyond the end of {} ({} bp)", seq_name, start, end, seq_name, length));
+            }
+            let seq = searcher.fetch(seq_name, *start, *end)?;
+            let best = match best_window(&query, &seq, *start) {
+                Some((best_start, best_end, strand, window, mismatches)) => format!("{}\t{}\t{}\t{}\t{}\t{}", best_start, best_end, strand, alignment(&query, &window), window, mismatches),
+                // shorter than the query, or without a PAM anywhere
+                None => ".\t.\t.\t.\t.\t.".to_string(),
+            };
+            Ok(format!("{}\t{}\t{}\t{}\t{}", seq_name, start, end, name, best))
+        })
+        .collect();
+    println!("seq_name\tstart\tend\tname\tbest_start\tbest_end\tstrand\talignment\tsequence\tmismatches");
+    for row in rows {
+        match row {
+            Ok(row) => println!("{}", row),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// xorshift64*, good enough for synthetic sequence and shuffles and reproducible everywhere.
+
+pub struct Rng(u64);
+
+impl Rng {
+    // the state must not be zero
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn base(&mut self, gc: f64) -> u8 {
+        let u = self.uniform();
+        if u < gc / 2.0 {
+            b'G'
+        } else if u < gc {
+            b'C'
+        } else if u < (1.0 + gc) / 2.0 {
+            b'A'
+        } else {
+            b'T'
+        }
+    }
+}
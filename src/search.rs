@@ -0,0 +1,1983 @@
+// Library entry point: one or more queries searched against a faidx-indexed FASTA.
+//
+//     let searcher = Searcher::new("genome.fa", SearchConfig::default())?;
+//     for hit in searcher.search_batch(&[Query::new("ACGT...", 3), Query::new("TTGA...", 2)]) {
+//         println!("{} {} {}:{}", hit.query, hit.seq_name, hit.start, hit.strand);
+//     }
+
+use crate::aliases::Aliases;
+use crate::stats::Composition;
+use crate::bloom::KmerFilter;
+use crate::contig_cache::ContigCache;
+use crate::mapped::MappedFasta;
+use crate::cache::ResultCache;
+use crate::md5::{self, Md5};
+use crate::minimizer::MinimizerSketch;
+use crate::tm::TmModel;
+use crate::stream::Records;
+use crate::{dedup, gaps, regions, seed, sliced, duplicate_fai_names, get_reverse_complement, load_fai, suffixed_index, to_dna, Alphabet, Backend, DuplicateNames};
+use rayon::prelude::*;
+use rust_htslib::bgzf;
+use rust_htslib::faidx::Reader;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::BufReader;
+use std::ops::{ControlFlow, Range};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Strand::Forward => "+",
+            Strand::Reverse => "-",
+        }
+    }
+}
+
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Query {
+    // DNA alphabet; U is converted by `Query::new`
+    pub sequence: String,
+    // on both strands, or the larger of `strand_max_mismatches`
+    pub max_mismatches: usize,
+    // separate (plus, minus) limits, neither above `max_mismatches`
+    pub strand_max_mismatches: Option<[usize; 2]>,
+    // append a PAM that must be present and is not counted as a mismatch
+    pub cas9: bool,
+    // the PAM in cas9 mode, N matching any base
+    pub pam: String,
+    // non-canonical PAMs of the same length also accepted in cas9 mode
+    pub alt_pams: Vec<String>,
+    // the PAM lies 5' of the protospacer, as for Cas12a, instead of 3'
+    pub pam_5prime: bool,
+    // 0-based query positions that never count as mismatches
+    pub ignored: Vec<usize>,
+    // report windows by duplex Tm instead of mismatch count
+    pub min_tm: Option<f64>,
+    // IUPAC codes in the query match any base they stand for, see `degenerate_matches`
+    pub degenerate: bool,
+    // abandon the scan of this query once it has used up the budget; shared by its clones
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub budget: Option<Arc<QueryBudget>>,
+}
+
+// `--per-query-timeout`: the time charged to a query, summed over threads, and the limit past
+// which the rest of its scan is skipped. Scans charge their own time, window scans from a
+// sample of their comparisons, and callers may charge the handling of the hits too.
+#[derive(Debug)]
+pub struct QueryBudget {
+    limit: Duration,
+    spent_nanos: AtomicU64,
+}
+
+impl QueryBudget {
+    pub fn new(limit: Duration) -> QueryBudget {
+        QueryBudget { limit, spent_nanos: AtomicU64::new(0) }
+    }
+
+    pub fn spent(&self) -> Duration {
+        Duration::from_nanos(self.spent_nanos.load(Ordering::Relaxed))
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.spent() >= self.limit
+    }
+
+    // Adds `time` to the spent time and tells whether the budget is now used up.
+    pub fn charge(&self, time: Duration) -> bool {
+        let spent = self.spent_nanos.fetch_add(time.as_nanos() as u64, Ordering::Relaxed) + time.as_nanos() as u64;
+        Duration::from_nanos(spent) >= self.limit
+    }
+}
+
+impl Query {
+    pub fn new(sequence: &str, max_mismatches: usize) -> Query {
+        Query { sequence: to_dna(sequence), max_mismatches, strand_max_mismatches: None, cas9: false, pam: "NGG".to_string(), alt_pams: Vec::new(), pam_5prime: false, ignored: Vec::new(), min_tm: None, degenerate: false, budget: None }
+    }
+
+    // The sequence each window is compared with.
+    pub fn target(&self) -> String {
+        if self.cas9 {
+            // add the PAM sequence to the target
+            if self.pam_5prime { format!("{}{}", self.pam, self.sequence) } else { format!("{}{}", self.sequence, self.pam) }
+        } else {
+            self.sequence.clone()
+        }
+    }
+
+    // The PAMs a window may carry, canonical first; none outside cas9 mode.
+    pub fn pams(&self) -> Vec<String> {
+        if self.cas9 {
+            std::iter::once(&self.pam).chain(&self.alt_pams).cloned().collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn max_mismatches_on(&self, strand: Strand) -> usize {
+        match (self.strand_max_mismatches, strand) {
+            (Some([plus, _]), Strand::Forward) => plus,
+            (Some([_, minus]), Strand::Reverse) => minus,
+            (None, _) => self.max_mismatches,
+        }
+    }
+
+    pub fn pam_len(&self) -> usize {
+        if self.cas9 { self.pam.len() } else { 0 }
+    }
+
+    // Where the PAM lies in the target and in every window of it, in query orientation.
+    pub fn pam_range(&self) -> Range<usize> {
+        match self.pam_5prime {
+            true => 0..self.pam_len(),
+            false => self.sequence.len()..self.sequence.len() + self.pam_len(),
+        }
+    }
+
+    // `ignored` as positions in the target.
+    pub fn ignored_in_target(&self) -> Vec<usize> {
+        let offset = if self.pam_5prime { self.pam_len() } else { 0 };
+        self.ignored.iter().map(|&i| i + offset).collect()
+    }
+
+    // The bases of a window (in query orientation) that are scored against the protospacer.
+    pub fn protospacer<'w>(&self, window: &'w str) -> &'w str {
+        match self.pam_5prime {
+            true => &window[self.pam_len()..],
+            false => &window[..window.len() - self.pam_len()],
+        }
+    }
+
+    // The PAM bases of a window, in query orientation; empty outside cas9 mode.
+    pub fn pam<'w>(&self, window: &'w str) -> &'w str {
+        &window[self.pam_range()]
+    }
+
+    // Where the PAM of `hit` lies on its contig, 0-based and half-open. A minus-strand hit is
+    // the reverse complement of its window, so a 3' PAM is on its left and a 5' PAM on its right.
+    pub fn pam_interval(&self, hit: &Hit) -> Range<usize> {
+        match (hit.strand == Strand::Forward) == self.pam_5prime {
+            true => hit.start..hit.start + self.pam_len(),
+            false => hit.end - self.pam_len()..hit.end,
+        }
+    }
+
+    // Where the protospacer of `hit` lies on its contig, the rest of the hit beside its PAM.
+    pub fn protospacer_interval(&self, hit: &Hit) -> Range<usize> {
+        match (hit.strand == Strand::Forward) == self.pam_5prime {
+            true => hit.start + self.pam_len()..hit.end,
+            false => hit.start..hit.end - self.pam_len(),
+        }
+    }
+
+    // Whether a search with `config` seeds this query on either strand, see `seed`.
+    pub fn is_seeded(&self, config: &SearchConfig) -> bool {
+        let (target, pams, ignored) = (self.target(), self.pams(), self.ignored_in_target());
+        let params = ScanParams {
+            target: &target,
+            pams: &pams,
+            pam_5prime: self.pam_5prime,
+            ignored: &ignored,
+            degenerate: self.degenerate,
+            max_mismatches: self.max_mismatches_on(Strand::Forward),
+            tm: self.min_tm.map(|min_tm| (&config.tm_model, min_tm)),
+            cancel: None,
+            budget: None,
+            seed_length: config.seed_length,
+            step: config.step.unwrap_or(1),
+            phase: 0,
+        };
+        config.alphabet == Alphabet::Dna && (seed::chosen(config.backend, &params) || seed::chosen(config.backend, &ScanParams { max_mismatches: self.max_mismatches_on(Strand::Reverse), ..params.clone() }))
+    }
+
+    // Shortest N run that rules out a hit in any window overlapping it (0: never skip).
+    // A window overlapping more than `max_mismatches` Ns can never match a query without Ns,
+    // so runs one longer than that (plus the PAM and ignored positions, which are not scored)
+    // are skipped losslessly.
+    // The Tm mode has no mismatch bound and only skips when asked to.
+    fn min_n_run(&self, config: &SearchConfig) -> usize {
+        match config.skip_n_runs {
+            // N is asparagine in a protein
+            _ if config.alphabet == Alphabet::Protein => 0,
+            Some(len) => len,
+            None if self.min_tm.is_none() && !self.sequence.contains(['N', 'n']) => self.max_mismatches + 1 + self.pam_len() + self.ignored.len(),
+            None => 0,
+        }
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// fields left out of a serialized configuration take their default
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SearchConfig {
+    // only search sequences whose names start with this
+    pub prefix: String,
+    // only search sequences of at least / at most this many bases
+    pub min_contig_length: Option<usize>,
+    pub max_contig_length: Option<usize>,
+    // minimum N run length to skip, overriding the lossless default; 0 disables skipping
+    pub skip_n_runs: Option<usize>,
+    // BED file of N runs used instead of scanning contigs for them
+    pub gaps: Option<String>,
+    // scan identical contigs once and report their hits under every name
+    pub skip_duplicate_contigs: bool,
+    pub tm_model: TmModel,
+    // set from any thread to stop a running search; hits found so far are still delivered
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancel: Option<Arc<AtomicBool>>,
+    // fetch and scan contigs in pieces of this many window starts instead of whole
+    pub chunk_size: Option<usize>,
+    // scan each fetched piece in parallel blocks of this many window starts, see `scan_blocks`
+    pub block_size: Option<usize>,
+    // directory of cached per-query results, see `cache`
+    pub cache_dir: Option<String>,
+    // skip contigs whose `index --bloom` k-mer filter rules out every query, see `bloom`
+    pub bloom: bool,
+    // skip contigs whose `index --minimizers` sketch rules out every query, see `minimizer`
+    pub minimizers: bool,
+    // tally the base composition of every scanned contig into `ContigHits::composition`
+    pub composition: bool,
+    // bgzipped, tabix-indexed BED of regions to search instead of whole contigs, see `regions`
+    pub regions: Option<String>,
+    // read a plain, local FASTA through htslib too instead of memory-mapping it, see `mapped`
+    pub no_mmap: bool,
+    // times a failed or short read of the FASTA is tried again, waiting fetch_backoff_ms before
+    // the first retry and twice as long before each next one, see `Searcher::retrying`
+    pub fetch_retries: usize,
+    pub fetch_backoff_ms: u64,
+    // bytes of decompressed contig pieces kept across fetches and searches, see `contig_cache`
+    pub cache_bytes: Option<usize>,
+    // table of names to report contigs under, which the gaps and regions files may also use, see `aliases`
+    pub aliases: Option<String>,
+    // lossy: skip windows without an exact k-mer of this length in common with the query, see `seed`
+    pub seed_length: Option<usize>,
+    // lossy: only compare windows starting at multiples of this
+    pub step: Option<usize>,
+    // names indexed more than once, see `DuplicateNames`
+    pub on_duplicate_names: DuplicateNames,
+    // use `bloom` and `minimizers` indexes of contigs that changed since they were built, which
+    // may miss hits on them, instead of refusing to search; see `Searcher::index_mismatches`
+    pub allow_mismatched_index: bool,
+    pub backend: Backend,
+    // a protein reference is scanned on its one strand, with U kept as selenocysteine
+    pub alphabet: Alphabet,
+}
+
+impl SearchConfig {
+    // Whether a sequence is searched under the prefix and length limits.
+    fn searches(&self, seq_name: &str, length: usize) -> bool {
+        seq_name.starts_with(&self.prefix) && self.min_contig_length.is_none_or(|min| length >= min) && self.max_contig_length.is_none_or(|max| length <= max)
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hit {
+    // index of the query in the batch
+    pub query: usize,
+    pub seq_name: Arc<str>,
+    pub strand: Strand,
+    pub start: usize,
+    pub end: usize,
+    // the matched window in query orientation
+    pub sequence: String,
+    pub mismatches: usize,
+    pub tm: Option<f64>,
+}
+
+// Everything found on one contig, handed to `Searcher::for_each_contig` callbacks.
+pub struct ContigHits<'a> {
+    pub seq_name: &'a str,
+    // contigs with identical sequence that were not scanned themselves
+    pub aliases: &'a [String],
+    pub length: usize,
+    // empty unless `SearchConfig::composition` is set
+    pub composition: Composition,
+    // hits per query, in batch order
+    pub hits: Vec<Vec<Hit>>,
+}
+
+#[derive(Clone)]
+pub struct ScanParams<'a> {
+    pub target: &'a str,
+    // PAMs the window must carry, which are not part of the mismatch count; empty outside cas9 mode
+    pub pams: &'a [String],
+    // the PAM starts the window instead of ending it
+    pub pam_5prime: bool,
+    // target positions that never count as mismatches
+    pub ignored: &'a [usize],
+    // IUPAC codes in the target match the bases they stand for
+    pub degenerate: bool,
+    pub max_mismatches: usize,
+    // replaces the mismatch threshold with a minimum duplex Tm
+    pub tm: Option<(&'a TmModel, f64)>,
+    // checked every `CANCEL_CHECK_INTERVAL` windows; the scan stops once it is set
+    pub cancel: Option<&'a AtomicBool>,
+    // charged for the comparisons of the target, which are skipped once it is exhausted
+    pub budget: Option<&'a QueryBudget>,
+    // only compare windows sharing an exact k-mer with the query at the same offset (lossy)
+    pub seed_length: Option<usize>,
+    // only compare the windows starting at `phase`, `phase + step`, ... (lossy unless step is 1)
+    pub step: usize,
+    pub phase: usize,
+}
+
+pub(crate) const CANCEL_CHECK_INTERVAL: usize = 1 << 16;
+// one window in this many is timed for the targets with a budget
+pub(crate) const BUDGET_SAMPLE_INTERVAL: usize = 64;
+
+// What timing nothing takes, taken off every timed comparison: often more than the comparison.
+pub(crate) fn timer_overhead() -> Duration {
+    static OVERHEAD: std::sync::OnceLock<Duration> = std::sync::OnceLock::new();
+    *OVERHEAD.get_or_init(|| {
+        let mut samples: Vec<Duration> = (0..1000).map(|_| Instant::now().elapsed()).collect();
+        samples.sort_unstable();
+        samples[samples.len() / 2]
+    })
+}
+
+// Calls `report(idx, window, mismatches, tm)` for every window of `sequence` that matches the
+// target, skipping windows that overlap any of the sorted `skip` intervals.
+pub fn scan_windows<F: FnMut(usize, &str, usize, Option<f64>)>(sequence: &str, params: &ScanParams, skip: &[(usize, usize)], mut report: F) {
+    scan_windows_batch(sequence, std::slice::from_ref(params), skip, |_, idx, window, distance, tm| report(idx, window, distance, tm))
+}
+
+// `scan_windows` for several targets of one length in a single pass over `sequence`, calling
+// `report(k, idx, window, mismatches, tm)` for matches of the k-th of `params`. Windows are
+// reported in increasing `idx`, and in `params` order within one window.
+pub fn scan_windows_batch<F: FnMut(usize, usize, &str, usize, Option<f64>)>(sequence: &str, params: &[ScanParams], skip: &[(usize, usize)], mut report: F) {
+    let Some(first) = params.first() else {
+        return;
+    };
+    let target_len = first.target.len();
+    // a shorter or longer target would be compared with a window of the wrong size
+    assert!(params.iter().all(|p| p.target.len() == target_len), "targets of one scan must have the same length");
+    // per target: where the PAM and the scored protospacer lie in a window
+    let layout: Vec<(Range<usize>, Range<usize>)> = params
+        .iter()
+        .map(|p| {
+            let pam_len = p.pams.first().map_or(0, String::len);
+            if p.pam_5prime { (0..pam_len, pam_len..target_len) } else { (target_len - pam_len..target_len, 0..target_len - pam_len) }
+        })
+        .collect();
+    // per target: the scored positions, those whose query base is rarest in `sequence` first,
+    // so that most windows are rejected after a few comparisons; only the Tm mode needs them all
+    let mut counts = [0usize; 256];
+    for &b in sequence.as_bytes() {
+        counts[b as usize] += 1;
+    }
+    let orders: Vec<Vec<usize>> = params
+        .iter()
+        .zip(&layout)
+        .map(|(p, (_, protospacer))| {
+            let target = p.target.as_bytes();
+            let mut order: Vec<usize> = protospacer.clone().filter(|i| !p.ignored.contains(i)).collect();
+            if p.tm.is_none() {
+                let matching = |code: u8| -> usize { (0..=255u8).filter(|&b| b == code || (p.degenerate && degenerate_matches(b, code))).map(|b| counts[b as usize]).sum() };
+                order.sort_by_cached_key(|&i| matching(target[i]));
+            }
+            order
+        })
+        .collect();
+    // the first window start at or after `idx` that is compared; the batch shares the step
+    let step = first.step.max(1);
+    let aligned = |idx: usize| if idx <= first.phase { first.phase } else { idx + (step - (idx - first.phase) % step) % step };
+    let bytes = sequence.as_bytes();
+    let mut skip = skip.iter().peekable();
+    let (mut idx, mut compared) = (aligned(0), 0usize);
+    let mut abandoned = vec![false; params.len()];
+    while idx + target_len <= bytes.len() {
+        if let Some(&&(start, end)) = skip.peek() {
+            if end <= idx {
+                skip.next();
+                continue;
+            }
+            if start < idx + target_len {
+                idx = aligned(end);
+                continue;
+            }
+        }
+        if compared % CANCEL_CHECK_INTERVAL == 0 {
+            if first.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                return;
+            }
+            // budgets are shared with the scans of other contigs and strands
+            for (abandoned, params) in abandoned.iter_mut().zip(params) {
+                *abandoned |= params.budget.is_some_and(QueryBudget::is_exhausted);
+            }
+            if abandoned.iter().all(|&abandoned| abandoned) {
+                return;
+            }
+        }
+        let timed = compared % BUDGET_SAMPLE_INTERVAL == 0;
+        compared += 1;
+        let window = &bytes[idx..idx + target_len];
+        let window_str = std::str::from_utf8(window).unwrap();
+        let at = idx;
+        idx += step;
+        for (k, ((params, (pam, _)), order)) in params.iter().zip(&layout).zip(&orders).enumerate() {
+            if abandoned[k] {
+                continue;
+            }
+            let started = (timed && params.budget.is_some()).then(Instant::now);
+            'compare: {
+                // the PAM is a few exact bases, so checking it first rules out most windows cheaply
+                if !params.pams.is_empty() && !params.pams.iter().any(|p| pam_matches(&window[pam.clone()], p)) {
+                    break 'compare;
+                }
+                let target = params.target.as_bytes();
+                let mismatches = order.iter().filter(|&&i| window[i] != target[i] && !(params.degenerate && degenerate_matches(window[i], target[i])));
+                // past the limit the exact count no longer matters, except for the Tm mode
+                let distance = match params.tm {
+                    Some(_) => mismatches.count(),
+                    None => mismatches.take(params.max_mismatches + 1).count(),
+                };
+                let tm = params.tm.map(|(model, _)| model.duplex_tm(target, window));
+                let accepted = match (tm, params.tm) {
+                    (Some(tm), Some((_, min_tm))) => tm >= min_tm,
+                    _ => distance <= params.max_mismatches,
+                };
+                if accepted {
+                    report(k, at, window_str, distance, tm);
+                }
+            }
+            if let (Some(started), Some(budget)) = (started, params.budget) {
+                abandoned[k] = budget.charge(started.elapsed().saturating_sub(timer_overhead()) * BUDGET_SAMPLE_INTERVAL as u32);
+            }
+        }
+    }
+}
+
+// The distinct queries of a batch, and for each query the slot of the distinct one it repeats
+// and whether it is that one's reverse complement, which only DNA queries (`stranded`) can be.
+fn collapse_queries(queries: &[Query], stranded: bool) -> (Vec<usize>, Vec<(usize, bool)>) {
+    let mut unique = Vec::new();
+    let mut slots = HashMap::new();
+    let key = |query: &Query, sequence: &str| (sequence.to_string(), query.max_mismatches, query.strand_max_mismatches, query.pams(), query.pam_5prime, query.ignored.clone(), query.min_tm.map(f64::to_bits), query.degenerate);
+    let sources = queries
+        .iter()
+        .enumerate()
+        .map(|(q, query)| {
+            if let Some(&slot) = slots.get(&key(query, &query.sequence)) {
+                return (slot, false);
+            }
+            // a PAM, a Tm, ignored positions or per-strand limits break the symmetry between
+            // strands, and so would a base whose complement is ambiguous
+            if stranded && !query.cas9 && query.min_tm.is_none() && query.ignored.is_empty() && query.strand_max_mismatches.is_none() && query.sequence.bytes().all(|b| b"ACGTacgt".contains(&b)) {
+                if let Some(&slot) = slots.get(&key(query, &get_reverse_complement(&query.sequence))) {
+                    return (slot, true);
+                }
+            }
+            slots.insert(key(query, &query.sequence), unique.len());
+            unique.push(q);
+            (unique.len() - 1, false)
+        })
+        .collect();
+    (unique, sources)
+}
+
+// The hits of the reverse complement of the query behind `hits`, as query `query`: the same
+// windows on the other strand, in the order a scan reports them.
+fn mirror_hits(hits: &[Hit], query: usize) -> Vec<Hit> {
+    let mirror = |hit: &Hit, strand| Hit { query, strand, sequence: get_reverse_complement(&hit.sequence), ..hit.clone() };
+    let reverse = hits.iter().rev().filter(|hit| hit.strand == Strand::Reverse).map(|hit| mirror(hit, Strand::Forward));
+    let forward = hits.iter().rev().filter(|hit| hit.strand == Strand::Forward).map(|hit| mirror(hit, Strand::Reverse));
+    reverse.chain(forward).collect()
+}
+
+// The bases an uppercase IUPAC code stands for, as bits A 1, C 2, G 4 and T 8; 0 for
+// anything else, soft-masked bases included.
+fn iupac_bases(b: u8) -> u8 {
+    match b {
+        b'A' => 1,
+        b'C' => 2,
+        b'G' => 4,
+        b'T' => 8,
+        b'R' => 1 | 4,
+        b'Y' => 2 | 8,
+        b'S' => 2 | 4,
+        b'W' => 1 | 8,
+        b'K' => 4 | 8,
+        b'M' => 1 | 2,
+        b'B' => 2 | 4 | 8,
+        b'D' => 1 | 4 | 8,
+        b'H' => 1 | 2 | 8,
+        b'V' => 1 | 2 | 4,
+        b'N' => 15,
+        _ => 0,
+    }
+}
+
+// Whether a window base falls within a degenerate query base: every base it may stand for is
+// one the query allows, so K matches G, T and K, and N matches anything but soft-masked bases.
+pub fn degenerate_matches(base: u8, code: u8) -> bool {
+    let (base, code) = (iupac_bases(base), iupac_bases(code));
+    base != 0 && base & !code == 0
+}
+
+// Whether a window's PAM bases match `pam`, in which N matches any base.
+pub fn pam_matches(window: &[u8], pam: &str) -> bool {
+    window.len() == pam.len() && window.iter().zip(pam.bytes()).all(|(&b, p)| p == b'N' || b == p)
+}
+
+// Residues of the first bases of the first contigs looked at by `detect_alphabet`.
+const DETECT_CONTIGS: u64 = 16;
+const DETECT_LENGTH: usize = 4096;
+
+// The alphabet of `fasta`: protein when the start of one of its first contigs has an amino acid
+// that is no IUPAC nucleotide code (E, F, I, L, P, Q and the rarer J, O and Z), DNA otherwise.
+pub fn detect_alphabet(fasta: &str) -> Result<Alphabet, String> {
+    let reader = Reader::from_path(fasta).map_err(|e| format!("{}: {}", fasta, e))?;
+    let lengths = load_fai(fasta)?;
+    for i in 0..reader.n_seqs().min(DETECT_CONTIGS) {
+        let seq_name = reader.seq_name(i as i32).map_err(|e| e.to_string())?;
+        let length = lengths[&seq_name].min(DETECT_LENGTH);
+        if length == 0 {
+            continue;
+        }
+        let seq = reader.fetch_seq_string(&seq_name, 0, length - 1).map_err(|e| e.to_string())?;
+        if seq.bytes().any(|b| b"EFIJLOPQZ".contains(&b.to_ascii_uppercase())) {
+            return Ok(Alphabet::Protein);
+        }
+    }
+    Ok(Alphabet::Dna)
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic.downcast_ref::<&str>().map_or_else(|| "panic while scanning".to_string(), |message| message.to_string()),
+    }
+}
+
+// A group of queries from `Searcher::scan_contig`: one that may be seeded, or several
+// compared window by window in one pass, bit-sliced where the backend does so.
+fn scan_batch<F: FnMut(usize, usize, &str, usize, Option<f64>)>(sequence: &str, params: &[ScanParams], skip: &[(usize, usize)], backend: Backend, mut report: F) {
+    match params {
+        [params] if seed::chosen(backend, params) => seed::scan_seeded(sequence, params, skip, |idx, window, distance, tm| report(0, idx, window, distance, tm)),
+        _ if sliced::chosen(backend, params) => sliced::scan_sliced(sequence, params, skip, report),
+        _ => scan_windows_batch(sequence, params, skip, report),
+    }
+}
+
+// `scan_batch` over blocks of `block` window starts of `sequence`, scanned in parallel, each
+// extended by the target length so that a window is compared in the block it starts in. Small
+// blocks stay in cache while every query is compared with them; matches are reported in the
+// order of a scan of the whole sequence.
+fn scan_blocks<F: FnMut(usize, usize, &str, usize, Option<f64>)>(sequence: &str, params: &[ScanParams], skip: &[(usize, usize)], backend: Backend, block: Option<usize>, mut report: F) {
+    let target_len = params.first().map_or(0, |p| p.target.len());
+    let block = match block {
+        Some(block) if block > 0 && sequence.len() > block + target_len => block,
+        _ => return scan_batch(sequence, params, skip, backend, report),
+    };
+    let found: Vec<Vec<_>> = (0..sequence.len())
+        .step_by(block)
+        .collect::<Vec<usize>>()
+        .into_par_iter()
+        .map(|start| {
+            let end = (start + block + target_len).saturating_sub(1).min(sequence.len());
+            let skip: Vec<(usize, usize)> = skip.iter().filter(|&&(s, e)| s < end && e > start).map(|&(s, e)| (s.max(start) - start, e.min(end) - start)).collect();
+            // the first window compared in the block is the first at or after its start
+            let params: Vec<ScanParams> = params
+                .iter()
+                .map(|p| {
+                    let step = p.step.max(1);
+                    let phase = if start <= p.phase { p.phase - start } else { (step - (start - p.phase) % step) % step };
+                    ScanParams { phase, ..p.clone() }
+                })
+                .collect();
+            let mut found = Vec::new();
+            scan_batch(&sequence[start..end], &params, &skip, backend, |k, idx, _, distance, tm| {
+                if idx < block {
+                    found.push((k, start + idx, distance, tm));
+                }
+            });
+            found
+        })
+        .collect();
+    for (k, idx, distance, tm) in found.into_iter().flatten() {
+        report(k, idx, &sequence[idx..idx + target_len], distance, tm);
+    }
+}
+
+// The FASTA name that stands for stdin, see `stream`.
+pub const STDIN: &str = "-";
+
+// What a searcher of `Searcher::from_sequences` gives as its FASTA in messages.
+const IN_MEMORY: &str = "<memory>";
+
+// Records of stdin held while the next are read; a longer record is a batch of its own.
+const STREAM_BATCH_BASES: usize = 64 << 20;
+
+// Where `Searcher::scan_contig` reads bases from: a faidx reader, or a streamed record.
+trait Bases {
+    // [start, end) of `seq_name`, end > start
+    fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String>;
+}
+
+impl Bases for Reader {
+    fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        self.fetch_seq_string(seq_name, start, end - 1).map_err(|e| e.to_string())
+    }
+}
+
+// Bases read from a `MappedFasta`, or through htslib where the lines do not match the index.
+struct Mapped<'a> {
+    mapped: &'a MappedFasta,
+    reader: &'a dyn Bases,
+}
+
+impl Bases for Mapped<'_> {
+    fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        self.mapped.bases(seq_name, start, end).or_else(|_| self.reader.bases(seq_name, start, end))
+    }
+}
+
+// Bases read again after an error or a short read, see `Searcher::retrying`.
+struct Retried<'a> {
+    bases: &'a dyn Bases,
+    searcher: &'a Searcher,
+}
+
+impl Bases for Retried<'_> {
+    fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        self.searcher.retrying(|| {
+            let seq = self.bases.bases(seq_name, start, end)?;
+            if seq.len() != end - start {
+                return Err(format!("read {} bases of {}:{}-{}", seq.len(), seq_name, start + 1, end));
+            }
+            Ok(seq)
+        })
+    }
+}
+
+// Bases read through a `ContigCache`.
+struct Cached<'a> {
+    bases: &'a dyn Bases,
+    cache: &'a ContigCache,
+    length: usize,
+}
+
+impl Bases for Cached<'_> {
+    fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        self.cache.get(seq_name, self.length, start, end, |start, end| self.bases.bases(seq_name, start, end))
+    }
+}
+
+impl Bases for String {
+    fn bases(&self, _: &str, start: usize, end: usize) -> Result<String, String> {
+        Ok(self[start..end].to_string())
+    }
+}
+
+// Where a thread reads the reference from: a faidx reader of its own, or the sequences of
+// `Searcher::from_sequences`, which no file backs.
+enum Source<'a> {
+    Reader(Reader),
+    Memory(&'a HashMap<String, String>),
+}
+
+impl Bases for Source<'_> {
+    fn bases(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        match self {
+            Source::Reader(reader) => reader.bases(seq_name, start, end),
+            Source::Memory(sequences) => sequences.get(seq_name).ok_or_else(|| format!("no sequence {}", seq_name))?.bases(seq_name, start, end),
+        }
+    }
+}
+
+pub struct Searcher {
+    fasta: String,
+    config: SearchConfig,
+    // (name, length) in index order, restricted to the configured prefix and lengths
+    contigs: Vec<(String, usize)>,
+    // every indexed contig, searched or not
+    lengths: HashMap<String, usize>,
+    duplicates: HashMap<String, Vec<String>>,
+    gaps: Option<HashMap<String, Vec<(usize, usize)>>>,
+    regions: Option<HashMap<String, Vec<(usize, usize)>>>,
+    aliases: Option<Aliases>,
+    contig_cache: Option<ContigCache>,
+    mapped: Option<MappedFasta>,
+    // contigs that could not be fetched or scanned, with the reason; the search goes on without them
+    failures: Mutex<Vec<(String, String)>>,
+    // reads of the FASTA that succeeded on a retry
+    retried: AtomicU64,
+    cache: Option<ResultCache>,
+    prefilters: Vec<Box<dyn Prefilter>>,
+    // temporary directory holding the renamed index of `DuplicateNames::Suffix`
+    _renamed: Option<TempDir>,
+    index_mismatches: Vec<String>,
+    // the sequences of `from_sequences`, read instead of a FASTA
+    sequences: Option<HashMap<String, String>>,
+}
+
+// N runs by FASTA name, from a file that may name contigs by their aliases.
+fn load_gaps(path: &str, aliases: Option<&Aliases>) -> HashMap<String, Vec<(usize, usize)>> {
+    let gaps = gaps::load_gaps(path, 1);
+    match aliases {
+        Some(aliases) => gaps.into_iter().map(|(name, runs)| (aliases.fasta_name(&name).to_string(), runs)).collect(),
+        None => gaps,
+    }
+}
+
+// The MD5 of every contig, read in pieces.
+fn contig_md5s(fasta: &str, contigs: &[(String, usize)]) -> Result<Vec<[u8; 16]>, String> {
+    contigs
+        .par_iter()
+        .map(|(seq_name, length)| {
+            let reader = Reader::from_path(fasta).map_err(|e| format!("{}: {}", fasta, e))?;
+            let mut md5 = Md5::default();
+            for start in (0..*length).step_by(1 << 20) {
+                let end = (start + (1 << 20)).min(*length);
+                md5.update(reader.fetch_seq(seq_name, start, end - 1).map_err(|e| format!("{}: {}", seq_name, e))?);
+            }
+            Ok(md5.finish())
+        })
+        .collect()
+}
+
+// Removed with the searcher, or as soon as `Searcher::new` fails.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// For each pigeonhole piece of a query, the index entries a contig must have to contain it.
+pub type Pieces = Vec<Vec<u64>>;
+
+// A per-contig index that can prove a contig holds no hit of a query without scanning it.
+pub trait Prefilter: Send + Sync {
+    // The indexed length and MD5 of `seq_name`, to catch an index built for another version of the FASTA.
+    fn length(&self, seq_name: &str) -> Option<usize>;
+    fn md5(&self, seq_name: &str) -> Option<[u8; 16]>;
+    // What `may_contain` checks for `query`, or None when the index cannot rule anything out for it.
+    fn pieces(&self, query: &Query) -> Option<Pieces>;
+    // False only if no hit of the query whose `pieces` these are can lie in `seq_name`.
+    fn may_contain(&self, seq_name: &str, pieces: &[Vec<u64>]) -> bool;
+}
+
+impl Searcher {
+    pub fn new(fasta: &str, config: SearchConfig) -> Result<Searcher, String> {
+        if fasta == STDIN {
+            return Searcher::streaming(config);
+        }
+        let duplicate_names = duplicate_fai_names(fasta)?;
+        let renamed = match config.on_duplicate_names {
+            _ if duplicate_names.is_empty() => None,
+            DuplicateNames::First => None,
+            DuplicateNames::Error => {
+                let shown: Vec<&str> = duplicate_names.iter().take(5).map(String::as_str).collect();
+                return Err(format!(
+                    "{}.fai: duplicate names {}{} ({} in all); choose `--on-duplicate-names first` or `suffix`",
+                    fasta,
+                    shown.join(", "),
+                    if duplicate_names.len() > shown.len() { ", ..." } else { "" },
+                    duplicate_names.len()
+                ));
+            }
+            DuplicateNames::Suffix => {
+                static RENAMED: AtomicUsize = AtomicUsize::new(0);
+                Some(TempDir(std::env::temp_dir().join(format!("hamming-fasta-names-{}-{}", std::process::id(), RENAMED.fetch_add(1, Ordering::Relaxed)))))
+            }
+        };
+        let source = fasta;
+        let fasta = &match &renamed {
+            Some(dir) => suffixed_index(source, &dir.0)?,
+            None => source.to_string(),
+        };
+        let reader = Reader::from_path(fasta).map_err(|e| format!("{}: {}", fasta, e))?;
+        let seq_lengths = load_fai(fasta)?;
+        let contigs: Vec<(String, usize)> = (0..reader.n_seqs())
+            .map(|i| reader.seq_name(i as i32).unwrap())
+            .map(|name| {
+                let length = seq_lengths[&name];
+                (name, length)
+            })
+            .filter(|(name, length)| config.searches(name, *length))
+            .collect();
+        let duplicates = if config.skip_duplicate_contigs {
+            let names: Vec<String> = contigs.iter().map(|(name, _)| name.clone()).collect();
+            dedup::find_duplicates(fasta, &names, &seq_lengths)?
+        } else {
+            HashMap::new()
+        };
+        let aliases = config.aliases.as_deref().map(Aliases::from_path).transpose()?;
+        // filtered per query by its own minimum run length
+        let gaps = config.gaps.as_ref().map(|path| load_gaps(path, aliases.as_ref()));
+        if config.regions.is_some() && config.skip_duplicate_contigs {
+            // identical contigs can have different regions
+            return Err("regions cannot be combined with skipping duplicate contigs".to_string());
+        }
+        let regions = config.regions.as_ref().map(|path| regions::load_regions(path, &contigs, aliases.as_ref())).transpose()?;
+        let cache = config.cache_dir.as_ref().map(|dir| ResultCache::open(dir, source, &config)).transpose()?;
+        let mut prefilters: Vec<(Box<dyn Prefilter>, String, &str)> = Vec::new();
+        if config.bloom {
+            prefilters.push((Box::new(KmerFilter::load(source)?), crate::bloom::filter_path(source), "index --bloom"));
+        }
+        if config.minimizers {
+            prefilters.push((Box::new(MinimizerSketch::load(source)?), crate::minimizer::sketch_path(source), "index --minimizers"));
+        }
+        // edited bases can hide behind an unchanged length, so every contig is checksummed too
+        let md5s = if prefilters.is_empty() { Vec::new() } else { contig_md5s(source, &contigs)? };
+        let mut index_mismatches = Vec::new();
+        for (prefilter, path, built_by) in &prefilters {
+            for ((seq_name, length), md5) in contigs.iter().zip(&md5s) {
+                let mismatch = match (prefilter.length(seq_name), prefilter.md5(seq_name)) {
+                    (Some(indexed), Some(indexed_md5)) if indexed == *length && indexed_md5 == *md5 => continue,
+                    (Some(indexed), Some(indexed_md5)) if indexed == *length => {
+                        format!("{}: {} has changed since it was indexed (MD5 {} there, {} now)", path, seq_name, md5::hex(&indexed_md5), md5::hex(md5))
+                    }
+                    _ => format!("{}: {} is missing or has another length", path, seq_name),
+                };
+                if !config.allow_mismatched_index {
+                    return Err(format!("{}; rebuild it with `{}`", mismatch, built_by));
+                }
+                index_mismatches.push(mismatch);
+            }
+        }
+        let prefilters = prefilters.into_iter().map(|(prefilter, _, _)| prefilter).collect();
+        // hits missed through a stale index must not be answered from the cache later
+        let cache = cache.filter(|_| index_mismatches.is_empty());
+        let contig_cache = config.cache_bytes.map(ContigCache::new);
+        let mapped = if config.no_mmap { None } else { MappedFasta::open(fasta) };
+        Ok(Searcher { fasta: fasta.to_string(), config, contigs, lengths: seq_lengths, duplicates, gaps, regions, aliases, contig_cache, mapped, failures: Mutex::default(), retried: AtomicU64::new(0), cache, prefilters, _renamed: renamed, index_mismatches, sequences: None })
+    }
+
+    // A searcher of the records of stdin, plain or gzipped, which are read as they are scanned.
+    // Nothing is known of them beforehand, so `contigs` is empty and what needs an index is refused.
+    fn streaming(config: SearchConfig) -> Result<Searcher, String> {
+        let needs_index = [(config.regions.is_some(), "regions"), (config.skip_duplicate_contigs, "skipping duplicate contigs"), (config.cache_dir.is_some(), "a result cache"), (config.bloom || config.minimizers, "k-mer indexes")];
+        if let Some((_, what)) = needs_index.iter().find(|(given, _)| *given) {
+            return Err(format!("{} need an indexed FASTA, not one read from stdin", what));
+        }
+        let aliases = config.aliases.as_deref().map(Aliases::from_path).transpose()?;
+        let gaps = config.gaps.as_ref().map(|path| load_gaps(path, aliases.as_ref()));
+        Ok(Searcher {
+            fasta: STDIN.to_string(),
+            config,
+            contigs: Vec::new(),
+            lengths: HashMap::new(),
+            duplicates: HashMap::new(),
+            gaps,
+            regions: None,
+            aliases,
+            // records are read once
+            contig_cache: None,
+            mapped: None,
+            failures: Mutex::default(),
+            retried: AtomicU64::new(0),
+            cache: None,
+            prefilters: Vec::new(),
+            _renamed: None,
+            index_mismatches: Vec::new(),
+            sequences: None,
+        })
+    }
+
+    // A searcher of `(name, sequence)` pairs held in memory, such as amplicons assembled earlier
+    // in the same process, searched in the order given. No FASTA is opened, through htslib or
+    // otherwise, so what needs the FASTA's own files is refused as for stdin; the gaps, regions
+    // and aliases files of the configuration are read as usual.
+    pub fn from_sequences<N: Into<String>, S: Into<String>>(sequences: impl IntoIterator<Item = (N, S)>, config: SearchConfig) -> Result<Searcher, String> {
+        let needs_index = [(config.skip_duplicate_contigs, "skipping duplicate contigs"), (config.cache_dir.is_some(), "a result cache"), (config.bloom || config.minimizers, "k-mer indexes")];
+        if let Some((_, what)) = needs_index.iter().find(|(given, _)| *given) {
+            return Err(format!("{} need an indexed FASTA, not sequences in memory", what));
+        }
+        let (mut held, mut contigs) = (HashMap::new(), Vec::new());
+        for (name, seq) in sequences {
+            let (name, seq): (String, String) = (name.into(), seq.into());
+            // windows are cut at byte offsets
+            if !seq.is_ascii() {
+                return Err(format!("sequence {} is not ASCII", name));
+            }
+            if config.searches(&name, seq.len()) {
+                contigs.push((name.clone(), seq.len()));
+            }
+            if held.insert(name.clone(), seq).is_some() {
+                return Err(format!("sequence {} is given twice", name));
+            }
+        }
+        let lengths = held.iter().map(|(name, seq)| (name.clone(), seq.len())).collect();
+        let aliases = config.aliases.as_deref().map(Aliases::from_path).transpose()?;
+        let gaps = config.gaps.as_ref().map(|path| load_gaps(path, aliases.as_ref()));
+        let regions = config.regions.as_ref().map(|path| regions::load_regions(path, &contigs, aliases.as_ref())).transpose()?;
+        Ok(Searcher {
+            fasta: IN_MEMORY.to_string(),
+            config,
+            contigs,
+            lengths,
+            duplicates: HashMap::new(),
+            gaps,
+            regions,
+            aliases,
+            // the sequences are in memory already
+            contig_cache: None,
+            mapped: None,
+            failures: Mutex::default(),
+            retried: AtomicU64::new(0),
+            cache: None,
+            prefilters: Vec::new(),
+            _renamed: None,
+            index_mismatches: Vec::new(),
+            sequences: Some(held),
+        })
+    }
+
+    // Contigs whose `bloom` or `minimizers` index did not match the FASTA, each with its index,
+    // when `SearchConfig::allow_mismatched_index` let the search go ahead.
+    pub fn index_mismatches(&self) -> &[String] {
+        &self.index_mismatches
+    }
+
+    // Chunks found in and read into the contig cache, if there is one.
+    pub fn contig_cache_stats(&self) -> Option<(u64, u64)> {
+        self.contig_cache.as_ref().map(ContigCache::stats)
+    }
+
+    // The `--alias` table, if one was given.
+    pub fn aliases(&self) -> Option<&Aliases> {
+        self.aliases.as_ref()
+    }
+
+    pub fn contigs(&self) -> &[(String, usize)] {
+        &self.contigs
+    }
+
+    pub fn length(&self, seq_name: &str) -> Option<usize> {
+        self.lengths.get(seq_name).copied()
+    }
+
+    // The reference bases of `seq_name` from `start` to `end`, 0-based and half-open. Each thread
+    // keeps its reader, so fetching many small pieces is cheap.
+    pub fn fetch(&self, seq_name: &str, start: usize, end: usize) -> Result<String, String> {
+        thread_local! {
+            static READER: RefCell<Option<(String, Reader)>> = const { RefCell::new(None) };
+        }
+        let length = self.length(seq_name).ok_or_else(|| format!("{}: no sequence {}", self.fasta, seq_name))?;
+        if start >= end || end > length {
+            return Err(format!("{}:{}-{} lies outside {} ({} bp)", seq_name, start + 1, end, seq_name, length));
+        }
+        if let Some(sequences) = &self.sequences {
+            let seq = Source::Memory(sequences).bases(seq_name, start, end)?;
+            return Ok(if self.config.alphabet == Alphabet::Dna { to_dna(&seq) } else { seq });
+        }
+        READER.with_borrow_mut(|cached| {
+            if cached.as_ref().is_none_or(|(fasta, _)| *fasta != self.fasta) {
+                *cached = Some((self.fasta.clone(), self.open_reader()?));
+            }
+            let opened = &cached.as_ref().unwrap().1;
+            let mapped;
+            let reader = match &self.mapped {
+                Some(fasta) => {
+                    mapped = Mapped { mapped: fasta, reader: opened };
+                    &mapped as &dyn Bases
+                }
+                None => opened,
+            };
+            let reader = &Retried { bases: reader, searcher: self };
+            let seq = match &self.contig_cache {
+                Some(cache) => cache.get(seq_name, length, start, end, |start, end| reader.bases(seq_name, start, end)),
+                None => reader.bases(seq_name, start, end),
+            };
+            // the next fetch opens the FASTA again, in case this handle went stale
+            let Ok(seq) = seq else {
+                *cached = None;
+                return seq;
+            };
+            Ok(if self.config.alphabet == Alphabet::Dna { to_dna(&seq) } else { seq })
+        })
+    }
+
+    // Mismatches between a plain query and the window starting at every position of `seq_name`,
+    // capped at 255: on the forward strand, and against the reverse complement of the window.
+    pub fn window_distances(&self, seq_name: &str, query: &Query) -> Result<[Vec<u8>; 2], String> {
+        let length = self.length(seq_name).ok_or_else(|| format!("{}: no sequence {}", self.fasta, seq_name))?;
+        let seq = self.fetch(seq_name, 0, length)?;
+        let distances = |target: &str| -> Vec<u8> {
+            let target = target.as_bytes();
+            let mismatch = |(&a, &b): (&u8, &u8)| a != b && !(query.degenerate && degenerate_matches(a, b));
+            seq.as_bytes().windows(target.len()).map(|window| window.iter().zip(target).filter(|&pair| mismatch(pair)).count().min(255) as u8).collect()
+        };
+        Ok([distances(&query.sequence), distances(&get_reverse_complement(&query.sequence))])
+    }
+
+    pub fn failures(&self) -> Vec<(String, String)> {
+        self.failures.lock().unwrap().clone()
+    }
+
+    // Reads of the FASTA that failed or came back short and succeeded when tried again.
+    pub fn retried_fetches(&self) -> u64 {
+        self.retried.load(Ordering::Relaxed)
+    }
+
+    // `read`, tried again up to `fetch_retries` times while it fails, as reads over NFS or from
+    // object storage now and then do; the error is the last one.
+    fn retrying<T>(&self, mut read: impl FnMut() -> Result<T, String>) -> Result<T, String> {
+        let (mut retry, mut wait) = (0, self.config.fetch_backoff_ms);
+        loop {
+            match read() {
+                Ok(value) => {
+                    if retry > 0 {
+                        self.retried.fetch_add(1, Ordering::Relaxed);
+                    }
+                    return Ok(value);
+                }
+                Err(error) if retry == self.config.fetch_retries || self.is_cancelled() => {
+                    return Err(if retry > 0 { format!("{} (tried {} times)", error, retry + 1) } else { error });
+                }
+                Err(_) => {
+                    std::thread::sleep(Duration::from_millis(wait));
+                    retry += 1;
+                    wait = wait.saturating_mul(2);
+                }
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.config.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    pub fn search(&self, query: &Query) -> Vec<Hit> {
+        self.search_batch(std::slice::from_ref(query))
+    }
+
+    // Searches all queries in one pass over the reference: each contig is fetched and
+    // reverse complemented once and scanned for every query. Hits carry their query's index.
+    pub fn search_batch(&self, queries: &[Query]) -> Vec<Hit> {
+        let all_hits = Mutex::new(Vec::new());
+        self.for_each_contig(queries, |contig| {
+            let mut found = Vec::new();
+            for name in std::iter::once(contig.seq_name).chain(contig.aliases.iter().map(String::as_str)) {
+                let name: Arc<str> = Arc::from(name);
+                for hit in contig.hits.iter().flatten() {
+                    found.push(Hit { seq_name: name.clone(), ..hit.clone() });
+                }
+            }
+            all_hits.lock().unwrap().extend(found);
+        });
+        all_hits.into_inner().unwrap()
+    }
+
+    // Scans contigs in parallel, calling `f` from the worker threads with each contig's hits.
+    // A query repeating an earlier one, or its reverse complement, is scanned once and its hits
+    // are copied or mirrored to every alias.
+    pub fn for_each_contig<F: Fn(ContigHits) + Sync>(&self, queries: &[Query], f: F) {
+        self.for_each_contig_until(queries, &AtomicBool::new(false), f)
+    }
+
+    // Calls `f` with every hit from the worker threads, in no particular order, until it returns
+    // `Break`. Each worker only holds the hits of its current contig, and after a `Break` no
+    // further contig is scanned; the first `Break` value is returned.
+    pub fn search_with<B: Send, F: Fn(&Hit) -> ControlFlow<B> + Sync>(&self, queries: &[Query], f: F) -> ControlFlow<B> {
+        let stop = AtomicBool::new(false);
+        let broken = Mutex::new(None);
+        self.for_each_contig_until(queries, &stop, |contig| {
+            let visit = |hit: &Hit| {
+                if stop.load(Ordering::Relaxed) {
+                    return false;
+                }
+                match f(hit) {
+                    ControlFlow::Continue(()) => true,
+                    ControlFlow::Break(value) => {
+                        stop.store(true, Ordering::Relaxed);
+                        broken.lock().unwrap().get_or_insert(value);
+                        false
+                    }
+                }
+            };
+            if !contig.hits.iter().flatten().all(visit) {
+                return;
+            }
+            for alias in contig.aliases {
+                let name: Arc<str> = Arc::from(alias.as_str());
+                if !contig.hits.iter().flatten().all(|hit| visit(&Hit { seq_name: name.clone(), ..hit.clone() })) {
+                    return;
+                }
+            }
+        });
+        match broken.into_inner().unwrap() {
+            Some(value) => ControlFlow::Break(value),
+            None => ControlFlow::Continue(()),
+        }
+    }
+
+    // `for_each_contig` that starts no further contig once `stop` is set.
+    fn for_each_contig_until<F: Fn(ContigHits) + Sync>(&self, queries: &[Query], stop: &AtomicBool, f: F) {
+        let (unique, sources) = collapse_queries(queries, self.config.alphabet == Alphabet::Dna);
+        if unique.len() == queries.len() {
+            return self.for_each_cached(queries, stop, f);
+        }
+        let unique: Vec<Query> = unique.into_iter().map(|q| queries[q].clone()).collect();
+        self.for_each_cached(&unique, stop, |contig| {
+            let hits = sources
+                .iter()
+                .enumerate()
+                .map(|(q, &(u, mirrored))| {
+                    let hits = &contig.hits[u];
+                    if mirrored {
+                        mirror_hits(hits, q)
+                    } else {
+                        hits.iter().map(|hit| Hit { query: q, ..hit.clone() }).collect()
+                    }
+                })
+                .collect();
+            f(ContigHits { hits, ..contig });
+        });
+        // a query answered by another's scan shares the time charged to it
+        for (query, &(u, _)) in queries.iter().zip(&sources) {
+            if let (Some(budget), Some(scanned)) = (&query.budget, &unique[u].budget) {
+                if !Arc::ptr_eq(budget, scanned) {
+                    budget.charge(scanned.spent());
+                }
+            }
+        }
+    }
+
+    // With a cache, queries found in it are not scanned again, and the hits of the others are
+    // stored once the search has completed without errors.
+    fn for_each_cached<F: Fn(ContigHits) + Sync>(&self, queries: &[Query], stop: &AtomicBool, f: F) {
+        let cache = match &self.cache {
+            // the reference composition is not cached, so such searches always scan
+            Some(cache) if !self.config.composition => cache,
+            _ => return self.scan_each_contig(queries, stop, f),
+        };
+        let keys: Vec<String> = queries.iter().map(|query| cache.key(query, &self.config)).collect();
+        let cached: Vec<Option<HashMap<String, Vec<Hit>>>> = keys.iter().enumerate().map(|(q, key)| cache.load(key, q)).collect();
+        let missing: Vec<usize> = (0..queries.len()).filter(|&q| cached[q].is_none()).collect();
+        let fresh: Vec<Mutex<HashMap<String, Vec<Hit>>>> = missing.iter().map(|_| Mutex::default()).collect();
+        let merge = |contig: ContigHits| {
+            let mut scanned = contig.hits.into_iter().zip(&missing).zip(&fresh);
+            let hits = (0..queries.len())
+                .map(|q| match &cached[q] {
+                    Some(entry) => entry.get(contig.seq_name).cloned().unwrap_or_default(),
+                    None => {
+                        let ((hits, &q), fresh) = scanned.next().unwrap();
+                        let hits: Vec<Hit> = hits.into_iter().map(|hit| Hit { query: q, ..hit }).collect();
+                        fresh.lock().unwrap().insert(contig.seq_name.to_string(), hits.clone());
+                        hits
+                    }
+                })
+                .collect();
+            f(ContigHits { hits, ..contig });
+        };
+        if missing.is_empty() {
+            let skipped: HashSet<&String> = self.duplicates.values().flatten().collect();
+            for (seq_name, seq_length) in self.contigs.iter().filter(|(seq_name, _)| !skipped.contains(seq_name)) {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let aliases = self.duplicates.get(seq_name).map(Vec::as_slice).unwrap_or_default();
+                merge(ContigHits { seq_name, aliases, length: *seq_length, composition: Composition::default(), hits: Vec::new() });
+            }
+            return;
+        }
+        let missing_queries: Vec<Query> = missing.iter().map(|&q| queries[q].clone()).collect();
+        self.scan_each_contig(&missing_queries, stop, merge);
+        if self.is_cancelled() || stop.load(Ordering::Relaxed) || !self.failures.lock().unwrap().is_empty() {
+            return;
+        }
+        for (&q, fresh) in missing.iter().zip(fresh) {
+            if queries[q].budget.as_ref().is_some_and(|budget| budget.is_exhausted()) {
+                continue;
+            }
+            // a failed write only costs a scan next time
+            let _ = cache.store(&keys[q], &fresh.into_inner().unwrap());
+        }
+    }
+
+    fn scan_each_contig<F: Fn(ContigHits) + Sync>(&self, queries: &[Query], stop: &AtomicBool, f: F) {
+        if self.fasta == STDIN {
+            return self.scan_stream(queries, stop, f);
+        }
+        let skipped: HashSet<&String> = self.duplicates.values().flatten().collect();
+        // a prefilter only helps when it applies to every query; the composition needs every contig read
+        let prefilters: Vec<(&dyn Prefilter, Vec<Pieces>)> = if self.config.composition {
+            Vec::new()
+        } else {
+            self.prefilters
+                .iter()
+                .filter_map(|prefilter| queries.iter().map(|query| prefilter.pieces(query)).collect::<Option<_>>().map(|pieces| (prefilter.as_ref(), pieces)))
+                .collect()
+        };
+        // longest first, handed out one at a time, so no thread is left with a long contig at the end
+        let mut by_length: Vec<&(String, usize)> = self.contigs.iter().collect();
+        by_length.sort_by_key(|(_, seq_length)| std::cmp::Reverse(*seq_length));
+        by_length.into_iter().par_bridge().for_each(|(seq_name, seq_length)| {
+            if skipped.contains(seq_name) || self.is_cancelled() || stop.load(Ordering::Relaxed) {
+                return;
+            }
+            if prefilters.iter().any(|(prefilter, pieces)| !pieces.iter().any(|pieces| prefilter.may_contain(seq_name, pieces))) {
+                let aliases = self.duplicates.get(seq_name).map(Vec::as_slice).unwrap_or_default();
+                f(ContigHits { seq_name, aliases, length: *seq_length, composition: Composition::default(), hits: vec![Vec::new(); queries.len()] });
+                return;
+            }
+            // a malformed record must not take the other contigs down with it
+            let scanned = panic::catch_unwind(AssertUnwindSafe(|| match &self.regions {
+                Some(regions) => self.scan_regions(seq_name, *seq_length, regions.get(seq_name).map(Vec::as_slice).unwrap_or_default(), queries),
+                None => self.scan_contig(&self.reader()?, seq_name, *seq_length, (0, *seq_length), queries, self.config.chunk_size),
+            }))
+            .unwrap_or_else(|panic| Err(panic_message(panic)));
+            match scanned {
+                Ok((hits, composition)) => {
+                    let aliases = self.duplicates.get(seq_name).map(Vec::as_slice).unwrap_or_default();
+                    f(ContigHits { seq_name, aliases, length: *seq_length, composition, hits });
+                }
+                Err(error) => self.failures.lock().unwrap().push((seq_name.clone(), error)),
+            }
+        });
+    }
+
+    // Scans the records of stdin a batch at a time, reading the next batch while one is scanned.
+    // A record that cannot be scanned is a failure like a contig; one that cannot be read ends
+    // the search as a failure of the stream.
+    fn scan_stream<F: Fn(ContigHits) + Sync>(&self, queries: &[Query], stop: &AtomicBool, f: F) {
+        let fail = |seq_name: &str, error: String| self.failures.lock().unwrap().push((seq_name.to_string(), error));
+        let mut records = match bgzf::Reader::from_stdin() {
+            Ok(input) => Records::new(BufReader::new(input)),
+            Err(e) => return fail(STDIN, e.to_string()),
+        };
+        let mut batch = records.batch(STREAM_BATCH_BASES);
+        loop {
+            let scanned = match batch {
+                Ok(scanned) if !scanned.is_empty() => scanned,
+                Ok(_) => return,
+                Err(error) => return fail(STDIN, error),
+            };
+            if self.is_cancelled() || stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let scan = || {
+                scanned.par_iter().filter(|(seq_name, seq)| self.config.searches(seq_name, seq.len())).for_each(|(seq_name, seq)| {
+                    let length = seq.len();
+                    let hits = panic::catch_unwind(AssertUnwindSafe(|| self.scan_contig(seq, seq_name, length, (0, length), queries, self.config.chunk_size)))
+                        .unwrap_or_else(|panic| Err(panic_message(panic)));
+                    match hits {
+                        Ok((hits, composition)) => f(ContigHits { seq_name, aliases: &[], length, composition, hits }),
+                        Err(error) => fail(seq_name, error),
+                    }
+                })
+            };
+            batch = rayon::join(scan, || records.batch(STREAM_BATCH_BASES)).1;
+        }
+    }
+
+    // Rescans up to `samples` contigs longer than the chunk size in one piece and compares their
+    // hits with the chunked scan, returning the number of hits compared or the first difference.
+    pub fn self_check(&self, queries: &[Query], samples: usize) -> Result<usize, String> {
+        let chunk_size = match self.config.chunk_size {
+            Some(chunk_size) => chunk_size,
+            None => return Ok(0),
+        };
+        let long: Vec<&(String, usize)> = self.contigs.iter().filter(|(_, length)| *length > chunk_size).collect();
+        let picked: Vec<&(String, usize)> = if long.len() <= samples {
+            long
+        } else {
+            (0..samples).map(|i| long[i * long.len() / samples]).collect()
+        };
+        let reader = self.reader()?;
+        let mut compared = 0;
+        for (seq_name, seq_length) in picked {
+            let (chunked, _) = self.scan_contig(&reader, seq_name, *seq_length, (0, *seq_length), queries, Some(chunk_size))?;
+            let (whole, _) = self.scan_contig(&reader, seq_name, *seq_length, (0, *seq_length), queries, None)?;
+            for (q, (chunked, whole)) in chunked.iter().zip(&whole).enumerate() {
+                let key = |hit: &Hit| (hit.strand, hit.start, hit.mismatches);
+                if let Some(i) = (0..chunked.len().max(whole.len())).find(|&i| chunked.get(i).map(key) != whole.get(i).map(key)) {
+                    return Err(format!(
+                        "{}: query {} differs between chunked and whole-contig scans at hit {}: {:?} vs {:?}",
+                        seq_name,
+                        q,
+                        i,
+                        chunked.get(i).map(key),
+                        whole.get(i).map(key)
+                    ));
+                }
+                compared += whole.len();
+            }
+        }
+        Ok(compared)
+    }
+
+    // Scans the (seq_name, start, end) ranges of `sample` for `queries` as a search would, in
+    // parallel in the current thread pool, and returns the number of hits; see `autotune`.
+    pub fn scan_sample(&self, queries: &[Query], sample: &[(String, usize, usize)]) -> Result<usize, String> {
+        sample
+            .par_iter()
+            .map_init(|| self.reader(), |reader, (seq_name, start, end)| {
+                let reader = reader.as_ref().map_err(String::clone)?;
+                let seq_length = self.length(seq_name).ok_or_else(|| format!("{}: no sequence {}", self.fasta, seq_name))?;
+                let (hits, _) = self.scan_contig(reader, seq_name, seq_length, (*start, *end), queries, self.config.chunk_size)?;
+                Ok(hits.iter().map(Vec::len).sum::<usize>())
+            })
+            .sum()
+    }
+
+    // A reader of its own for each thread, for thread safety.
+    fn reader(&self) -> Result<Source<'_>, String> {
+        match &self.sequences {
+            Some(sequences) => Ok(Source::Memory(sequences)),
+            None => self.open_reader().map(Source::Reader),
+        }
+    }
+
+    fn open_reader(&self) -> Result<Reader, String> {
+        self.retrying(|| Reader::from_path(&self.fasta).map_err(|e| e.to_string()))
+    }
+
+    // Scans each region of a contig on its own, in parallel, and returns the hits in the order
+    // of a contig scan. Windows must lie within a region.
+    fn scan_regions(&self, seq_name: &str, seq_length: usize, regions: &[(usize, usize)], queries: &[Query]) -> Result<(Vec<Vec<Hit>>, Composition), String> {
+        let scanned: Vec<Vec<Vec<Hit>>> = regions
+            .par_iter()
+            .map_init(|| self.reader(), |reader, &region| {
+                let reader = reader.as_ref().map_err(String::clone)?;
+                self.scan_contig(reader, seq_name, seq_length, region, queries, self.config.chunk_size).map(|(hits, _)| hits)
+            })
+            .collect::<Result<_, String>>()?;
+        let hits = (0..queries.len())
+            .map(|q| {
+                let forward = scanned.iter().flat_map(|hits| hits[q].iter().filter(|hit| hit.strand == Strand::Forward));
+                let reverse = scanned.iter().rev().flat_map(|hits| hits[q].iter().filter(|hit| hit.strand == Strand::Reverse));
+                forward.chain(reverse).cloned().collect()
+            })
+            .collect();
+        Ok((hits, Composition::default()))
+    }
+
+    // Fetches `range` of `seq_name` in chunks of `chunk_size` window starts (whole if None), each
+    // extended by the target length so windows crossing into the next chunk are scanned where
+    // they start. Hits come out in the same order as from a whole-range scan.
+    fn scan_contig(
+        &self,
+        reader: &dyn Bases,
+        seq_name: &str,
+        seq_length: usize,
+        range: (usize, usize),
+        queries: &[Query],
+        chunk_size: Option<usize>,
+    ) -> Result<(Vec<Vec<Hit>>, Composition), String> {
+        let (mapped, cached);
+        let reader = match &self.mapped {
+            Some(fasta) => {
+                mapped = Mapped { mapped: fasta, reader };
+                &mapped as &dyn Bases
+            }
+            None => reader,
+        };
+        let retried = Retried { bases: reader, searcher: self };
+        let reader = &retried as &dyn Bases;
+        let reader = match &self.contig_cache {
+            Some(cache) => {
+                cached = Cached { bases: reader, cache, length: seq_length };
+                &cached as &dyn Bases
+            }
+            None => reader,
+        };
+        let targets: Vec<String> = queries.iter().map(Query::target).collect();
+        let pams: Vec<Vec<String>> = queries.iter().map(Query::pams).collect();
+        let ignored: Vec<Vec<usize>> = queries.iter().map(Query::ignored_in_target).collect();
+        let min_runs: Vec<usize> = queries.iter().map(|q| q.min_n_run(&self.config)).collect();
+        let longest_target = targets.iter().map(String::len).max().unwrap_or(0);
+        let chunk_size = chunk_size.unwrap_or(range.1 - range.0).max(1);
+        let name: Arc<str> = Arc::from(seq_name);
+        let params: Vec<ScanParams> = queries
+            .iter()
+            .enumerate()
+            .map(|(q, query)| ScanParams {
+                target: &targets[q],
+                pams: &pams[q],
+                pam_5prime: query.pam_5prime,
+                ignored: &ignored[q],
+                degenerate: query.degenerate,
+                max_mismatches: query.max_mismatches_on(Strand::Forward),
+                tm: query.min_tm.map(|min_tm| (&self.config.tm_model, min_tm)),
+                cancel: self.config.cancel.as_deref(),
+                budget: query.budget.as_deref(),
+                seed_length: self.config.seed_length,
+                step: self.config.step.unwrap_or(1),
+                phase: 0,
+            })
+            .collect();
+        let rev_params: Vec<ScanParams> = queries.iter().zip(&params).map(|(query, params)| ScanParams { max_mismatches: query.max_mismatches_on(Strand::Reverse), ..params.clone() }).collect();
+        // queries compared window by window share a pass when their targets have the same
+        // length and skip the same N runs, and those that can be bit-sliced share it with each
+        // other; seeded ones are scanned alone. Auto leaves a query unseeded when enough others
+        // would share its bit-sliced pass, which costs less than a seeded scan per query.
+        let backend = if self.config.alphabet == Alphabet::Dna { self.config.backend } else { Backend::Windows };
+        let key = |q: usize| (targets[q].len(), min_runs[q], sliced::possible(&params[q]));
+        let mut sliceable: HashMap<(usize, usize, bool), usize> = HashMap::new();
+        for q in (0..queries.len()).filter(|&q| backend == Backend::Auto && sliced::possible(&params[q])) {
+            *sliceable.entry(key(q)).or_default() += 1;
+        }
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut by_length: HashMap<(usize, usize, bool), usize> = HashMap::new();
+        for q in 0..queries.len() {
+            let shares = sliceable.get(&key(q)).is_some_and(|&alike| alike >= sliced::MIN_AUTO_SHARED);
+            if !shares && (seed::chosen(backend, &params[q]) || seed::chosen(backend, &rev_params[q])) {
+                groups.push(vec![q]);
+                continue;
+            }
+            match by_length.get(&key(q)) {
+                Some(&group) => groups[group].push(q),
+                None => {
+                    by_length.insert(key(q), groups.len());
+                    groups.push(vec![q]);
+                }
+            }
+        }
+
+        let protein = self.config.alphabet == Alphabet::Protein;
+        let mut forward: Vec<Vec<Hit>> = vec![Vec::new(); queries.len()];
+        let mut reverse: Vec<Vec<Vec<Hit>>> = vec![Vec::new(); queries.len()];
+        let mut composition = Composition::default();
+        let mut prev_base = None;
+        let mut prev_runs = Vec::new();
+        let mut chunk_start = range.0;
+        while chunk_start < range.1 && !self.is_cancelled() {
+            let owned = chunk_size.min(range.1 - chunk_start);
+            let fetch_end = (chunk_start + owned + longest_target.saturating_sub(1)).min(range.1);
+            let chunk = reader.bases(seq_name, chunk_start, fetch_end)?;
+            let chunk = if protein { chunk } else { to_dna(&chunk) };
+            if chunk.len() != fetch_end - chunk_start {
+                return Err(format!("fetched {} bases at {}, the index promises {}", chunk.len(), chunk_start, fetch_end - chunk_start));
+            }
+            // a protein has no minus strand, and nothing is found in an empty one
+            let rev_chunk = if protein { String::new() } else { get_reverse_complement(&chunk) };
+            if self.config.composition {
+                composition.add_after(prev_base, &chunk.as_bytes()[..owned]);
+                prev_base = Some(chunk.as_bytes()[owned - 1]);
+            }
+            let n_runs = self.chunk_n_runs(reader, seq_name, seq_length, chunk_start, chunk.as_bytes(), &min_runs, &mut prev_runs)?;
+
+            for group in &groups {
+                let target_len = targets[group[0]].len();
+                let runs: Vec<(usize, usize)> = match min_runs[group[0]] {
+                    0 => Vec::new(),
+                    len => n_runs.iter().filter(|(_, _, run_len)| *run_len >= len).map(|&(start, end, _)| (start, end)).collect(),
+                };
+                let rev_runs = gaps::mirror(&runs, chunk.len());
+                let hit = |q: usize, strand, start: usize, window_str: &str, distance, tm| Hit { query: q, seq_name: name.clone(), strand, start, end: start + target_len, sequence: window_str.to_string(), mismatches: distance, tm };
+                let mut rev_hits: Vec<Vec<Hit>> = vec![Vec::new(); group.len()];
+                // with a step, both strands compare the windows starting at multiples of it in the contig
+                let step = self.config.step.unwrap_or(1);
+                let phase = (step - chunk_start % step) % step;
+                let rev_phase = (chunk_start + chunk.len()).saturating_sub(target_len) % step;
+                let group_params: Vec<ScanParams> = group.iter().map(|&q| ScanParams { phase, ..params[q].clone() }).collect();
+                let group_rev_params: Vec<ScanParams> = group.iter().map(|&q| ScanParams { phase: rev_phase, ..rev_params[q].clone() }).collect();
+                scan_blocks(&chunk, &group_params, &runs, backend, self.config.block_size, |k, idx, window_str, distance, tm| {
+                    if idx < owned {
+                        forward[group[k]].push(hit(group[k], Strand::Forward, chunk_start + idx, window_str, distance, tm));
+                    }
+                });
+                scan_blocks(&rev_chunk, &group_rev_params, &rev_runs, backend, self.config.block_size, |k, idx, window_str, distance, tm| {
+                    let local_start = chunk.len() - (idx + target_len);
+                    if local_start < owned {
+                        rev_hits[k].push(hit(group[k], Strand::Reverse, chunk_start + local_start, window_str, distance, tm));
+                    }
+                });
+                for (&q, rev_hits) in group.iter().zip(rev_hits) {
+                    reverse[q].push(rev_hits);
+                }
+            }
+            chunk_start += owned;
+        }
+
+        // minus-strand hits run from the contig end backwards
+        let hits = forward
+            .into_iter()
+            .zip(reverse)
+            .map(|(mut hits, rev)| {
+                hits.extend(rev.into_iter().rev().flatten());
+                hits
+            })
+            .collect();
+        Ok((hits, composition))
+    }
+
+    // N runs within the chunk starting at `chunk_start`, as chunk-local (start, end) with the
+    // length of the whole run they belong to, so skipping does not depend on chunk boundaries.
+    // `prev_runs` carries the previous chunk's runs (contig coordinates) over to this one.
+    #[allow(clippy::too_many_arguments)]
+    fn chunk_n_runs(
+        &self,
+        reader: &dyn Bases,
+        seq_name: &str,
+        seq_length: usize,
+        chunk_start: usize,
+        chunk: &[u8],
+        min_runs: &[usize],
+        prev_runs: &mut Vec<(usize, usize)>,
+    ) -> Result<Vec<(usize, usize, usize)>, String> {
+        let chunk_end = chunk_start + chunk.len();
+        let longest_needed = match min_runs.iter().copied().filter(|&len| len > 0).max() {
+            Some(len) => len,
+            None => return Ok(Vec::new()),
+        };
+        let mut runs: Vec<(usize, usize)> = match &self.gaps {
+            Some(gaps) => gaps
+                .get(seq_name)
+                .map(|runs| runs.iter().copied().filter(|&(start, end)| start < chunk_end && end > chunk_start).collect())
+                .unwrap_or_default(),
+            None => {
+                let mut runs: Vec<(usize, usize)> = gaps::find_n_runs(chunk, 1).into_iter().map(|(start, end)| (chunk_start + start, chunk_start + end)).collect();
+                // a run reaching into this chunk from the previous one
+                if let (Some(first), Some(prev)) = (runs.first_mut(), prev_runs.iter().find(|(start, end)| *start < chunk_start && *end >= chunk_start)) {
+                    if first.0 == chunk_start {
+                        *first = (prev.0, first.1.max(prev.1));
+                    }
+                }
+                // a run continuing past the end of the chunk, only measured as far as any query cares
+                if let Some(last) = runs.last_mut().filter(|last| last.1 == chunk_end) {
+                    while last.1 < seq_length && last.1 - last.0 < longest_needed {
+                        let ahead_end = (last.1 + longest_needed).min(seq_length);
+                        let ahead = reader.bases(seq_name, last.1, ahead_end)?;
+                        let n = ahead.bytes().take_while(|&b| b == b'N' || b == b'n').count();
+                        last.1 += n;
+                        if n < ahead.len() {
+                            break;
+                        }
+                    }
+                }
+                *prev_runs = runs.clone();
+                runs
+            }
+        };
+        runs.retain(|&(start, end)| start < chunk_end && end > chunk_start);
+        Ok(runs.iter().map(|&(start, end)| (start.max(chunk_start) - chunk_start, end.min(chunk_end) - chunk_start, end - start)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    // Random contigs with N runs of every length up to 12 and some soft-masked and IUPAC bases.
+    fn random_contig(rng: &mut Rng, length: usize) -> String {
+        let mut seq = String::with_capacity(length);
+        while seq.len() < length {
+            match rng.below(40) {
+                0 => seq.extend(std::iter::repeat_n('N', 1 + rng.below(12))),
+                1 => seq.push("acgtnRY".as_bytes()[rng.below(7)] as char),
+                _ => seq.push("ACGT".as_bytes()[rng.below(4)] as char),
+            }
+        }
+        seq.truncate(length);
+        seq
+    }
+
+    // A FASTA written for one test, in a directory of its own removed when it is dropped.
+    struct Fixture {
+        _dir: TempDir,
+        path: PathBuf,
+    }
+
+    impl std::ops::Deref for Fixture {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    fn write_fasta(name: &str, contigs: &[(String, String)]) -> Fixture {
+        static FIXTURES: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!("hamming-fasta-test-{}-{}", std::process::id(), FIXTURES.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let (mut fasta, mut fai) = (String::new(), String::new());
+        for (seq_name, seq) in contigs {
+            fasta.push_str(&format!(">{}\n", seq_name));
+            fai.push_str(&format!("{}\t{}\t{}\t60\t61\n", seq_name, seq.len(), fasta.len()));
+            for line in seq.as_bytes().chunks(60) {
+                fasta.push_str(std::str::from_utf8(line).unwrap());
+                fasta.push('\n');
+            }
+        }
+        fs::write(&path, fasta).unwrap();
+        fs::write(format!("{}.fai", path.display()), fai).unwrap();
+        Fixture { _dir: TempDir(dir), path }
+    }
+
+    fn searcher(path: &Path, chunk_size: Option<usize>, skip_n_runs: Option<usize>) -> Searcher {
+        let config = SearchConfig { chunk_size, skip_n_runs, ..SearchConfig::default() };
+        Searcher::new(path.to_str().unwrap(), config).unwrap()
+    }
+
+    fn keys(hits: Vec<Hit>) -> Vec<(usize, String, Strand, usize, usize, String, usize)> {
+        let mut keys: Vec<_> = hits.into_iter().map(|h| (h.query, h.seq_name.to_string(), h.strand, h.start, h.end, h.sequence, h.mismatches)).collect();
+        keys.sort();
+        keys
+    }
+
+    fn test_fasta(name: &str) -> (Fixture, Vec<Query>) {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let lengths = [1, 5, 19, 20, 21, 22, 23, 64, 300, 1000];
+        let contigs: Vec<(String, String)> = lengths.iter().enumerate().map(|(i, &len)| (format!("c{}", i), random_contig(&mut rng, len))).collect();
+        let big = &contigs[lengths.len() - 1].1;
+        let mut cas9 = Query::new(&big[100..117], 4);
+        cas9.cas9 = true;
+        // targets of different lengths in one batch, so chunks overlap by the longest of them
+        let queries = vec![Query::new(&big[500..520], 6), Query::new(&big[40..45], 1), cas9, Query::new("ACGTACGTACGTACGTACGTACGTACGTAC", 12)];
+        (write_fasta(name, &contigs), queries)
+    }
+
+    #[test]
+    fn chunked_scans_match_whole_contig_scans() {
+        let (path, queries) = test_fasta("chunked.fa");
+        for skip_n_runs in [None, Some(0), Some(1), Some(2), Some(5), Some(9)] {
+            let whole = keys(searcher(&path, None, skip_n_runs).search_batch(&queries));
+            assert!(!whole.is_empty());
+            for chunk_size in (1..=40).chain([63, 64, 65, 299, 999, 1000, 5000]) {
+                let chunked = keys(searcher(&path, Some(chunk_size), skip_n_runs).search_batch(&queries));
+                assert_eq!(chunked, whole, "chunk size {} with skip_n_runs {:?}", chunk_size, skip_n_runs);
+            }
+        }
+    }
+
+    #[test]
+    fn blocked_scans_match_single_pass_scans() {
+        let (path, queries) = test_fasta("blocked.fa");
+        for step in [None, Some(3)] {
+            let scan = |block_size| {
+                let config = SearchConfig { block_size, step, skip_n_runs: Some(2), ..SearchConfig::default() };
+                keys(Searcher::new(path.to_str().unwrap(), config).unwrap().search_batch(&queries))
+            };
+            let whole = scan(None);
+            assert!(!whole.is_empty());
+            for block_size in [1, 2, 7, 19, 20, 64, 299] {
+                assert_eq!(scan(Some(block_size)), whole, "block size {} with step {:?}", block_size, step);
+            }
+        }
+    }
+
+    #[test]
+    fn backends_report_the_same_hits() {
+        let (path, mut queries) = test_fasta("backends.fa");
+        let big = searcher(&path, None, None).fetch("c9", 0, 1000).unwrap();
+        // short pieces that only the pigeonhole backend seeds, and longer ones auto seeds too
+        queries.extend([Query::new(&big[600..620], 4), Query::new(&big[700..730], 3), Query::new(&big[200..264], 5)]);
+        // and more 20-base ones than fit one bit-sliced block, with ignored and degenerate bases
+        let mut ignored = Query::new(&big[300..320], 2);
+        ignored.ignored = vec![0, 7];
+        let mut degenerate = Query::new(&format!("{}NRY{}", &big[400..408], &big[411..420]), 2);
+        degenerate.degenerate = true;
+        queries.extend([ignored, degenerate]);
+        queries.extend((0..70).map(|i| Query::new(&big[i * 13..i * 13 + 20], i % 4)));
+        let scan = |backend| keys(Searcher::new(path.to_str().unwrap(), SearchConfig { backend, chunk_size: Some(97), ..SearchConfig::default() }).unwrap().search_batch(&queries));
+        let naive = scan(Backend::Windows);
+        assert!(!naive.is_empty());
+        assert_eq!(scan(Backend::Auto), naive);
+        assert_eq!(scan(Backend::Pigeonhole), naive);
+        assert_eq!(scan(Backend::Simd), naive);
+    }
+
+    #[test]
+    fn cached_fetches_and_searches_match_uncached_ones() {
+        let mut rng = Rng(7);
+        let length = crate::contig_cache::CHUNK_BASES + 5000;
+        let seq = random_contig(&mut rng, length);
+        let path = write_fasta("contig_cache.fa", &[("c".to_string(), seq.clone()), ("d".to_string(), random_contig(&mut rng, 3000))]);
+        let boundary = crate::contig_cache::CHUNK_BASES;
+        let queries = vec![Query::new(&seq[boundary - 10..boundary + 10], 3), Query::new(&seq[2000..2020], 4)];
+        let uncached = searcher(&path, Some(700_000), None);
+        let expected = keys(uncached.search_batch(&queries));
+        assert!(!expected.is_empty());
+        // room for one chunk, so consecutive searches both reuse and evict chunks
+        for cache_bytes in [1 << 20, 64 << 20] {
+            let config = SearchConfig { chunk_size: Some(700_000), cache_bytes: Some(cache_bytes), ..SearchConfig::default() };
+            let cached = Searcher::new(path.to_str().unwrap(), config).unwrap();
+            for _ in 0..2 {
+                assert_eq!(keys(cached.search_batch(&queries)), expected);
+                assert_eq!(cached.fetch("c", boundary - 100, boundary + 100).unwrap(), uncached.fetch("c", boundary - 100, boundary + 100).unwrap());
+                assert_eq!(cached.fetch("d", 0, 3000).unwrap(), uncached.fetch("d", 0, 3000).unwrap());
+            }
+            assert!(cached.contig_cache_stats().unwrap().0 > 0);
+        }
+    }
+
+    #[test]
+    fn mapped_fasta_reads_match_htslib() {
+        let (path, queries) = test_fasta("mapped.fa");
+        let mapped = Searcher::new(path.to_str().unwrap(), SearchConfig::default()).unwrap();
+        let config = SearchConfig { no_mmap: true, ..SearchConfig::default() };
+        let read = Searcher::new(path.to_str().unwrap(), config).unwrap();
+        assert!(mapped.mapped.is_some() && read.mapped.is_none());
+        assert_eq!(keys(mapped.search_batch(&queries)), keys(read.search_batch(&queries)));
+        // ranges within a line, across line ends and up to the end
+        for (start, end) in [(0, 1000), (59, 61), (60, 120), (7, 8), (950, 1000)] {
+            assert_eq!(mapped.fetch("c9", start, end).unwrap(), read.fetch("c9", start, end).unwrap());
+        }
+    }
+
+    #[test]
+    fn failed_and_short_reads_are_retried() {
+        // fails, then comes back short, then reads `bases`
+        struct Flaky {
+            bases: String,
+            calls: std::cell::Cell<usize>,
+        }
+        impl Bases for Flaky {
+            fn bases(&self, _: &str, start: usize, end: usize) -> Result<String, String> {
+                self.calls.set(self.calls.get() + 1);
+                match self.calls.get() {
+                    1 => Err("stale file handle".to_string()),
+                    2 => Ok(self.bases[start..end - 1].to_string()),
+                    _ => Ok(self.bases[start..end].to_string()),
+                }
+            }
+        }
+        let (path, _) = test_fasta("retried.fa");
+        let flaky = Flaky { bases: "ACGTACGT".to_string(), calls: Default::default() };
+        for (retries, read) in [(2, Ok("CGTA".to_string())), (1, Err("read 3 bases of c:2-5 (tried 2 times)".to_string()))] {
+            flaky.calls.set(0);
+            let searcher = Searcher::new(path.to_str().unwrap(), SearchConfig { fetch_retries: retries, ..SearchConfig::default() }).unwrap();
+            assert_eq!(Retried { bases: &flaky, searcher: &searcher }.bases("c", 1, 5), read);
+            assert_eq!(searcher.retried_fetches(), read.is_ok() as u64);
+        }
+    }
+
+    #[test]
+    fn in_memory_sequences_search_like_their_fasta() {
+        let (path, queries) = test_fasta("in_memory.fa");
+        let file = searcher(&path, None, None);
+        let sequences: Vec<(String, String)> = file.contigs().iter().map(|(seq_name, length)| (seq_name.clone(), file.fetch(seq_name, 0, *length).unwrap())).collect();
+        for chunk_size in [None, Some(97)] {
+            let config = SearchConfig { chunk_size, ..SearchConfig::default() };
+            let memory = Searcher::from_sequences(sequences.clone(), config).unwrap();
+            assert_eq!(memory.contigs(), file.contigs());
+            assert_eq!(keys(memory.search_batch(&queries)), keys(file.search_batch(&queries)));
+            assert_eq!(memory.fetch("c9", 59, 121).unwrap(), file.fetch("c9", 59, 121).unwrap());
+        }
+        assert!(Searcher::from_sequences([("a", "ACGT"), ("a", "ACGT")], SearchConfig::default()).is_err());
+    }
+
+    #[test]
+    fn chunk_boundary_hits_are_reported_once() {
+        let mut rng = Rng(42);
+        let seq = random_contig(&mut rng, 200);
+        let path = write_fasta("boundary.fa", &[("c".to_string(), seq.clone())]);
+        // every window is a hit, so every window start must appear exactly once per strand
+        let query = Query::new(&seq[..10], 10);
+        for chunk_size in 1..=25 {
+            let hits = keys(searcher(&path, Some(chunk_size), Some(0)).search(&query));
+            for strand in [Strand::Forward, Strand::Reverse] {
+                let starts: Vec<usize> = hits.iter().filter(|h| h.2 == strand).map(|h| h.3).collect();
+                assert_eq!(starts, (0..=190).collect::<Vec<usize>>(), "chunk size {}", chunk_size);
+            }
+        }
+    }
+
+    #[test]
+    fn n_runs_split_by_chunks_are_skipped_whole() {
+        // windows overlapping one end of an N run match with a single mismatch, so every run
+        // that is measured short because a chunk boundary splits it shows up as extra hits
+        let seq: String = (1..=12).map(|len| format!("TTACGT{}ACGTTT", "N".repeat(len))).collect();
+        let path = write_fasta("split_runs.fa", &[("c".to_string(), seq)]);
+        let query = Query::new("GACGT", 1);
+        for skip_n_runs in [2, 5, 9] {
+            let whole = keys(searcher(&path, None, Some(skip_n_runs)).search(&query));
+            for chunk_size in 1..=40 {
+                let chunked = keys(searcher(&path, Some(chunk_size), Some(skip_n_runs)).search(&query));
+                assert_eq!(chunked, whole, "chunk size {} with skip_n_runs {}", chunk_size, skip_n_runs);
+            }
+        }
+    }
+
+    #[test]
+    fn self_check_compares_long_contigs() {
+        let (path, queries) = test_fasta("self_check.fa");
+        let compared = searcher(&path, Some(17), Some(3)).self_check(&queries, 3).unwrap();
+        assert!(compared > 0);
+        assert_eq!(searcher(&path, None, None).self_check(&queries, 3), Ok(0));
+    }
+
+    #[test]
+    fn duplicate_names_are_handled_as_configured() {
+        let mut rng = Rng(7);
+        let contigs: Vec<(String, String)> = ["a", "b", "a", "a_2", "a"].iter().map(|name| (name.to_string(), random_contig(&mut rng, 100))).collect();
+        let path = write_fasta("duplicate_names.fa", &contigs);
+        let path = path.to_str().unwrap();
+        let named = |on_duplicate_names| Searcher::new(path, SearchConfig { on_duplicate_names, ..SearchConfig::default() });
+        assert!(named(DuplicateNames::Error).is_err());
+        let names = |searcher: &Searcher| searcher.contigs().iter().map(|(name, _)| name.clone()).collect::<Vec<String>>();
+        assert_eq!(names(&named(DuplicateNames::First).unwrap()), ["a", "b", "a_2"]);
+        let suffixed = named(DuplicateNames::Suffix).unwrap();
+        assert_eq!(names(&suffixed), ["a", "b", "a_3", "a_2", "a_4"]);
+        // every record is searched under its new name
+        for (seq_name, (_, seq)) in ["a", "b", "a_3", "a_2", "a_4"].iter().zip(&contigs) {
+            let hits = suffixed.search(&Query::new(&seq[10..30], 0));
+            assert!(hits.iter().any(|hit| &*hit.seq_name == *seq_name && hit.start == 10), "{}", seq_name);
+        }
+    }
+
+    #[test]
+    fn search_with_visits_every_hit_until_break() {
+        let (path, queries) = test_fasta("search_with.fa");
+        let searcher = searcher(&path, Some(50), None);
+        let all = searcher.search_batch(&queries);
+        let visited = Mutex::new(Vec::new());
+        let flow = searcher.search_with(&queries, |hit| {
+            visited.lock().unwrap().push(hit.clone());
+            ControlFlow::<()>::Continue(())
+        });
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(keys(visited.into_inner().unwrap()), keys(all.clone()));
+        let seen = AtomicUsize::new(0);
+        let flow = searcher.search_with(&queries, |hit| match seen.fetch_add(1, Ordering::Relaxed) {
+            2 => ControlFlow::Break(hit.start),
+            _ => ControlFlow::Continue(()),
+        });
+        assert!(matches!(flow, ControlFlow::Break(_)));
+        // hits being visited on other threads at the time are the only ones after the break
+        let seen = seen.into_inner();
+        assert!(seen >= 3 && seen < 3 + rayon::current_num_threads() && seen < all.len(), "{}", seen);
+    }
+
+    #[test]
+    fn pam_and_protospacer_intervals_on_both_strands() {
+        let mut rng = Rng(11);
+        let mut bases = |n: usize| -> String { (0..n).map(|_| "ACGT".as_bytes()[rng.below(4)] as char).collect() };
+        for (pam, pam_5prime) in [("NGG", false), ("NNGAAT", false), ("TTTN", true), ("TN", true)] {
+            let protospacer = bases(17);
+            let mut query = Query::new(&protospacer, 0);
+            (query.cas9, query.pam, query.pam_5prime) = (true, pam.to_string(), pam_5prime);
+            for strand in [Strand::Forward, Strand::Reverse] {
+                // one site per contig, at every offset from the contig's first base to its last
+                let windows: Vec<String> = (0..=20)
+                    .map(|_| {
+                        let site_pam: String = pam.chars().map(|c| if c == 'N' { bases(1) } else { c.to_string() }).collect();
+                        if pam_5prime { site_pam + &protospacer } else { protospacer.clone() + &site_pam }
+                    })
+                    .collect();
+                let contigs: Vec<(String, String)> = windows
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, window)| {
+                        let site = if strand == Strand::Forward { window.clone() } else { get_reverse_complement(window) };
+                        (format!("c{}", offset), format!("{}{}{}", bases(offset), site, bases(20 - offset)))
+                    })
+                    .collect();
+                let path = write_fasta(&format!("pam_{}_{:?}.fa", pam, strand), &contigs);
+                let hits = searcher(&path, None, None).search(&query);
+                for (offset, ((seq_name, seq), window)) in contigs.iter().zip(&windows).enumerate() {
+                    let context = format!("{} {:?} at {}", pam, strand, offset);
+                    let hit = hits.iter().find(|hit| &*hit.seq_name == seq_name && hit.strand == strand && hit.start == offset).expect(&context);
+                    assert_eq!((hit.end, &hit.sequence), (offset + window.len(), window), "{}", context);
+                    let oriented = |range: Range<usize>| if strand == Strand::Forward { seq[range].to_string() } else { get_reverse_complement(&seq[range]) };
+                    let (pam_interval, protospacer_interval) = (query.pam_interval(hit), query.protospacer_interval(hit));
+                    // the two tile the hit
+                    let mut tiles = [pam_interval.clone(), protospacer_interval.clone()];
+                    tiles.sort_by_key(|range| range.start);
+                    assert_eq!((tiles[0].start, tiles[0].end, tiles[1].end), (hit.start, tiles[1].start, hit.end), "{}", context);
+                    assert_eq!(oriented(pam_interval), query.pam(&hit.sequence), "{}", context);
+                    assert!(pam_matches(query.pam(&hit.sequence).as_bytes(), pam), "{}", context);
+                    assert_eq!(oriented(protospacer_interval), protospacer, "{}", context);
+                    assert_eq!(query.protospacer(&hit.sequence), protospacer, "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_queries_match_their_best_expansion() {
+        let mut rng = Rng(23);
+        // only bases whose matches every expansion agrees on
+        let mut seq: String = (0..3000).map(|_| "ACGTACGTacgt".as_bytes()[rng.below(12)] as char).collect();
+        let pattern = "GGCNNKGATRTCNNKTGC";
+        let code = |c: char| match c {
+            'N' => "ACGT".to_string(),
+            'K' => "GT".to_string(),
+            'R' => "AG".to_string(),
+            c => c.to_string(),
+        };
+        let mut expansions = vec![String::new()];
+        for c in pattern.chars() {
+            expansions = expansions.iter().flat_map(|prefix| code(c).chars().map(|b| format!("{}{}", prefix, b)).collect::<Vec<String>>()).collect();
+        }
+        // a few exact sites of each orientation
+        for (i, at) in [100, 900, 2000].into_iter().enumerate() {
+            let site = &expansions[rng.below(expansions.len())];
+            let site = if i == 1 { get_reverse_complement(site) } else { site.clone() };
+            seq.replace_range(at..at + site.len(), &site);
+        }
+        let path = write_fasta("degenerate.fa", &[("c".to_string(), seq)]);
+        let searcher = searcher(&path, None, None);
+        let mut query = Query::new(pattern, 3);
+        query.degenerate = true;
+        let found: Vec<(Strand, usize, String, usize)> = keys(searcher.search(&query)).into_iter().map(|(_, _, strand, start, _, window, mismatches)| (strand, start, window, mismatches)).collect();
+        // the fewest mismatches against any expansion, per window
+        let mut best: HashMap<(Strand, usize), (String, usize)> = HashMap::new();
+        for hit in searcher.search_batch(&expansions.iter().map(|e| Query::new(e, 3)).collect::<Vec<Query>>()) {
+            let entry = best.entry((hit.strand, hit.start)).or_insert((hit.sequence.clone(), hit.mismatches));
+            entry.1 = entry.1.min(hit.mismatches);
+        }
+        let mut expected: Vec<(Strand, usize, String, usize)> = best.into_iter().map(|((strand, start), (window, mismatches))| (strand, start, window, mismatches)).collect();
+        expected.sort();
+        assert!(found.iter().filter(|hit| hit.3 == 0).count() >= 3);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn protein_references_are_scanned_on_one_strand() {
+        let mut rng = Rng(29);
+        let residues = "ACDEFGHIKLMNPQRSTVWYU".as_bytes();
+        let mut seq: String = (0..2000).map(|_| residues[rng.below(residues.len())] as char).collect();
+        let peptide = "MKTUYIAKQRQISFVK";
+        seq.replace_range(700..700 + peptide.len(), peptide);
+        let path = write_fasta("protein.fa", &[("p".to_string(), seq.clone())]);
+        assert_eq!(detect_alphabet(path.to_str().unwrap()), Ok(Alphabet::Protein));
+        let searcher = Searcher::new(path.to_str().unwrap(), SearchConfig { alphabet: Alphabet::Protein, ..SearchConfig::default() }).unwrap();
+        let mut query = Query::new(peptide, 9);
+        query.sequence = peptide.to_string();
+        let found: Vec<(Strand, usize, String, usize)> = keys(searcher.search(&query)).into_iter().map(|(_, _, strand, start, _, window, mismatches)| (strand, start, window, mismatches)).collect();
+        let expected: Vec<(Strand, usize, String, usize)> = (0..=seq.len() - peptide.len())
+            .map(|start| (start, &seq[start..start + peptide.len()]))
+            .map(|(start, window)| (Strand::Forward, start, window.to_string(), crate::hamming_distance(window, peptide)))
+            .filter(|hit| hit.3 <= 9)
+            .collect();
+        assert!(expected.iter().any(|hit| hit.1 == 700 && hit.3 == 0));
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,140 @@
+// Seed-and-verify scanning for long queries. A window within k mismatches of the query matches
+// at least one of k + 1 disjoint query pieces exactly, so only windows anchored by an exact
+// piece match are compared, and each comparison stops at the first mismatch over the limit.
+// The lossy `--fast` mode seeds with every k-mer of the query instead.
+
+use crate::search::ScanParams;
+use crate::Backend;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+// Auto seeds queries whose pieces are at least this long. Timed against the full scan on
+// random and repeat-rich references, seeding was faster from 3-base pieces on, for queries of
+// any length and for one to hundreds of queries sharing a pass; with 2-base pieces most windows
+// are anchored and a repetitive query could be slower.
+const MIN_PIECE_LENGTH: usize = 3;
+// pieces are packed two bits per base into a u64
+const MAX_PIECE_LENGTH: usize = 32;
+
+fn code(b: u8) -> Option<u64> {
+    match b {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+fn piece_length(params: &ScanParams) -> usize {
+    match params.seed_length {
+        Some(k) => k,
+        None => (params.target.len() / (params.max_mismatches + 1)).min(MAX_PIECE_LENGTH),
+    }
+}
+
+// Offsets of the query pieces: k + 1 disjoint ones, or every k-mer in `--fast` mode.
+fn piece_offsets(params: &ScanParams, piece_len: usize) -> Vec<usize> {
+    match params.seed_length {
+        Some(_) => (0..=params.target.len() - piece_len).collect(),
+        None => (0..=params.max_mismatches).map(|p| p * piece_len).collect(),
+    }
+}
+
+// Seeding reproduces the full scan only for plain mismatch searches with ACGT queries, no
+// ignored positions, which break the exact piece match, and no step; `--fast` only seeds those too.
+pub fn possible(params: &ScanParams) -> bool {
+    let plain = params.pams.is_empty() && params.tm.is_none() && params.ignored.is_empty() && params.step <= 1 && params.target.bytes().all(|b| code(b).is_some());
+    match params.seed_length {
+        Some(k) => plain && (1..=MAX_PIECE_LENGTH).contains(&k) && k <= params.target.len(),
+        None => plain && piece_length(params) >= 1,
+    }
+}
+
+// Whether auto seeds: when seeding is possible and its pieces are long enough to be selective.
+pub fn applies(params: &ScanParams) -> bool {
+    possible(params) && (params.seed_length.is_some() || piece_length(params) >= MIN_PIECE_LENGTH)
+}
+
+// Whether `backend` seeds the target of `params` rather than comparing every window.
+pub fn chosen(backend: Backend, params: &ScanParams) -> bool {
+    match backend {
+        Backend::Auto => applies(params),
+        Backend::Windows | Backend::Simd => false,
+        Backend::Pigeonhole => possible(params),
+    }
+}
+
+// Same contract and report order as `scan_windows`.
+pub fn scan_seeded<F: FnMut(usize, &str, usize, Option<f64>)>(sequence: &str, params: &ScanParams, skip: &[(usize, usize)], mut report: F) {
+    // a seeded scan is of one target, so all of its time is charged to that target's budget
+    let mut checked = Instant::now();
+    let target = params.target.as_bytes();
+    let bytes = sequence.as_bytes();
+    if bytes.len() < target.len() {
+        return;
+    }
+    let piece_len = piece_length(params);
+    let mask = if piece_len == MAX_PIECE_LENGTH { u64::MAX } else { (1u64 << (2 * piece_len)) - 1 };
+    let mut pieces: HashMap<u64, Vec<usize>> = HashMap::new();
+    for offset in piece_offsets(params, piece_len) {
+        let packed = target[offset..offset + piece_len].iter().fold(0u64, |acc, &b| (acc << 2) | code(b).unwrap());
+        pieces.entry(packed).or_default().push(offset);
+    }
+
+    let last_start = bytes.len() - target.len();
+    let mut candidates = Vec::new();
+    let (mut packed, mut valid) = (0u64, 0);
+    for (i, &b) in bytes.iter().enumerate() {
+        match code(b) {
+            Some(c) => {
+                packed = ((packed << 2) | c) & mask;
+                valid += 1;
+            }
+            None => valid = 0,
+        }
+        if valid >= piece_len {
+            if let Some(offsets) = pieces.get(&packed) {
+                let piece_start = i + 1 - piece_len;
+                candidates.extend(offsets.iter().filter(|&&offset| offset <= piece_start && piece_start - offset <= last_start).map(|&offset| piece_start - offset));
+            }
+        }
+    }
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let mut skip = skip.iter().peekable();
+    for (n, idx) in candidates.into_iter().enumerate() {
+        if n % 1024 == 0 {
+            if params.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                return;
+            }
+            if params.budget.is_some_and(|budget| budget.charge(std::mem::replace(&mut checked, Instant::now()).elapsed())) {
+                return;
+            }
+        }
+        while skip.peek().is_some_and(|&&(_, end)| end <= idx) {
+            skip.next();
+        }
+        if skip.peek().is_some_and(|&&(start, _)| start < idx + target.len()) {
+            continue;
+        }
+        let window = &bytes[idx..idx + target.len()];
+        let mut distance = 0;
+        for (a, b) in window.iter().zip(target) {
+            if a != b {
+                distance += 1;
+                if distance > params.max_mismatches {
+                    break;
+                }
+            }
+        }
+        if distance <= params.max_mismatches {
+            report(idx, std::str::from_utf8(window).unwrap(), distance, None);
+        }
+    }
+    if let Some(budget) = params.budget {
+        budget.charge(checked.elapsed());
+    }
+}
@@ -0,0 +1,61 @@
+// Dinucleotide-preserving shuffles (Altschul & Erickson 1985), used as random controls with
+// the same base and dinucleotide composition as the query.
+
+use crate::rng::Rng;
+use std::collections::BTreeMap;
+
+// A uniformly random sequence with exactly the dinucleotide counts, first and last base of
+// `seq`: a random Eulerian walk through its dinucleotide graph.
+pub fn dinucleotide_shuffle(seq: &str, rng: &mut Rng) -> String {
+    let bases = seq.as_bytes();
+    if bases.len() < 3 {
+        return seq.to_string();
+    }
+    let last = bases[bases.len() - 1];
+    let mut edges: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+    for pair in bases.windows(2) {
+        edges.entry(pair[0]).or_default().push(pair[1]);
+    }
+
+    // every base but the last leaves through a random last-exit edge; accept them once they
+    // form a tree leading to the last base, so the walk cannot strand edges behind it
+    let exits: BTreeMap<u8, usize> = loop {
+        let exits: BTreeMap<u8, usize> = edges.iter().filter(|&(&b, _)| b != last).map(|(&b, out)| (b, rng.below(out.len()))).collect();
+        let reaches_last = exits.keys().all(|&start| {
+            let mut b = start;
+            for _ in 0..=exits.len() {
+                if b == last {
+                    return true;
+                }
+                b = edges[&b][exits[&b]];
+            }
+            false
+        });
+        if reaches_last {
+            break exits;
+        }
+    };
+
+    // shuffle the other edges of each base and leave its last-exit edge for the end
+    for (b, out) in edges.iter_mut() {
+        let n = match exits.get(b) {
+            Some(&exit) => {
+                let end = out.len() - 1;
+                out.swap(exit, end);
+                end
+            }
+            None => out.len(),
+        };
+        for i in (1..n).rev() {
+            out.swap(i, rng.below(i + 1));
+        }
+        out.reverse();
+    }
+    let mut shuffled = vec![bases[0]];
+    let mut b = bases[0];
+    while let Some(next) = edges.get_mut(&b).and_then(Vec::pop) {
+        shuffled.push(next);
+        b = next;
+    }
+    String::from_utf8(shuffled).unwrap()
+}
@@ -0,0 +1,765 @@
+// The writers a search's output goes to, fed a contig at a time from the `Searcher` callback:
+// the rows themselves, to stdout or --output, the --output-dir partitions, the --scatter-output
+// files or --format vcf records, with their --report and --hits-fasta copies; the sites of
+// --collapse-by-position; the quotas of --max-hits-per-sample; the profiles of --hits-consensus
+// and --hits-logo; and the files a --manifest lists.
+
+use crate::report::Report;
+use crate::spool::{collapse_order, Sorted, Spool};
+use crate::vcf::Vcf;
+use crate::{exit_with, hit_id, write_genotypes, Columns, NamedHit, EXIT_USAGE};
+use hamming_fasta::consensus::Profile;
+use hamming_fasta::{get_reverse_complement, pansn, Hit, Searcher, Strand};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+// Rows written after the search, from a spool, go out in batches of about this many bytes.
+const BATCH_BYTES: usize = 1 << 20;
+
+// Where rows go: the --output-dir partitions, a file per contig under --scatter-output, --format
+// vcf records, or else stdout or --output; the --report and --hits-fasta get every row as well.
+pub struct Rows<'a> {
+    pub columns: &'a Columns<'a>,
+    pub query_names: &'a [String],
+    pub out: Mutex<Box<dyn Write + Send>>,
+    // what `out` is called in errors
+    pub out_name: String,
+    pub partitions: Option<&'a Partitions>,
+    pub scatter: Option<Scatter>,
+    pub vcf: Option<Vcf>,
+    pub report: Option<Mutex<Report>>,
+    pub hits_fasta: Option<HitsFasta<'a>>,
+}
+
+// Rows gathered to be written together, such as those of one contig, holding the --report while
+// they are.
+pub struct Batch<'b> {
+    report: Option<MutexGuard<'b, Report>>,
+    rows: Vec<u8>,
+    partitions: BTreeMap<String, Vec<u8>>,
+    records: Vec<u8>,
+}
+
+impl Batch<'_> {
+    fn len(&self) -> usize {
+        self.rows.len() + self.partitions.values().map(Vec::len).sum::<usize>() + self.records.len()
+    }
+}
+
+impl Rows<'_> {
+    pub fn batch(&self) -> Batch<'_> {
+        Batch { report: self.report.as_ref().map(|report| report.lock().unwrap()), rows: Vec::new(), partitions: BTreeMap::new(), records: Vec::new() }
+    }
+
+    // `row` is the line written for `hit` under `name`.
+    pub fn push(&self, batch: &mut Batch, name: &str, hit: &Hit, row: &[u8]) -> Result<(), String> {
+        if let Some(report) = &mut batch.report {
+            report.add(hit, name, row);
+        }
+        if let Some(hits_fasta) = &self.hits_fasta {
+            hits_fasta.record(&mut batch.records, self.columns, name, hit);
+        }
+        match (&self.vcf, self.partitions) {
+            // written in order once the search is done
+            (Some(vcf), _) => vcf.add_record(name, &self.query_names[hit.query], hit, std::str::from_utf8(row).unwrap())?,
+            (None, Some(partitions)) => batch.partitions.entry(partitions.key(&self.query_names[hit.query], name)).or_default().extend_from_slice(row),
+            (None, None) => batch.rows.extend_from_slice(row),
+        }
+        Ok(())
+    }
+
+    // As `push`, writing the batch whenever it has grown to BATCH_BYTES.
+    pub fn stream(&self, batch: &mut Batch, name: &str, hit: &Hit, row: &[u8]) -> Result<(), String> {
+        self.push(batch, name, hit, row)?;
+        if batch.len() >= BATCH_BYTES {
+            self.write(batch, None)?;
+        }
+        Ok(())
+    }
+
+    // The rows of a contig's hits, each under every name the contig is reported by.
+    pub fn add(&self, contig: &str, names: &[&str], hits: &[Hit]) -> Result<(), String> {
+        let mut batch = self.batch();
+        for name in names {
+            for hit in hits {
+                let mut row = Vec::new();
+                self.columns.write_hit(&mut row, name, hit);
+                self.push(&mut batch, name, hit, &row)?;
+            }
+        }
+        self.write(&mut batch, Some(contig))
+    }
+
+    // Writes and empties a batch; under --scatter-output, the batch of a scanned `contig` is that
+    // contig's file.
+    pub fn write(&self, batch: &mut Batch, contig: Option<&str>) -> Result<(), String> {
+        if let Some(hits_fasta) = &self.hits_fasta {
+            hits_fasta.write(&std::mem::take(&mut batch.records))?;
+        }
+        if let Some(partitions) = self.partitions {
+            return partitions.write(std::mem::take(&mut batch.partitions));
+        }
+        self.write_lines(contig, &std::mem::take(&mut batch.rows))
+    }
+
+    // Lines that are not hit rows, such as a header or the intervals of --invert.
+    pub fn write_lines(&self, contig: Option<&str>, lines: &[u8]) -> Result<(), String> {
+        match (&self.scatter, contig) {
+            (Some(scatter), Some(contig)) => scatter.write(contig, lines),
+            _ => self.out.lock().unwrap().write_all(lines).map_err(|e| format!("{}: {}", self.out_name, e))?,
+        }
+        Ok(())
+    }
+
+    // The header of the rows, unless they go to partitions, which each have their own.
+    pub fn write_header(&self, header: &str, fasta: &str) -> Result<(), String> {
+        let mut out = self.out.lock().unwrap();
+        let written = match (&self.vcf, self.partitions) {
+            (Some(vcf), _) => vcf.write_header(&mut *out, header, fasta),
+            (None, None) => writeln!(out, "{}", header),
+            (None, Some(_)) => Ok(()),
+        };
+        written.map_err(|e| format!("{}: {}", self.out_name, e))
+    }
+
+    // Writes what is held until the search is done and returns the --report to be written.
+    pub fn finish(self) -> Result<Option<Report>, String> {
+        let Rows { out, out_name, scatter, vcf, report, hits_fasta, .. } = self;
+        let mut out = out.into_inner().unwrap();
+        if let Some(scatter) = &scatter {
+            scatter.merge(&mut out);
+        }
+        if let Some(vcf) = vcf {
+            vcf.finish(&mut out)?;
+        }
+        if let Some(hits_fasta) = hits_fasta {
+            hits_fasta.finish()?;
+        }
+        out.flush().map_err(|e| format!("{}: {}", out_name, e))?;
+        Ok(report.map(|report| report.into_inner().unwrap()))
+    }
+}
+
+// --hits-fasta: records of the reported hits in query orientation, or as --orientation and
+// --rna have the sequence column.
+pub struct HitsFasta<'a> {
+    path: String,
+    out: Mutex<BufWriter<File>>,
+    flank: usize,
+    searcher: &'a Searcher,
+    query_names: &'a [String],
+}
+
+impl<'a> HitsFasta<'a> {
+    pub fn create(path: &str, flank: usize, searcher: &'a Searcher, query_names: &'a [String]) -> Result<HitsFasta<'a>, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(HitsFasta { path: path.to_string(), out: Mutex::new(BufWriter::new(file)), flank, searcher, query_names })
+    }
+
+    fn record(&self, records: &mut Vec<u8>, columns: &Columns, seq_name: &str, hit: &Hit) {
+        let mut bases = hit.sequence.clone();
+        if let Some((left, right)) = (self.flank > 0).then(|| columns.flanks(self.searcher, hit, self.flank)).flatten() {
+            let (before, after) = match hit.strand {
+                Strand::Forward => (left, right),
+                Strand::Reverse => (get_reverse_complement(&right), get_reverse_complement(&left)),
+            };
+            bases = format!("{}{}{}", before.to_ascii_lowercase(), bases, after.to_ascii_lowercase());
+        }
+        writeln!(records, ">{}|{}|{}|{}|{}\n{}", self.query_names[hit.query], seq_name, hit.start, hit.strand, hit.mismatches, columns.oriented(hit, &bases)).unwrap();
+    }
+
+    fn write(&self, records: &[u8]) -> Result<(), String> {
+        self.out.lock().unwrap().write_all(records).map_err(|e| format!("{}: {}", self.path, e))
+    }
+
+    fn finish(self) -> Result<(), String> {
+        self.out.into_inner().unwrap().flush().map_err(|e| format!("{}: {}", self.path, e))
+    }
+}
+
+// --collapse-by-position: every reported hit under each of its names, grouped into sites once the
+// search is done.
+pub struct Collapse {
+    hits: Mutex<Spool<NamedHit>>,
+    tolerance: usize,
+}
+
+impl Collapse {
+    pub fn new(limit: usize, tolerance: usize) -> Collapse {
+        Collapse { hits: Mutex::new(Spool::new(limit, collapse_order)), tolerance }
+    }
+
+    pub fn add(&self, names: &[&str], hits: &[Hit]) -> Result<(), String> {
+        let mut spool = self.hits.lock().unwrap();
+        for name in names {
+            for hit in hits {
+                spool.push((name.to_string(), hit.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // A row per site, named by the contig without its PanSN prefix.
+    pub fn finish(self, rows: &Rows) -> Result<(), String> {
+        let hits = self.hits.into_inner().unwrap();
+        if hits.runs() > 0 {
+            eprintln!("# --collapse-by-position: {} sorted runs of hits spilled to {} (--sort-memory)", hits.runs(), std::env::temp_dir().display());
+        }
+        let mut batch = rows.batch();
+        write_collapsed(hits.into_sorted()?, self.tolerance, rows.columns, |best, contig, row| rows.stream(&mut batch, contig, best, row))?;
+        rows.write(&mut batch, None)
+    }
+}
+
+// Group hits by query, PanSN-stripped contig and strand, chaining hits whose starts lie within
+// `tolerance` of the previous one, and emit one row per site led by its best hit. The rows come
+// sorted by those and start, as `Spool::into_sorted` gives them, so sites are emitted as they end.
+fn write_collapsed<F: FnMut(&Hit, &str, &[u8]) -> Result<(), String>>(rows: Sorted<NamedHit>, tolerance: usize, columns: &Columns, mut emit: F) -> Result<(), String> {
+    let mut site: Vec<NamedHit> = Vec::new();
+    for row in rows {
+        let row = row?;
+        if let Some((last_name, last)) = site.last() {
+            let same = last.query == row.1.query && last.strand == row.1.strand && pansn::parse(last_name).contig == pansn::parse(&row.0).contig;
+            if !same || row.1.start - last.start > tolerance {
+                write_site(&site, columns, &mut emit)?;
+                site.clear();
+            }
+        }
+        site.push(row);
+    }
+    if !site.is_empty() {
+        write_site(&site, columns, &mut emit)?;
+    }
+    Ok(())
+}
+
+fn write_site<F: FnMut(&Hit, &str, &[u8]) -> Result<(), String>>(site: &[NamedHit], columns: &Columns, emit: &mut F) -> Result<(), String> {
+    let contig = pansn::parse(&site[0].0).contig;
+    let strand = site[0].1.strand;
+    let best = site.iter().map(|(_, hit)| hit).min_by_key(|hit| (hit.mismatches, hit.start)).unwrap();
+    let mut haplotypes: Vec<String> = site.iter().map(|(name, _)| pansn::parse(name).haplotype_id()).collect();
+    haplotypes.sort_unstable();
+    haplotypes.dedup();
+    let mut out = Vec::new();
+    columns.write_name(&mut out, best);
+    write!(out, "{}\t{}\t{}\t{}", contig, strand, best.start, best.end).unwrap();
+    columns.write_sequence(&mut out, best);
+    if let Some(tm) = best.tm {
+        write!(out, "\t{:.1}", tm).unwrap();
+    }
+    write!(out, "\t{}\t{}", haplotypes.len(), haplotypes.join(",")).unwrap();
+    if let Some(score) = columns.score(best) {
+        write!(out, "\t{}", score).unwrap();
+    }
+    columns.write_offtarget(&mut out, best);
+    columns.write_base_edit(&mut out, best);
+    columns.write_repeats(&mut out, &best.seq_name, best);
+    columns.write_population(&mut out, contig, best);
+    if columns.hit_ids {
+        write!(out, "\t{}", hit_id(&columns.queries[best.query].sequence, contig, best.start, strand)).unwrap();
+    }
+    columns.write_query_note(&mut out, best);
+    columns.write_pam_class(&mut out, best);
+    columns.write_context(&mut out, best);
+    if let Some(samples) = &columns.genotypes {
+        write_genotypes(&mut out, site, samples);
+    }
+    writeln!(out).unwrap();
+    emit(best, contig, &out)
+}
+
+// One TSV per query, contig or PanSN sample, each starting with the header. Files are opened
+// for appending one batch of rows at a time, so any number of partitions can be written.
+pub struct Partitions {
+    dir: PathBuf,
+    by: String,
+    header: String,
+    // partitions written so far; each new one truncates a file left over from an earlier run
+    started: Mutex<HashSet<String>>,
+}
+
+impl Partitions {
+    pub fn create(dir: &str, by: &str, header: &str) -> Result<Partitions, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("--output-dir {}: {}", dir, e))?;
+        Ok(Partitions { dir: PathBuf::from(dir), by: by.to_string(), header: header.to_string(), started: Mutex::default() })
+    }
+
+    fn key(&self, query_name: &str, seq_name: &str) -> String {
+        let key = match self.by.as_str() {
+            "query" => query_name,
+            "contig" => seq_name,
+            _ => {
+                let name = pansn::parse(seq_name);
+                name.sample.unwrap_or(name.contig)
+            }
+        };
+        key.chars().map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' }).collect()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.tsv", key))
+    }
+
+    fn write(&self, rows: BTreeMap<String, Vec<u8>>) -> Result<(), String> {
+        let mut started = self.started.lock().unwrap();
+        for (key, rows) in rows {
+            let path = self.path(&key);
+            let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+            let mut file = if started.insert(key) {
+                let mut file = File::create(&path).map_err(error)?;
+                writeln!(file, "{}", self.header).map_err(error)?;
+                file
+            } else {
+                OpenOptions::new().append(true).open(&path).map_err(error)?
+            };
+            file.write_all(&rows).map_err(error)?;
+        }
+        Ok(())
+    }
+
+    // The files written so far, by name.
+    fn files(&self) -> Vec<PathBuf> {
+        let started: BTreeSet<String> = self.started.lock().unwrap().iter().cloned().collect();
+        started.iter().map(|key| self.path(key)).collect()
+    }
+}
+
+// --output: rows go to a temporary file next to the path, renamed over it once the search has
+// completed, so a crashed, failed or timed-out run never leaves a file that looks finished.
+pub struct Output {
+    pub path: PathBuf,
+    pub partial: PathBuf,
+    // false when appending to rows that already have one
+    pub needs_header: bool,
+}
+
+impl Output {
+    pub fn create(path: &str, force: bool, append: bool) -> Result<(Output, File), String> {
+        let path = PathBuf::from(path);
+        let partial = PathBuf::from(format!("{}.partial", path.display()));
+        let existing = std::fs::metadata(&path).ok().map(|metadata| metadata.len());
+        if existing.is_some() && !force && !append {
+            eprintln!("error: {} exists; replace it with --force or add to it with --append", path.display());
+            exit_with(EXIT_USAGE);
+        }
+        let error = |e: std::io::Error| format!("{}: {}", partial.display(), e);
+        let file = match (append, existing) {
+            (true, Some(_)) => {
+                std::fs::copy(&path, &partial).map_err(|e| format!("copying {} to {}: {}", path.display(), partial.display(), e))?;
+                OpenOptions::new().append(true).open(&partial).map_err(error)?
+            }
+            _ => File::create(&partial).map_err(error)?,
+        };
+        let needs_header = !append || existing.is_none_or(|len| len == 0);
+        Ok((Output { path, partial, needs_header }, file))
+    }
+
+    pub fn finish(&self) -> Result<(), String> {
+        std::fs::rename(&self.partial, &self.path).map_err(|e| format!("renaming {} to {}: {}", self.partial.display(), self.path.display(), e))
+    }
+}
+
+// --manifest: the files a run wrote, with --output under its temporary name unless it completed,
+// then the partitions by name and `others` in the order given.
+pub fn written(output: Option<&Output>, partitions: Option<&Partitions>, complete: bool, others: &[&Option<String>]) -> Vec<String> {
+    let mut outputs: Vec<String> = output.iter().map(|output| if complete { &output.path } else { &output.partial }).map(|path| path.display().to_string()).collect();
+    outputs.extend(partitions.iter().flat_map(|partitions| partitions.files()).map(|path| path.display().to_string()));
+    outputs.extend(others.iter().copied().flatten().cloned());
+    outputs
+}
+
+// --max-hits-per-sample: the hits of each query in each PanSN sample, of which the `max` with the
+// fewest mismatches are reported, ties going to the lowest name, start and strand so the choice
+// does not depend on the order contigs are scanned in. Names outside the spec are a sample each.
+pub struct Quotas {
+    max: usize,
+    // --sort-memory of each spool
+    limit: usize,
+    // every hit found, by query, sample and then as above
+    hits: Mutex<Spool<NamedHit>>,
+}
+
+// A (query, sample) over the quota, with the number of hits found.
+pub type Capped = (usize, String, usize);
+
+fn sample_of(name: &str) -> &str {
+    let parsed = pansn::parse(name);
+    parsed.sample.unwrap_or(parsed.contig)
+}
+
+fn quota_key((name, hit): &NamedHit) -> (usize, &str, usize, &str, usize, Strand) {
+    (hit.query, sample_of(name), hit.mismatches, name, hit.start, hit.strand)
+}
+
+fn quota_order(a: &NamedHit, b: &NamedHit) -> std::cmp::Ordering {
+    quota_key(a).cmp(&quota_key(b))
+}
+
+// The reported rows come by name, start, strand and query.
+fn row_order((a_name, a): &NamedHit, (b_name, b): &NamedHit) -> std::cmp::Ordering {
+    (a_name, a.start, a.strand, a.query).cmp(&(b_name, b.start, b.strand, b.query))
+}
+
+impl Quotas {
+    pub fn new(max: usize, limit: usize) -> Quotas {
+        Quotas { max, limit, hits: Mutex::new(Spool::new(limit, quota_order)) }
+    }
+
+    pub fn add(&self, names: &[&str], hits: &[Hit]) -> Result<(), String> {
+        let mut spool = self.hits.lock().unwrap();
+        for name in names {
+            for hit in hits {
+                spool.push((name.to_string(), hit.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // The reported rows, and for each (query, sample) over the quota the number of hits found.
+    pub fn finish(self) -> Result<(Sorted<NamedHit>, Vec<Capped>), String> {
+        let mut rows = Spool::new(self.limit, row_order);
+        let mut capped = Vec::new();
+        // the (query, sample) being read and its hits so far
+        let mut group: Option<((usize, String), usize)> = None;
+        for named in self.hits.into_inner().unwrap().into_sorted()? {
+            let named = named?;
+            let key = (named.1.query, sample_of(&named.0).to_string());
+            match &mut group {
+                Some((current, found)) if *current == key => *found += 1,
+                _ => {
+                    if let Some(((query, sample), found)) = group.replace((key, 1)) {
+                        if found > self.max {
+                            capped.push((query, sample, found));
+                        }
+                    }
+                }
+            }
+            if group.as_ref().is_some_and(|(_, found)| *found <= self.max) {
+                rows.push(named)?;
+            }
+        }
+        if let Some(((query, sample), found)) = group {
+            if found > self.max {
+                capped.push((query, sample, found));
+            }
+        }
+        Ok((rows.into_sorted()?, capped))
+    }
+}
+
+// --scatter-output: one temporary file per contig, written by the thread that scanned it
+// without any lock, and concatenated in index order once the search is done.
+pub struct Scatter {
+    dir: PathBuf,
+    // position of each searched contig in the index
+    order: HashMap<String, usize>,
+}
+
+impl Scatter {
+    pub fn new(contigs: &[(String, usize)]) -> Scatter {
+        let dir = std::env::temp_dir().join(format!("hamming-fasta-scatter-{}", std::process::id()));
+        // left over from a crashed run that had the same process id
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let order = contigs.iter().enumerate().map(|(i, (seq_name, _))| (seq_name.clone(), i)).collect();
+        Scatter { dir, order }
+    }
+
+    fn path(&self, i: usize) -> PathBuf {
+        self.dir.join(format!("{}.tsv", i))
+    }
+
+    fn write(&self, seq_name: &str, rows: &[u8]) {
+        if !rows.is_empty() {
+            std::fs::write(self.path(self.order[seq_name]), rows).unwrap();
+        }
+    }
+
+    fn merge<W: Write>(&self, out: &mut W) {
+        for i in 0..self.order.len() {
+            if let Ok(mut file) = File::open(self.path(i)) {
+                std::io::copy(&mut file, out).unwrap();
+            }
+        }
+        std::fs::remove_dir_all(&self.dir).unwrap();
+    }
+}
+
+// --hits-consensus and --hits-logo: per query, the bases of its reported hits at each position.
+pub struct Profiles(Vec<Mutex<Profile>>);
+
+impl Profiles {
+    pub fn new(queries: usize) -> Profiles {
+        Profiles((0..queries).map(|_| Mutex::default()).collect())
+    }
+
+    // The hits of query `q` on a contig reported under `copies` names.
+    pub fn add(&self, q: usize, copies: usize, hits: &[Hit]) {
+        let mut contig_profile = Profile::default();
+        for hit in hits {
+            contig_profile.add(&hit.sequence);
+        }
+        let mut profile = self.0[q].lock().unwrap();
+        for _ in 0..copies {
+            profile.merge(&contig_profile);
+        }
+    }
+
+    pub fn get(&self, q: usize) -> MutexGuard<'_, Profile> {
+        self.0[q].lock().unwrap()
+    }
+
+    // --hits-logo: the matrix of each query, and with `text` its logo.
+    pub fn write_logo(&self, path: &str, query_names: &[String], targets: &[String], text: bool) -> Result<(), String> {
+        let write = || -> std::io::Result<()> {
+            let mut out = BufWriter::new(File::create(path)?);
+            for (q, profile) in self.0.iter().enumerate() {
+                let profile = profile.lock().unwrap();
+                writeln!(out, "# query {}", query_names[q])?;
+                profile.write_matrix(&mut out, &targets[q])?;
+                if text {
+                    profile.write_logo(&mut out, &targets[q], 4)?;
+                }
+            }
+            out.flush()
+        };
+        write().map_err(|e| format!("--hits-logo {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Orientation;
+    use hamming_fasta::{Query, SearchConfig};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    // A directory for one test, removed when it is dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("hamming-fasta-sinks-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).display().to_string()
+        }
+
+        fn read(&self, name: &str) -> String {
+            std::fs::read_to_string(self.0.join(name)).unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn columns(queries: &[Query]) -> Columns<'_> {
+        Columns {
+            queries,
+            names: None,
+            scored_targets: queries.iter().map(|query| query.sequence.clone()).collect(),
+            matrix: None,
+            liftover: None,
+            graph: None,
+            hit_ids: false,
+            orientation: Orientation::Query,
+            rna: false,
+            base_editor: None,
+            repeats: None,
+            population: None,
+            polymorphic_sites: Mutex::default(),
+            flank_failures: Mutex::default(),
+            population_failures: Mutex::default(),
+            query_notes: None,
+            pam_classes: false,
+            context: None,
+            split_pam: false,
+            seed_region: None,
+            mit: false,
+            genotypes: None,
+            aliases: None,
+        }
+    }
+
+    fn rows<'a>(columns: &'a Columns, query_names: &'a [String], out: &str) -> Rows<'a> {
+        Rows {
+            columns,
+            query_names,
+            out: Mutex::new(Box::new(File::create(out).unwrap())),
+            out_name: out.to_string(),
+            partitions: None,
+            scatter: None,
+            vcf: None,
+            report: None,
+            hits_fasta: None,
+        }
+    }
+
+    fn hit(query: usize, seq_name: &str, strand: Strand, start: usize, mismatches: usize) -> Hit {
+        Hit { query, seq_name: Arc::from(seq_name), strand, start, end: start + 4, sequence: "ACGA".to_string(), mismatches, tm: None }
+    }
+
+    fn names() -> Vec<String> {
+        vec!["q1".to_string(), "q2".to_string()]
+    }
+
+    fn queries() -> Vec<Query> {
+        vec![Query::new("ACGT", 2), Query::new("TTTT", 2)]
+    }
+
+    const HEADER: &str = "seq_name\tstrand\tstart\tend\tsequence\tmismatches";
+
+    #[test]
+    fn rows_are_copied_to_the_report_and_the_hits_fasta() {
+        let dir = TempDir::new("rows");
+        let fasta = dir.path("ref.fa");
+        std::fs::write(&fasta, ">chr1\nACGTACGTAC\n").unwrap();
+        std::fs::write(format!("{}.fai", fasta), "chr1\t10\t6\t10\t11\n").unwrap();
+        let searcher = Searcher::new(&fasta, SearchConfig::default()).unwrap();
+        let (queries, query_names) = (queries(), names());
+        let columns = columns(&queries);
+        let mut rows = rows(&columns, &query_names, &dir.path("out.tsv"));
+        rows.report = Some(Mutex::default());
+        rows.hits_fasta = Some(HitsFasta::create(&dir.path("hits.fa"), 0, &searcher, &query_names).unwrap());
+        rows.write_header(HEADER, &fasta).unwrap();
+        // a contig and the identical one that shares its hits
+        rows.add("chr1", &["chr1", "copy"], &[hit(0, "chr1", Strand::Forward, 0, 1), hit(1, "chr1", Strand::Reverse, 4, 2)]).unwrap();
+        let report = rows.finish().unwrap().unwrap();
+        let expected = [HEADER, "chr1\t+\t0\t4\tACGA\t1", "chr1\t-\t4\t8\tACGA\t2", "copy\t+\t0\t4\tACGA\t1", "copy\t-\t4\t8\tACGA\t2"];
+        assert_eq!(dir.read("out.tsv").lines().collect::<Vec<_>>(), expected);
+        assert_eq!(dir.read("hits.fa"), ">q1|chr1|0|+|1\nACGA\n>q2|chr1|4|-|2\nACGA\n>q1|copy|0|+|1\nACGA\n>q2|copy|4|-|2\nACGA\n");
+        report.write(&dir.path("report.html"), HEADER, &[("chr1".to_string(), 10)]).unwrap();
+        let html = dir.read("report.html");
+        assert!(html.contains("Hits: 4."));
+        assert!(html.contains("<tr><td>copy</td><td>-</td><td>4</td><td>8</td><td>ACGA</td><td>2</td></tr>"));
+    }
+
+    #[test]
+    fn partitions_start_with_the_header_and_are_listed_by_name() {
+        let dir = TempDir::new("partitions");
+        let (queries, query_names) = (queries(), names());
+        let columns = columns(&queries);
+        let partitions = Partitions::create(&dir.path("by-sample"), "sample", HEADER).unwrap();
+        let mut rows = rows(&columns, &query_names, &dir.path("out.tsv"));
+        rows.partitions = Some(&partitions);
+        rows.write_header(HEADER, "ref.fa").unwrap();
+        rows.add("HG2#1#chr1", &["HG2#1#chr1"], &[hit(0, "HG2#1#chr1", Strand::Forward, 3, 0)]).unwrap();
+        rows.add("HG1#1#chr1", &["HG1#1#chr1", "HG1#2#chr1"], &[hit(1, "HG1#1#chr1", Strand::Forward, 5, 1)]).unwrap();
+        rows.add("HG2#2#chr1", &["HG2#2#chr1"], &[hit(0, "HG2#2#chr1", Strand::Reverse, 7, 2)]).unwrap();
+        rows.finish().unwrap();
+        // the header goes to each partition rather than to stdout
+        assert_eq!(dir.read("out.tsv"), "");
+        assert_eq!(dir.read("by-sample/HG1.tsv"), format!("{}\nHG1#1#chr1\t+\t5\t9\tACGA\t1\nHG1#2#chr1\t+\t5\t9\tACGA\t1\n", HEADER));
+        assert_eq!(dir.read("by-sample/HG2.tsv"), format!("{}\nHG2#1#chr1\t+\t3\t7\tACGA\t0\nHG2#2#chr1\t-\t7\t11\tACGA\t2\n", HEADER));
+        let report = Some("report.html".to_string());
+        assert_eq!(written(None, Some(&partitions), true, &[&report, &None]), [dir.path("by-sample/HG1.tsv"), dir.path("by-sample/HG2.tsv"), "report.html".to_string()]);
+    }
+
+    #[test]
+    fn scattered_rows_are_merged_in_index_order() {
+        let dir = TempDir::new("scatter");
+        let (queries, query_names) = (queries(), names());
+        let columns = columns(&queries);
+        let mut rows = rows(&columns, &query_names, &dir.path("out.tsv"));
+        rows.scatter = Some(Scatter::new(&[("chr1".to_string(), 10), ("chr2".to_string(), 10), ("chr3".to_string(), 10)]));
+        let scatter_dir = rows.scatter.as_ref().unwrap().dir.clone();
+        // as threads finish: the last contig first, and one without hits
+        rows.add("chr3", &["chr3"], &[hit(0, "chr3", Strand::Forward, 1, 0)]).unwrap();
+        rows.add("chr2", &["chr2"], &[]).unwrap();
+        rows.write_lines(Some("chr1"), b"chr1\t0\t10\n").unwrap();
+        rows.finish().unwrap();
+        assert_eq!(dir.read("out.tsv"), "chr1\t0\t10\nchr3\t+\t1\t5\tACGA\t0\n");
+        assert!(!scatter_dir.exists());
+    }
+
+    #[test]
+    fn vcf_records_replace_the_rows() {
+        let dir = TempDir::new("vcf");
+        let (queries, query_names) = (queries(), names());
+        let columns = columns(&queries);
+        let mut rows = rows(&columns, &query_names, &dir.path("out.vcf"));
+        rows.vcf = Some(Vcf::new(HEADER, vec![("chr1".to_string(), 100)], usize::MAX));
+        rows.add("chr1", &["chr1"], &[hit(1, "chr1", Strand::Reverse, 40, 1), hit(0, "chr1", Strand::Forward, 9, 0)]).unwrap();
+        rows.finish().unwrap();
+        let positions: Vec<(String, String)> = dir.read("out.vcf").lines().map(|line| line.split('\t').take(2).collect::<Vec<_>>()).map(|fields| (fields[0].to_string(), fields[1].to_string())).collect();
+        assert_eq!(positions, [("chr1".to_string(), "10".to_string()), ("chr1".to_string(), "41".to_string())]);
+    }
+
+    #[test]
+    fn collapsed_sites_name_their_haplotypes() {
+        let dir = TempDir::new("collapse");
+        let (queries, query_names) = (queries(), names());
+        let columns = columns(&queries);
+        let rows = rows(&columns, &query_names, &dir.path("out.tsv"));
+        // held in memory, and spilled as a run per hit
+        for limit in [usize::MAX, 1] {
+            let collapse = Collapse::new(limit, 2);
+            collapse.add(&["HG2#1#chr1"], &[hit(0, "HG2#1#chr1", Strand::Forward, 11, 0), hit(0, "HG2#1#chr1", Strand::Forward, 30, 1)]).unwrap();
+            // a site chained across a start 2 bases on, led by its best hit
+            collapse.add(&["HG1#1#chr1", "HG1#2#chr1"], &[hit(0, "HG1#1#chr1", Strand::Forward, 10, 1), hit(0, "HG1#1#chr1", Strand::Forward, 13, 1)]).unwrap();
+            collapse.finish(&rows).unwrap();
+        }
+        rows.finish().unwrap();
+        let site = "chr1\t+\t11\t15\tACGA\t0\t3\tHG1#1,HG1#2,HG2#1\nchr1\t+\t30\t34\tACGA\t1\t1\tHG2#1\n";
+        assert_eq!(dir.read("out.tsv"), site.repeat(2));
+    }
+
+    #[test]
+    fn quotas_keep_the_best_hits_of_each_sample() {
+        let quotas = Quotas::new(1, usize::MAX);
+        quotas.add(&["HG1#1#chr1", "HG1#2#chr1"], &[hit(0, "HG1#1#chr1", Strand::Forward, 20, 1), hit(0, "HG1#1#chr1", Strand::Forward, 5, 2)]).unwrap();
+        quotas.add(&["HG2#1#chr1"], &[hit(0, "HG2#1#chr1", Strand::Reverse, 8, 2), hit(1, "HG2#1#chr1", Strand::Forward, 1, 0)]).unwrap();
+        let (rows, capped) = quotas.finish().unwrap();
+        let rows: Vec<(String, usize, usize)> = rows.map(|row| row.unwrap()).map(|(name, hit)| (name, hit.query, hit.start)).collect();
+        // the tie at 1 mismatch goes to the first haplotype; the rows come by name
+        assert_eq!(rows, [("HG1#1#chr1".to_string(), 0, 20), ("HG2#1#chr1".to_string(), 1, 1), ("HG2#1#chr1".to_string(), 0, 8)]);
+        assert_eq!(capped, [(0, "HG1".to_string(), 4)]);
+    }
+
+    #[test]
+    fn profiles_count_every_reported_copy() {
+        let dir = TempDir::new("profiles");
+        let profiles = Profiles::new(2);
+        profiles.add(0, 2, &[hit(0, "chr1", Strand::Forward, 0, 1)]);
+        profiles.add(0, 1, &[]);
+        assert_eq!(profiles.get(0).consensus(), "ACGA");
+        profiles.write_logo(&dir.path("logo.txt"), &names(), &["ACGT".to_string(), "TTTT".to_string()], false).unwrap();
+        let logo = dir.read("logo.txt");
+        let lines: Vec<&str> = logo.lines().collect();
+        assert_eq!(lines[..3], ["# query q1", "# position counts of 2 hits", "position\tquery\tA\tC\tG\tT\tother\tbits"]);
+        assert_eq!(lines[6], "4\tT\t2\t0\t0\t0\t0\t2.000");
+        assert_eq!(lines[7..9], ["# query q2", "# position counts of 0 hits"]);
+        assert!(profiles.write_logo(&dir.path("missing/logo.txt"), &names(), &["ACGT".to_string(), "TTTT".to_string()], false).unwrap_err().starts_with("--hits-logo "));
+    }
+
+    #[test]
+    fn output_is_renamed_into_place_once_finished() {
+        let dir = TempDir::new("output");
+        let path = dir.path("out.tsv");
+        let (output, mut file) = Output::create(&path, false, false).unwrap();
+        assert!(output.needs_header);
+        file.write_all(b"header\nrow\n").unwrap();
+        assert!(!Path::new(&path).exists());
+        assert_eq!(written(Some(&output), None, false, &[]), [format!("{}.partial", path)]);
+        output.finish().unwrap();
+        assert_eq!(written(Some(&output), None, true, &[]), [path.as_str()]);
+        // appended rows follow the existing header in a copy
+        let (output, mut file) = Output::create(&path, false, true).unwrap();
+        assert!(!output.needs_header);
+        file.write_all(b"more\n").unwrap();
+        assert_eq!(dir.read("out.tsv"), "header\nrow\n");
+        output.finish().unwrap();
+        assert_eq!(dir.read("out.tsv"), "header\nrow\nmore\n");
+    }
+}
@@ -0,0 +1,172 @@
+// Per-contig sketches built by `index` and the k-mer helpers they share. Sketches are stored
+// next to the FASTA as one little-endian file: a magic, two parameters and one or more
+// generations of entries, each holding per contig its name, indexed length, the MD5 of its
+// sequence and a vector of u64. `index --add` appends a generation instead of rebuilding the file.
+
+use crate::md5::sequence_md5;
+use crate::search::Query;
+use rayon::prelude::*;
+use rust_htslib::faidx::Reader;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+// k-mers are packed two bits per base into a u64
+pub const MAX_K: usize = 32;
+
+pub(crate) fn code(b: u8) -> Option<u64> {
+    match b {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+// splitmix64 finalizer
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// Canonical packed k-mers (the smaller of each k-mer and its reverse complement) of the
+// uppercase ACGT stretches of `seq`, with the position of their first base. Soft-masked and
+// ambiguous bases never match a query exactly, so k-mers containing them are left out.
+pub(crate) fn canonical_kmers(seq: &[u8], k: usize, mut f: impl FnMut(usize, u64)) {
+    let mask = if k == MAX_K { u64::MAX } else { (1u64 << (2 * k)) - 1 };
+    let (mut forward, mut reverse, mut valid) = (0u64, 0u64, 0);
+    for (i, &b) in seq.iter().enumerate() {
+        match code(b) {
+            Some(c) => {
+                forward = ((forward << 2) | c) & mask;
+                reverse = (reverse >> 2) | ((3 - c) << (2 * (k - 1)));
+                valid += 1;
+            }
+            None => valid = 0,
+        }
+        if valid >= k {
+            f(i + 1 - k, forward.min(reverse));
+        }
+    }
+}
+
+// The k + 1 disjoint pieces of `query`, one of which any window within its k mismatches
+// contains exactly, or None when that cannot be relied on: no mismatch bound (Tm mode), ignored
+// positions, bases other than ACGT, or pieces shorter than `min_len`.
+pub(crate) fn pigeonhole_pieces(query: &Query, min_len: usize) -> Option<Vec<&[u8]>> {
+    let sequence = query.sequence.as_bytes();
+    let piece_len = sequence.len() / (query.max_mismatches + 1);
+    if query.min_tm.is_some() || !query.ignored.is_empty() || piece_len < min_len || !sequence.iter().all(|&b| code(b).is_some()) {
+        return None;
+    }
+    Some((0..=query.max_mismatches).map(|p| &sequence[p * piece_len..(p + 1) * piece_len]).collect())
+}
+
+// A contig's MD5 and values.
+type Sketch = ([u8; 16], Vec<u64>);
+
+// The MD5 and sketch of every contig, sketched in parallel with `sketch` (given the whole contig).
+fn sketch_contigs<F: Fn(&[u8]) -> Vec<u64> + Sync>(fasta: &str, contigs: &[(String, usize)], sketch: F) -> Vec<Result<Sketch, String>> {
+    contigs
+        .par_iter()
+        .map(|(seq_name, length)| {
+            let reader = Reader::from_path(fasta).map_err(|e| e.to_string())?;
+            if *length == 0 {
+                return Ok((sequence_md5(&[]), sketch(&[])));
+            }
+            let seq = reader.fetch_seq_string(seq_name, 0, length - 1).map_err(|e| format!("{}: {}", seq_name, e))?;
+            Ok((sequence_md5(seq.as_bytes()), sketch(seq.as_bytes())))
+        })
+        .collect()
+}
+
+// One generation of entries: their count, then per contig its name, length, MD5 and values.
+fn write_generation<W: Write>(out: &mut W, path: &str, contigs: &[(String, usize)], sketches: Vec<Result<Sketch, String>>) -> Result<(), String> {
+    let mut write = |bytes: &[u8]| out.write_all(bytes).map_err(|e| format!("{}: {}", path, e));
+    write(&(contigs.len() as u64).to_le_bytes())?;
+    for ((seq_name, length), sketch) in contigs.iter().zip(sketches) {
+        let (md5, values) = sketch?;
+        write(&(seq_name.len() as u32).to_le_bytes())?;
+        write(seq_name.as_bytes())?;
+        write(&(*length as u64).to_le_bytes())?;
+        write(&md5)?;
+        write(&(values.len() as u64).to_le_bytes())?;
+        for value in &values {
+            write(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// Sketches every contig of `fasta` and writes them to `path`.
+pub(crate) fn build<F: Fn(&[u8]) -> Vec<u64> + Sync>(fasta: &str, path: &str, magic: &[u8; 8], params: [u32; 2], contigs: &[(String, usize)], sketch: F) -> Result<(), String> {
+    let sketches = sketch_contigs(fasta, contigs, sketch);
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    let mut header = magic.to_vec();
+    header.extend(params[0].to_le_bytes());
+    header.extend(params[1].to_le_bytes());
+    out.write_all(&header).map_err(|e| format!("{}: {}", path, e))?;
+    write_generation(&mut out, path, contigs, sketches)?;
+    out.flush().map_err(|e| format!("{}: {}", path, e))
+}
+
+// The magics differ only in their last byte, the version of the format.
+fn check_magic(path: &str, found: &[u8], magic: &[u8; 8], built_by: &str) -> Result<(), String> {
+    match found {
+        _ if found == magic => Ok(()),
+        _ if found[..7] == magic[..7] => Err(format!("{}: written by another version of hamming-fasta; rebuild it with `{}`", path, built_by)),
+        _ => Err(format!("{}: not written by `{}`", path, built_by)),
+    }
+}
+
+// The parameters of an existing file, which sketches appended to it must be built with.
+pub(crate) fn params(path: &str, magic: &[u8; 8], built_by: &str) -> Result<[u32; 2], String> {
+    let mut header = [0u8; 16];
+    File::open(path).and_then(|mut file| file.read_exact(&mut header)).map_err(|e| format!("{}: {} (build it with `{}`)", path, e, built_by))?;
+    check_magic(path, &header[..8], magic, built_by)?;
+    Ok([u32::from_le_bytes(header[8..12].try_into().unwrap()), u32::from_le_bytes(header[12..16].try_into().unwrap())])
+}
+
+// Sketches the contigs of `fasta` and appends them to the file at `path` as a new generation,
+// leaving the earlier ones as they are. A contig sketched again replaces its earlier entry.
+pub(crate) fn append<F: Fn(&[u8]) -> Vec<u64> + Sync>(fasta: &str, path: &str, contigs: &[(String, usize)], sketch: F) -> Result<(), String> {
+    // written whole once every contig is sketched, so a failed fetch leaves the file intact
+    let mut generation = Vec::new();
+    write_generation(&mut generation, path, contigs, sketch_contigs(fasta, contigs, sketch))?;
+    let mut file = OpenOptions::new().append(true).open(path).map_err(|e| format!("{}: {}", path, e))?;
+    file.write_all(&generation).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub(crate) type Sketches = HashMap<String, (usize, [u8; 16], Vec<u64>)>;
+
+// The parameters and the (length, MD5, values) per contig of a file written by `build` and `append`.
+pub(crate) fn load(path: &str, magic: &[u8; 8], built_by: &str) -> Result<([u32; 2], Sketches), String> {
+    let file = File::open(path).map_err(|e| format!("{}: {} (build it with `{}`)", path, e, built_by))?;
+    let mut input = BufReader::new(file);
+    let read = |input: &mut BufReader<File>, n: usize| {
+        let mut bytes = vec![0u8; n];
+        input.read_exact(&mut bytes).map(|_| bytes).map_err(|e| format!("{}: {}", path, e))
+    };
+    check_magic(path, &read(&mut input, 8)?, magic, built_by)?;
+    let u32_at = |bytes: Vec<u8>| u32::from_le_bytes(bytes.try_into().unwrap());
+    let u64_at = |bytes: Vec<u8>| u64::from_le_bytes(bytes.try_into().unwrap()) as usize;
+    let params = [u32_at(read(&mut input, 4)?), u32_at(read(&mut input, 4)?)];
+    let mut contigs = HashMap::new();
+    // generations follow each other to the end of the file, later entries replacing earlier ones
+    while !input.fill_buf().map_err(|e| format!("{}: {}", path, e))?.is_empty() {
+        let n_contigs = u64_at(read(&mut input, 8)?);
+        for _ in 0..n_contigs {
+            let name_len = u32_at(read(&mut input, 4)?) as usize;
+            let seq_name = String::from_utf8_lossy(&read(&mut input, name_len)?).to_string();
+            let length = u64_at(read(&mut input, 8)?);
+            let md5 = read(&mut input, 16)?.try_into().unwrap();
+            let n_values = u64_at(read(&mut input, 8)?);
+            let values = read(&mut input, n_values * 8)?.chunks_exact(8).map(|value| u64::from_le_bytes(value.try_into().unwrap())).collect();
+            contigs.insert(seq_name, (length, md5, values));
+        }
+    }
+    Ok((params, contigs))
+}
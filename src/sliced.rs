@@ -0,0 +1,202 @@
+// Bit-sliced scanning of many short targets of one length in a single pass, for guide
+// libraries. Each bit of a machine word is one target's lane: for every target position and
+// reference byte a word holds the lanes that count that byte as a mismatch, and the mismatch
+// counts of all lanes are kept as vertical counters, one word per count bit, so a window is
+// compared with 64 targets in a handful of word operations per position. A window is left as
+// soon as every lane is over its limit, which after the PAM is a few positions for most.
+
+use crate::search::{degenerate_matches, pam_matches, timer_overhead, QueryBudget, ScanParams, BUDGET_SAMPLE_INTERVAL, CANCEL_CHECK_INTERVAL};
+use crate::Backend;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+// Targets up to this long are bit-sliced.
+pub const MAX_TARGET_LENGTH: usize = 32;
+const LANES: usize = 64;
+// count bits of the vertical counters: enough for a mismatch limit of MAX_TARGET_LENGTH
+const MAX_COUNT_BITS: usize = 6;
+// Auto slices passes of at least this many targets, and shares one with queries it could seed
+// when at least MIN_AUTO_SHARED would take part. Timed on a random reference, one or two
+// 20-mers were seeded fastest and three or more were faster bit-sliced; Cas9 guides, which
+// cannot be seeded, were faster bit-sliced from a pair on and about as fast alone.
+const MIN_AUTO_TARGETS: usize = 2;
+pub const MIN_AUTO_SHARED: usize = 3;
+
+// What can be bit-sliced: targets up to MAX_TARGET_LENGTH compared by mismatch count, not Tm.
+pub fn possible(params: &ScanParams) -> bool {
+    params.tm.is_none() && params.target.len() <= MAX_TARGET_LENGTH
+}
+
+// Whether `backend` bit-slices a group of targets compared window by window.
+pub fn chosen(backend: Backend, params: &[ScanParams]) -> bool {
+    let eligible = params.iter().all(possible);
+    match backend {
+        Backend::Auto => eligible && params.len() >= MIN_AUTO_TARGETS,
+        Backend::Simd => eligible,
+        Backend::Windows | Backend::Pigeonhole => false,
+    }
+}
+
+// Up to LANES targets scanned together.
+struct Block {
+    first: usize,
+    lanes: u64,
+    // per scored position, the lanes for which each byte is a mismatch
+    mismatches: Vec<(usize, Box<[u64; 256]>)>,
+    // per PAM position, the lanes for which each byte is allowed by none of the PAMs
+    rejects: Vec<(usize, Box<[u64; 256]>)>,
+    // counters start at 2^bits - 1 - limit, so they carry out of the top bit past the limit
+    starts: [u64; MAX_COUNT_BITS],
+    bits: usize,
+}
+
+impl Block {
+    fn new(first: usize, params: &[ScanParams]) -> Block {
+        let target_len = params[0].target.len();
+        let limits: Vec<usize> = params.iter().map(|p| p.max_mismatches.min(target_len)).collect();
+        let bits = (usize::BITS - limits.iter().max().unwrap().leading_zeros()).max(1) as usize;
+        let mut starts = [0u64; MAX_COUNT_BITS];
+        for (lane, limit) in limits.iter().enumerate() {
+            let start = (1 << bits) - 1 - limit;
+            for (b, plane) in starts.iter_mut().enumerate().take(bits) {
+                *plane |= ((start >> b) as u64 & 1) << lane;
+            }
+        }
+        let (mut mismatches, mut rejects) = (Vec::new(), Vec::new());
+        for i in 0..target_len {
+            let (mut mismatch, mut reject) = (Box::new([0u64; 256]), Box::new([0u64; 256]));
+            for (lane, p) in params.iter().enumerate() {
+                let target = p.target.as_bytes();
+                let pam_len = p.pams.first().map_or(0, String::len);
+                let pam_offset = match p.pam_5prime {
+                    true => (i < pam_len).then_some(i),
+                    false => (i + pam_len >= target_len).then(|| i + pam_len - target_len),
+                };
+                for b in 0..=255u8 {
+                    // a byte no PAM allows at its position; all of the PAM is checked on a match
+                    let rejected = pam_offset.is_some_and(|j| !p.pams.iter().any(|pam| pam_matches(&[b], &pam[j..j + 1])));
+                    let mismatched = pam_offset.is_none() && !p.ignored.contains(&i) && b != target[i] && !(p.degenerate && degenerate_matches(b, target[i]));
+                    reject[b as usize] |= (rejected as u64) << lane;
+                    mismatch[b as usize] |= (mismatched as u64) << lane;
+                }
+            }
+            if reject.iter().any(|&lanes| lanes != 0) {
+                rejects.push((i, reject));
+            }
+            if mismatch.iter().any(|&lanes| lanes != 0) {
+                mismatches.push((i, mismatch));
+            }
+        }
+        let lanes = if params.len() == LANES { u64::MAX } else { (1 << params.len()) - 1 };
+        Block { first, lanes, mismatches, rejects, starts, bits }
+    }
+
+    // The lanes of `active` that `window` matches, and the count planes they were found with.
+    fn compare(&self, window: &[u8], active: u64) -> (u64, [u64; MAX_COUNT_BITS]) {
+        let mut alive = active;
+        for (i, reject) in &self.rejects {
+            alive &= !reject[window[*i] as usize];
+            if alive == 0 {
+                return (0, self.starts);
+            }
+        }
+        let mut planes = self.starts;
+        for (i, mismatch) in &self.mismatches {
+            let mut carry = mismatch[window[*i] as usize] & alive;
+            for plane in planes.iter_mut().take(self.bits) {
+                let next = *plane & carry;
+                *plane ^= carry;
+                carry = next;
+            }
+            alive &= !carry;
+            if alive == 0 {
+                break;
+            }
+        }
+        (alive, planes)
+    }
+
+    fn distance(&self, planes: &[u64; MAX_COUNT_BITS], lane: usize) -> usize {
+        let value = |planes: &[u64; MAX_COUNT_BITS]| (0..self.bits).map(|b| ((planes[b] >> lane) as usize & 1) << b).sum::<usize>();
+        value(planes) - value(&self.starts)
+    }
+}
+
+// Same contract and report order as `scan_windows_batch`, for targets that are `possible`.
+pub fn scan_sliced<F: FnMut(usize, usize, &str, usize, Option<f64>)>(sequence: &str, params: &[ScanParams], skip: &[(usize, usize)], mut report: F) {
+    let Some(first) = params.first() else {
+        return;
+    };
+    let target_len = first.target.len();
+    assert!(params.iter().all(|p| p.target.len() == target_len && possible(p)), "bit-sliced targets must be short, of one length and without a Tm");
+    let blocks: Vec<Block> = params.chunks(LANES).enumerate().map(|(i, chunk)| Block::new(i * LANES, chunk)).collect();
+    let step = first.step.max(1);
+    let aligned = |idx: usize| if idx <= first.phase { first.phase } else { idx + (step - (idx - first.phase) % step) % step };
+    let bytes = sequence.as_bytes();
+    let mut skip = skip.iter().peekable();
+    let (mut idx, mut compared) = (aligned(0), 0usize);
+    let mut active: Vec<u64> = blocks.iter().map(|block| block.lanes).collect();
+    while idx + target_len <= bytes.len() {
+        if let Some(&&(start, end)) = skip.peek() {
+            if end <= idx {
+                skip.next();
+                continue;
+            }
+            if start < idx + target_len {
+                idx = aligned(end);
+                continue;
+            }
+        }
+        if compared % CANCEL_CHECK_INTERVAL == 0 {
+            if first.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                return;
+            }
+            for (block, active) in blocks.iter().zip(active.iter_mut()) {
+                for (lane, params) in params[block.first..].iter().take(LANES).enumerate() {
+                    if params.budget.is_some_and(QueryBudget::is_exhausted) {
+                        *active &= !(1 << lane);
+                    }
+                }
+            }
+            if active.iter().all(|&lanes| lanes == 0) {
+                return;
+            }
+        }
+        let timed = compared % BUDGET_SAMPLE_INTERVAL == 0 && params.iter().any(|p| p.budget.is_some());
+        compared += 1;
+        let window = &bytes[idx..idx + target_len];
+        let window_str = std::str::from_utf8(window).unwrap();
+        let at = idx;
+        idx += step;
+        for (block, active) in blocks.iter().zip(active.iter_mut()) {
+            if *active == 0 {
+                continue;
+            }
+            let started = timed.then(Instant::now);
+            let (mut matched, planes) = block.compare(window, *active);
+            while matched != 0 {
+                let lane = matched.trailing_zeros() as usize;
+                matched &= matched - 1;
+                let k = block.first + lane;
+                if params[k].pams.is_empty() || params[k].pams.iter().any(|pam| pam_matches(&window[pam_range(&params[k], target_len)], pam)) {
+                    report(k, at, window_str, block.distance(&planes, lane), None);
+                }
+            }
+            // a comparison is shared by the block, so each of its targets is charged a share
+            if let Some(started) = started {
+                let lanes = active.count_ones();
+                let share = started.elapsed().saturating_sub(timer_overhead()) * BUDGET_SAMPLE_INTERVAL as u32 / lanes;
+                for (lane, params) in params[block.first..].iter().take(LANES).enumerate() {
+                    if *active & (1 << lane) != 0 && params.budget.is_some_and(|budget| budget.charge(share)) {
+                        *active &= !(1 << lane);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pam_range(params: &ScanParams, target_len: usize) -> std::ops::Range<usize> {
+    let pam_len = params.pams[0].len();
+    if params.pam_5prime { 0..pam_len } else { target_len - pam_len..target_len }
+}
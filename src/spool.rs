@@ -0,0 +1,343 @@
+// --sort-memory: rows gathered from every contig and wanted in an order of their own, such as the
+// hits --collapse-by-position groups into sites or the records of --format vcf. They are held in
+// memory up to a limit and past it written out as sorted runs to BGZF-compressed temporary files
+// (in TMPDIR), which are merged back in order at the end, so a low-stringency scan of a large
+// pangenome only ever holds one run at a time.
+
+use crate::NamedHit;
+use hamming_fasta::{pansn, Hit, Strand};
+use rust_htslib::bgzf;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+
+// A row a Spool can hold: what it takes in memory, and its line in a run.
+pub trait Spooled: Sized {
+    fn size(&self) -> usize;
+    // without the line end, which the row must not contain
+    fn line(&self) -> String;
+    fn parse(line: &str) -> Result<Self, String>;
+}
+
+impl Spooled for NamedHit {
+    fn size(&self) -> usize {
+        std::mem::size_of::<NamedHit>() + self.0.len() + self.1.sequence.len()
+    }
+
+    fn line(&self) -> String {
+        let (name, hit) = self;
+        let tm = hit.tm.map_or_else(|| ".".to_string(), |tm| tm.to_string());
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", name, hit.query, hit.seq_name, hit.strand, hit.start, hit.end, hit.sequence, hit.mismatches, tm)
+    }
+
+    fn parse(line: &str) -> Result<NamedHit, String> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let malformed = || format!("malformed spooled hit: {}", line);
+        let number = |i: usize| fields.get(i).and_then(|field| field.parse::<usize>().ok()).ok_or_else(malformed);
+        if fields.len() != 9 {
+            return Err(malformed());
+        }
+        let hit = Hit {
+            query: number(1)?,
+            seq_name: Arc::from(fields[2]),
+            strand: if fields[3] == "+" { Strand::Forward } else { Strand::Reverse },
+            start: number(4)?,
+            end: number(5)?,
+            sequence: fields[6].to_string(),
+            mismatches: number(7)?,
+            tm: if fields[8] == "." { None } else { Some(fields[8].parse().map_err(|_| malformed())?) },
+        };
+        Ok((fields[0].to_string(), hit))
+    }
+}
+
+// --collapse-by-position: sites are grouped by query, contig without its PanSN prefix, strand
+// and start; ties go to the lowest name so the leading hit of a site does not depend on the
+// order contigs finish in.
+fn collapse_key(row: &NamedHit) -> (usize, &str, Strand, usize, &str) {
+    (row.1.query, pansn::parse(&row.0).contig, row.1.strand, row.1.start, &row.0)
+}
+
+pub fn collapse_order(a: &NamedHit, b: &NamedHit) -> Ordering {
+    collapse_key(a).cmp(&collapse_key(b))
+}
+
+// The rows of a spool in order, or the error that broke off reading them.
+pub type Sorted<T> = Box<dyn Iterator<Item = Result<T, String>>>;
+
+// Spools of one process, each with a directory of its own.
+static SPOOLS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Spool<T> {
+    limit: usize,
+    held: usize,
+    rows: Vec<T>,
+    order: fn(&T, &T) -> Ordering,
+    dir: PathBuf,
+    runs: usize,
+}
+
+impl<T: Spooled + 'static> Spool<T> {
+    // Rows sorted by `order`, which keeps rows that compare equal in the order pushed.
+    pub fn new(limit: usize, order: fn(&T, &T) -> Ordering) -> Spool<T> {
+        let n = SPOOLS.fetch_add(1, AtomicOrdering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("hamming-fasta-spool-{}-{}", std::process::id(), n));
+        Spool { limit, held: 0, rows: Vec::new(), order, dir, runs: 0 }
+    }
+
+    pub fn push(&mut self, row: T) -> Result<(), String> {
+        self.held += row.size();
+        self.rows.push(row);
+        if self.held > self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, run: usize) -> PathBuf {
+        self.dir.join(format!("{}.tsv.gz", run))
+    }
+
+    // Writes the rows held as the next sorted run.
+    fn spill(&mut self) -> Result<(), String> {
+        let error = |path: &PathBuf, e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+        if self.runs == 0 {
+            // left over from a crashed run that had the same process id
+            let _ = std::fs::remove_dir_all(&self.dir);
+            std::fs::create_dir_all(&self.dir).map_err(|e| error(&self.dir, &e))?;
+        }
+        let order = self.order;
+        self.rows.sort_by(order);
+        let path = self.path(self.runs);
+        let mut out = BufWriter::new(bgzf::Writer::from_path(&path).map_err(|e| error(&path, &e))?);
+        for row in self.rows.drain(..) {
+            writeln!(out, "{}", row.line()).map_err(|e| error(&path, &e))?;
+        }
+        out.flush().map_err(|e| error(&path, &e))?;
+        self.held = 0;
+        self.runs += 1;
+        Ok(())
+    }
+
+    // Sorted runs written so far.
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    // Every row pushed, in order.
+    pub fn into_sorted(mut self) -> Result<Sorted<T>, String> {
+        let order = self.order;
+        if self.runs == 0 {
+            let mut rows = std::mem::take(&mut self.rows);
+            rows.sort_by(order);
+            return Ok(Box::new(rows.into_iter().map(Ok)));
+        }
+        if !self.rows.is_empty() {
+            self.spill()?;
+        }
+        // the merge removes the runs once it is dropped
+        let dir = std::mem::take(&mut self.dir);
+        let mut merge = Merge { runs: Vec::new(), heads: BinaryHeap::new(), dir };
+        for run in 0..self.runs {
+            let path = merge.dir.join(format!("{}.tsv.gz", run));
+            let reader = bgzf::Reader::from_path(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            merge.runs.push((path, BufReader::new(reader).lines()));
+        }
+        for run in 0..self.runs {
+            if let Some(row) = merge.next_row(run)? {
+                merge.heads.push(Head(row, run, order));
+            }
+        }
+        Ok(Box::new(merge))
+    }
+}
+
+impl<T> Drop for Spool<T> {
+    fn drop(&mut self) {
+        if self.runs > 0 && !self.dir.as_os_str().is_empty() {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+// The next row of a run, ordered so the heap pops the lowest row, and of equal rows the one of
+// the earliest run.
+struct Head<T>(T, usize, fn(&T, &T) -> Ordering);
+
+impl<T> Ord for Head<T> {
+    fn cmp(&self, other: &Head<T>) -> Ordering {
+        (self.2)(&other.0, &self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+impl<T> PartialOrd for Head<T> {
+    fn partial_cmp(&self, other: &Head<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Head<T> {
+    fn eq(&self, other: &Head<T>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Head<T> {}
+
+struct Merge<T> {
+    runs: Vec<(PathBuf, Lines<BufReader<bgzf::Reader>>)>,
+    heads: BinaryHeap<Head<T>>,
+    dir: PathBuf,
+}
+
+impl<T: Spooled> Merge<T> {
+    fn next_row(&mut self, run: usize) -> Result<Option<T>, String> {
+        let (path, lines) = &mut self.runs[run];
+        match lines.next() {
+            None => Ok(None),
+            Some(line) => {
+                let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+                T::parse(&line).map(Some).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+        }
+    }
+}
+
+impl<T: Spooled> Iterator for Merge<T> {
+    type Item = Result<T, String>;
+
+    fn next(&mut self) -> Option<Result<T, String>> {
+        let Head(row, run, order) = self.heads.pop()?;
+        match self.next_row(run) {
+            Ok(Some(next)) => self.heads.push(Head(next, run, order)),
+            Ok(None) => {}
+            Err(error) => {
+                // nothing after a broken run can be trusted to be in order
+                self.heads.clear();
+                return Some(Err(error));
+            }
+        }
+        Some(Ok(row))
+    }
+}
+
+impl<T> Drop for Merge<T> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, query: usize, strand: Strand, start: usize, mismatches: usize) -> NamedHit {
+        let tm = mismatches.is_multiple_of(2).then_some(55.25 + mismatches as f64);
+        (name.to_string(), Hit { query, seq_name: Arc::from(name), strand, start, end: start + 20, sequence: "ACGTACGTACGTACGTACGT".to_string(), mismatches, tm })
+    }
+
+    type Fields = (String, usize, String, Strand, usize, usize, String, usize, Option<f64>);
+
+    fn fields(row: NamedHit) -> Fields {
+        let (name, hit) = row;
+        (name, hit.query, hit.seq_name.to_string(), hit.strand, hit.start, hit.end, hit.sequence, hit.mismatches, hit.tm)
+    }
+
+    // Rows at few distinct sites, so that whole keys tie across runs and only push order tells
+    // them apart.
+    fn rows(seed: u64, n: usize) -> Vec<NamedHit> {
+        let mut state = seed;
+        (0..n)
+            .map(|i| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let r = (state >> 33) as usize;
+                let name = ["HG1#1#chr1", "HG2#1#chr1", "HG1#2#chr2", "chr1"][r % 4];
+                let strand = if (r / 4).is_multiple_of(2) { Strand::Forward } else { Strand::Reverse };
+                row(name, r / 8 % 3, strand, r / 24 % 10, i)
+            })
+            .collect()
+    }
+
+    fn sorted(rows: &[NamedHit]) -> Vec<Fields> {
+        let mut rows = rows.to_vec();
+        rows.sort_by(collapse_order);
+        rows.into_iter().map(fields).collect()
+    }
+
+    fn spooled(rows: &[NamedHit], limit: usize) -> (Spool<NamedHit>, usize) {
+        let mut spool = Spool::new(limit, collapse_order);
+        for row in rows {
+            spool.push(row.clone()).unwrap();
+        }
+        let runs = spool.runs();
+        (spool, runs)
+    }
+
+    #[test]
+    fn an_empty_spool_is_empty() {
+        let spool: Spool<NamedHit> = Spool::new(1, collapse_order);
+        let dir = spool.dir.clone();
+        assert_eq!(spool.into_sorted().unwrap().count(), 0);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn into_sorted_matches_an_in_memory_sort() {
+        let rows = rows(7, 500);
+        let expected = sorted(&rows);
+        for limit in [usize::MAX, 2000, 1] {
+            let (spool, runs) = spooled(&rows, limit);
+            match limit {
+                usize::MAX => assert_eq!(runs, 0),
+                1 => assert_eq!(runs, rows.len()),
+                _ => assert!(runs > 10, "{} runs", runs),
+            }
+            let dir = spool.dir.clone();
+            let sorted: Vec<_> = spool.into_sorted().unwrap().map(|row| fields(row.unwrap())).collect();
+            assert_eq!(sorted, expected, "limit {}", limit);
+            assert!(!dir.exists(), "runs left in {}", dir.display());
+        }
+    }
+
+    #[test]
+    fn spools_of_one_process_keep_apart() {
+        let (a, b) = (rows(1, 200), rows(2, 300));
+        let (spool_a, runs_a) = spooled(&a, 500);
+        let (spool_b, runs_b) = spooled(&b, 500);
+        assert!(runs_a > 1 && runs_b > 1);
+        assert_ne!(spool_a.dir, spool_b.dir);
+        let (mut merged_a, mut merged_b) = (spool_a.into_sorted().unwrap(), spool_b.into_sorted().unwrap());
+        // read in turns, as a clash of their runs would show
+        let (mut got_a, mut got_b) = (Vec::new(), Vec::new());
+        loop {
+            let (next_a, next_b) = (merged_a.next(), merged_b.next());
+            if next_a.is_none() && next_b.is_none() {
+                break;
+            }
+            got_a.extend(next_a.map(|row| fields(row.unwrap())));
+            got_b.extend(next_b.map(|row| fields(row.unwrap())));
+        }
+        assert_eq!(got_a, sorted(&a));
+        assert_eq!(got_b, sorted(&b));
+    }
+
+    #[test]
+    fn a_dropped_spool_removes_its_runs() {
+        let (spool, runs) = spooled(&rows(3, 50), 1);
+        let dir = spool.dir.clone();
+        assert!(runs > 0 && dir.exists());
+        drop(spool);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn a_damaged_run_is_an_error() {
+        let (spool, _) = spooled(&rows(4, 20), 1);
+        std::fs::write(spool.path(1), "not a hit\n").unwrap();
+        let error = spool.into_sorted().and_then(|rows| rows.collect::<Result<Vec<_>, _>>()).err().unwrap();
+        assert!(error.ends_with("1.tsv.gz: malformed spooled hit: not a hit"), "{}", error);
+    }
+}
@@ -0,0 +1,440 @@
+// Expected hit counts under a background model of the scanned reference, used to judge
+// whether an observed number of hits is more than chance would produce.
+
+use crate::search::degenerate_matches;
+
+// Bases A, C, G, T plus a catch-all state for anything the query can never match
+// (N, soft-masked lowercase, IUPAC codes).
+const STATES: usize = 5;
+const OTHER: usize = 4;
+
+fn state(b: u8) -> usize {
+    match b {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => OTHER,
+    }
+}
+
+fn complement_state(s: usize) -> usize {
+    if s == OTHER {
+        OTHER
+    } else {
+        3 - s
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Composition {
+    mono: [u64; STATES],
+    di: [[u64; STATES]; STATES],
+}
+
+impl Composition {
+    pub fn add(&mut self, seq: &[u8]) {
+        self.add_after(None, seq);
+    }
+
+    // Adds `seq` as the continuation of a sequence ending in `prev`, so a sequence read in
+    // pieces gets the same counts as when added whole.
+    pub fn add_after(&mut self, prev: Option<u8>, seq: &[u8]) {
+        let mut prev: Option<usize> = prev.map(state);
+        for &b in seq {
+            let s = state(b);
+            self.mono[s] += 1;
+            if let Some(p) = prev {
+                self.di[p][s] += 1;
+            }
+            prev = Some(s);
+        }
+    }
+
+    pub fn merge(&mut self, other: &Composition) {
+        for i in 0..STATES {
+            self.mono[i] += other.mono[i];
+            for j in 0..STATES {
+                self.di[i][j] += other.di[i][j];
+            }
+        }
+    }
+
+    // Composition of the reverse-complemented reference, i.e. what minus-strand windows see.
+    pub fn reverse_complement(&self) -> Composition {
+        let mut rc = Composition::default();
+        for i in 0..STATES {
+            rc.mono[complement_state(i)] = self.mono[i];
+            for j in 0..STATES {
+                rc.di[complement_state(j)][complement_state(i)] = self.di[i][j];
+            }
+        }
+        rc
+    }
+}
+
+// Highest order of a background fitted from `KmerCounts`.
+pub const MAX_ORDER: usize = 5;
+
+// The state names in k-mers of `KmerCounts::to_json`, N standing for the catch-all.
+const STATE_NAMES: &[u8; STATES] = b"ACGTN";
+
+// Counts of the (order + 1)-mers of a reference, from which a background of that order or
+// lower is fitted; `stats --background-model` writes them out for later searches.
+#[derive(Clone)]
+pub struct KmerCounts {
+    pub order: usize,
+    // by k-mer, its first state most significant in base STATES
+    counts: Vec<u64>,
+}
+
+impl KmerCounts {
+    pub fn new(order: usize) -> KmerCounts {
+        KmerCounts { order, counts: vec![0; STATES.pow(order as u32 + 1)] }
+    }
+
+    // Adds the k-mers of `seq` starting before `owned`, so a sequence read in pieces that
+    // overlap by `order` bases is counted once.
+    pub fn add(&mut self, seq: &[u8], owned: usize) {
+        let k = self.order + 1;
+        let (mut index, mut filled) = (0, 0);
+        for (i, &b) in seq.iter().enumerate() {
+            index = (index * STATES + state(b)) % self.counts.len();
+            filled += 1;
+            if filled >= k && i + 1 - k < owned {
+                self.counts[index] += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &KmerCounts) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+    }
+
+    // Counts of the reverse-complemented reference, i.e. what minus-strand windows see.
+    pub fn reverse_complement(&self) -> KmerCounts {
+        let k = self.order + 1;
+        let mut rc = KmerCounts::new(self.order);
+        for (index, &count) in self.counts.iter().enumerate() {
+            let (mut rest, mut mirrored) = (index, 0);
+            for _ in 0..k {
+                mirrored = mirrored * STATES + complement_state(rest % STATES);
+                rest /= STATES;
+            }
+            rc.counts[mirrored] = count;
+        }
+        rc
+    }
+
+    fn kmer(&self, index: usize) -> String {
+        let k = self.order + 1;
+        (0..k).rev().map(|i| STATE_NAMES[index / STATES.pow(i as u32) % STATES] as char).collect()
+    }
+
+    // The counts of every k-mer, and for convenience the transition probabilities of the
+    // background of the same order, from each context of `order` states.
+    pub fn to_json(&self) -> String {
+        let background = Background::from_counts(self, self.order);
+        let mut json = format!("{{\n  \"order\": {},\n  \"states\": [\"A\", \"C\", \"G\", \"T\", \"N\"],\n  \"counts\": {{", self.order);
+        let counts: Vec<String> = (0..self.counts.len()).map(|index| format!("\"{}\": {}", self.kmer(index), self.counts[index])).collect();
+        json.push_str(&counts.join(", "));
+        json.push_str("},\n  \"transitions\": {");
+        let level = &background.levels[self.order];
+        let transitions: Vec<String> = (0..level.len() / STATES)
+            .map(|context| {
+                let probabilities: Vec<String> = level[context * STATES..(context + 1) * STATES].iter().map(|p| format!("{:.6e}", p)).collect();
+                format!("\"{}\": [{}]", &self.kmer(context * STATES)[..self.order], probabilities.join(", "))
+            })
+            .collect();
+        json.push_str(&transitions.join(", "));
+        json.push_str("}\n}\n");
+        json
+    }
+
+    // Reads back the counts of `to_json`, ignoring everything else.
+    pub fn from_json(json: &str) -> Result<KmerCounts, String> {
+        let field = |name: &str| json.find(&format!("\"{}\":", name)).map(|at| json[at + name.len() + 3..].trim_start()).ok_or_else(|| format!("no {:?} field", name));
+        let order_text = field("order")?;
+        let order: usize = order_text[..order_text.find(|c: char| !c.is_ascii_digit()).unwrap_or(order_text.len())].parse().map_err(|_| "bad order".to_string())?;
+        if order > MAX_ORDER {
+            return Err(format!("order {} is above the highest supported, {}", order, MAX_ORDER));
+        }
+        let counts_text = field("counts")?;
+        let body = counts_text.strip_prefix('{').and_then(|rest| rest.split_once('}')).ok_or_else(|| "counts is not an object".to_string())?.0;
+        let mut counts = KmerCounts::new(order);
+        for entry in body.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (kmer, count) = entry.split_once(':').ok_or_else(|| format!("bad count {:?}", entry.trim()))?;
+            let kmer = kmer.trim().trim_matches('"');
+            let count: u64 = count.trim().parse().map_err(|_| format!("bad count {:?}", entry.trim()))?;
+            let states: Option<Vec<usize>> = kmer.bytes().map(|b| STATE_NAMES.iter().position(|&name| name == b)).collect();
+            match states {
+                Some(states) if states.len() == order + 1 => counts.counts[states.iter().fold(0, |index, &s| index * STATES + s)] = count,
+                _ => return Err(format!("{:?} is not a {}-mer of ACGTN", kmer, order + 1)),
+            }
+        }
+        Ok(counts)
+    }
+}
+
+pub struct Background {
+    // levels[j][c * STATES + s]: probability of state s after the j states of context c, the
+    // latest least significant, for j up to the order of the model
+    levels: Vec<Vec<f64>>,
+}
+
+// The rows of counts by context turned into probabilities; a context never seen gets the
+// distribution of `fallback`.
+fn normalize(counts: &[u64], fallback: &[f64]) -> Vec<f64> {
+    counts
+        .chunks(STATES)
+        .flat_map(|row| {
+            let total: u64 = row.iter().sum();
+            let row: Vec<f64> = if total > 0 { row.iter().map(|&c| c as f64 / total as f64).collect() } else { fallback.to_vec() };
+            row
+        })
+        .collect()
+}
+
+impl Background {
+    // `order` 0 treats positions independently, 1 conditions each base on its predecessor.
+    pub fn fit(composition: &Composition, order: usize) -> Background {
+        let initial = normalize(&composition.mono, &[0.0; STATES]);
+        let mut levels = vec![initial.clone()];
+        if order >= 1 {
+            let di: Vec<u64> = composition.di.iter().flatten().copied().collect();
+            levels.push(normalize(&di, &initial));
+        }
+        Background { levels }
+    }
+
+    // A background of `order` at most that of `counts`. The lower levels, used for the first
+    // positions of a window, come from the counts of the k-mers' last bases.
+    pub fn from_counts(counts: &KmerCounts, order: usize) -> Background {
+        let mut levels: Vec<Vec<f64>> = Vec::new();
+        for j in 0..=order.min(counts.order) {
+            let contexts = STATES.pow(j as u32 + 1);
+            let mut suffixes = vec![0u64; contexts];
+            for (index, &count) in counts.counts.iter().enumerate() {
+                suffixes[index % contexts] += count;
+            }
+            let fallback = levels.first().cloned().unwrap_or_else(|| vec![0.0; STATES]);
+            levels.push(normalize(&suffixes, &fallback));
+        }
+        Background { levels }
+    }
+
+    pub fn order(&self) -> usize {
+        self.levels.len() - 1
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum PatternPos {
+    // scored position: a different base costs one mismatch
+    Scored(u8),
+    // scored position of a degenerate query: a base outside the IUPAC code costs one mismatch
+    Degenerate(u8),
+    // must match exactly or the window is rejected (e.g. the GG of a PAM)
+    Required(u8),
+    // matches anything at no cost
+    Free,
+}
+
+// Probability that a random window drawn from `bg` has exactly k mismatches against
+// `pattern`, for k in 0..=max_mismatches.
+pub fn mismatch_distribution(bg: &Background, pattern: &[PatternPos], max_mismatches: usize) -> Vec<f64> {
+    let width = max_mismatches + 1;
+    let order = bg.order();
+    // dp[c * width + k]: probability of the prefix ending in the states of context c, the last
+    // `order` of them at most, with k mismatches
+    let mut dp = vec![1.0];
+    dp.resize(width, 0.0);
+    for (i, pos) in pattern.iter().enumerate() {
+        let level = &bg.levels[i.min(order)];
+        let contexts = STATES.pow(i.min(order) as u32);
+        let next_contexts = STATES.pow((i + 1).min(order) as u32);
+        let mut next = vec![0.0; next_contexts * width];
+        for s in 0..STATES {
+            let cost = |p: &PatternPos| -> Option<usize> {
+                match *p {
+                    PatternPos::Scored(b) => Some(usize::from(state(b) != s)),
+                    PatternPos::Degenerate(b) => Some(usize::from(s == OTHER || !degenerate_matches(b"ACGT"[s], b))),
+                    PatternPos::Required(b) => (state(b) == s).then_some(0),
+                    PatternPos::Free => Some(0),
+                }
+            };
+            let Some(cost) = cost(pos) else { continue };
+            for c in 0..contexts {
+                let p_next = level[c * STATES + s];
+                if p_next == 0.0 {
+                    continue;
+                }
+                let next_c = (c * STATES + s) % next_contexts;
+                for k in 0..width - cost {
+                    next[next_c * width + k + cost] += dp[c * width + k] * p_next;
+                }
+            }
+        }
+        dp = next;
+    }
+    (0..width).map(|k| dp.chunks(width).map(|row| row[k]).sum()).collect()
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    // Lanczos approximation
+    const G: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut ser = 1.000000000190015;
+    for (j, g) in G.iter().enumerate() {
+        ser += g / (x + 1.0 + j as f64);
+    }
+    -tmp + (2.5066282746310005 * ser / x).ln()
+}
+
+// Regularized lower incomplete gamma function P(a, x).
+fn gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x < a + 1.0 {
+        let mut sum = 1.0 / a;
+        let mut term = sum;
+        let mut n = a;
+        for _ in 0..10_000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (sum.ln() - x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // continued fraction for the upper tail
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..10_000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        1.0 - (-x + a * x.ln() - ln_gamma(a)).exp() * h
+    }
+}
+
+// P(X >= observed) for X ~ Poisson(expected).
+pub fn poisson_upper_tail(expected: f64, observed: u64) -> f64 {
+    if observed == 0 {
+        1.0
+    } else if expected <= 0.0 {
+        0.0
+    } else {
+        gamma_p(observed as f64, expected).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+        (actual - expected).abs() <= tolerance * expected.abs().max(1e-300)
+    }
+
+    fn binomial(n: usize, k: usize, p: f64) -> f64 {
+        let choose = (ln_gamma(n as f64 + 1.0) - ln_gamma(k as f64 + 1.0) - ln_gamma((n - k) as f64 + 1.0)).exp();
+        choose * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32)
+    }
+
+    #[test]
+    fn ln_gamma_at_integers_is_ln_factorial() {
+        let mut factorial = 1.0f64;
+        for n in 1..=30 {
+            assert!((ln_gamma(n as f64) - factorial.ln()).abs() < 1e-10 * factorial.ln().max(1.0), "ln Γ({})", n);
+            factorial *= n as f64;
+        }
+        assert!(close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-9));
+    }
+
+    #[test]
+    fn gamma_p_of_small_shapes_has_closed_forms() {
+        // P(1, x) = 1 - e^-x and P(2, x) = 1 - e^-x (1 + x), on both sides of x = a + 1
+        for x in [0.01, 0.5, 1.5, 2.5, 10.0, 40.0] {
+            assert!(close(gamma_p(1.0, x), 1.0 - (-x).exp(), 1e-9), "P(1, {})", x);
+            assert!(close(gamma_p(2.0, x), 1.0 - (-x).exp() * (1.0 + x), 1e-9), "P(2, {})", x);
+        }
+        assert_eq!(gamma_p(3.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn poisson_tails_match_summed_probabilities() {
+        // (λ, k, P(X >= k)) summed term by term
+        let tails = [
+            (0.5, 1, 0.39346934028736663),
+            (2.0, 3, 0.3233235838169365),
+            (0.01, 2, 4.966791334026594e-05),
+            (1e-6, 1, 9.999995000001678e-07),
+            (50.0, 10, 0.999999999998738),
+            (100.0, 100, 0.5132987982791705),
+            (100.0, 130, 0.0022820930543169878),
+            (1000.0, 1100, 0.0009626304058662804),
+        ];
+        for (expected, observed, tail) in tails {
+            assert!(close(poisson_upper_tail(expected, observed), tail, 1e-7), "P(X >= {}) at λ = {}", observed, expected);
+        }
+        assert_eq!(poisson_upper_tail(3.0, 0), 1.0);
+        assert_eq!(poisson_upper_tail(0.0, 2), 0.0);
+    }
+
+    #[test]
+    fn uniform_background_gives_the_binomial() {
+        // a de Bruijn sequence holds every dinucleotide once; continuing its own end, its repeats
+        // count each base and each pair equally
+        let mut uniform = Composition::default();
+        uniform.add_after(Some(b'T'), &b"AACAGATCCGCTGGTT".repeat(50));
+        for order in [0, 1] {
+            let bg = Background::fit(&uniform, order);
+            let pattern: Vec<PatternPos> = b"GATTACAGATTACAGATTAC".iter().map(|&b| PatternPos::Scored(b)).collect();
+            let distribution = mismatch_distribution(&bg, &pattern, 6);
+            for (k, &p) in distribution.iter().enumerate() {
+                assert!(close(p, binomial(20, k, 0.75), 1e-6), "order {}, k = {}: {} vs {}", order, k, p, binomial(20, k, 0.75));
+            }
+            // a required base only passes a quarter of windows; a free one passes them all
+            let mut pam = pattern.clone();
+            pam.extend([PatternPos::Free, PatternPos::Required(b'G'), PatternPos::Required(b'G')]);
+            for (k, &p) in mismatch_distribution(&bg, &pam, 6).iter().enumerate() {
+                assert!(close(p, binomial(20, k, 0.75) / 16.0, 1e-6), "order {}, k = {} with a PAM", order, k);
+            }
+            // R matches A or G, so mismatches half the windows
+            let degenerate = vec![PatternPos::Degenerate(b'R'); 8];
+            for (k, &p) in mismatch_distribution(&bg, &degenerate, 8).iter().enumerate() {
+                assert!(close(p, binomial(8, k, 0.5), 1e-6), "order {}, k = {} of RRRRRRRR", order, k);
+            }
+        }
+    }
+}
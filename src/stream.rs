@@ -0,0 +1,81 @@
+// `--fasta -`: FASTA records read from a stream without an index, such as a decompressed
+// assembly piped to stdin. A record is named by its header up to the first whitespace, and its
+// sequence is its lines joined.
+
+use std::io::BufRead;
+
+pub struct Records<R: BufRead> {
+    input: R,
+    // the header of the next record, read at the end of the one before it
+    header: Option<String>,
+    line: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(input: R) -> Records<R> {
+        Records { input, header: None, line: 0 }
+    }
+
+    // The next line without its line ending, or None at the end of the stream.
+    fn read_line(&mut self) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                self.line += 1;
+                Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+            }
+            Err(e) => Err(format!("line {}: {}", self.line + 1, e)),
+        }
+    }
+
+    // The next records up to the first that brings them to at least `bases` bases, fewer at the
+    // end of the stream and none after it.
+    pub fn batch(&mut self, bases: usize) -> Result<Vec<(String, String)>, String> {
+        let (mut batch, mut held) = (Vec::new(), 0);
+        while held < bases {
+            let Some(record) = self.next() else {
+                break;
+            };
+            let record = record?;
+            held += record.1.len();
+            batch.push(record);
+        }
+        Ok(batch)
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<(String, String), String>;
+
+    fn next(&mut self) -> Option<Result<(String, String), String>> {
+        let header = match self.header.take() {
+            Some(header) => header,
+            None => loop {
+                match self.read_line() {
+                    Ok(Some(line)) if line.starts_with('>') => break line,
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(_)) => return Some(Err(format!("line {}: sequence before the first header", self.line))),
+                    Ok(None) => return None,
+                    Err(e) => return Some(Err(e)),
+                }
+            },
+        };
+        let Some(name) = header[1..].split_whitespace().next().map(str::to_string) else {
+            return Some(Err(format!("line {}: header without a name", self.line)));
+        };
+        let mut seq = String::new();
+        loop {
+            match self.read_line() {
+                Ok(Some(line)) if line.starts_with('>') => {
+                    self.header = Some(line);
+                    break;
+                }
+                Ok(Some(line)) => seq.push_str(line.trim_end()),
+                Ok(None) => break,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(Ok((name, seq)))
+    }
+}
@@ -0,0 +1,130 @@
+// `subset`: named contigs and regions of a FASTA, such as a pangenome, copied into a small
+// indexed FASTA for test fixtures. PanSN names are kept, and a region is named the way samtools
+// names it, `name:start-end`, so that the sample and haplotype of its contig still parse.
+
+use hamming_fasta::{pansn, Alphabet, SearchConfig, Searcher};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+// Bases fetched at a time from a contig, rounded down to whole lines.
+const PIECE_LENGTH: usize = 8 << 20;
+
+#[derive(clap::Args, Debug)]
+pub struct SubsetArgs {
+    /// Path to the FASTA file
+    #[arg(short, long)]
+    fasta: String,
+
+    /// FASTA file to write, with its .fai next to it
+    #[arg(short, long)]
+    output: String,
+
+    /// Copy this contig whole; may be repeated
+    #[arg(long, required_unless_present_any = ["sample", "region", "bed"])]
+    contig: Vec<String>,
+
+    /// Copy every contig of this PanSN sample, or of one of its haplotypes given as sample#haplotype; may be repeated
+    #[arg(long)]
+    sample: Vec<String>,
+
+    /// Copy this region (chr:start-end, 1-based inclusive); may be repeated
+    #[arg(long)]
+    region: Vec<String>,
+
+    /// Copy each interval of this BED file, 0-based half-open
+    #[arg(long)]
+    bed: Option<String>,
+
+    /// Bases per line of the written FASTA
+    #[arg(long, default_value_t = 60, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    line_width: usize,
+}
+
+// Whether `seq_name` is a contig of `sample`, given as a sample or as sample#haplotype.
+fn of_sample(sample: &str, seq_name: &str) -> bool {
+    let name = pansn::parse(seq_name);
+    name.sample.is_some() && (name.sample == Some(sample) || name.haplotype_id() == sample)
+}
+
+// The intervals of a BED file as (contig, start, end).
+fn load_bed(path: &str) -> Result<Vec<(String, usize, usize)>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut intervals = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("{}: {}", path, e))?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields[0].is_empty() || fields[0].starts_with('#') || fields[0] == "track" || fields[0] == "browser" {
+            continue;
+        }
+        let parsed = match fields[..] {
+            [seq_name, start, end, ..] => start.parse::<usize>().ok().zip(end.parse::<usize>().ok()).map(|(start, end)| (seq_name.to_string(), start, end)),
+            _ => None,
+        };
+        intervals.push(parsed.ok_or_else(|| format!("{}: malformed BED line: {}", path, line))?);
+    }
+    Ok(intervals)
+}
+
+pub fn run(args: &SubsetArgs) {
+    let fail = |error: String| -> ! {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    };
+    if args.output == args.fasta {
+        fail(format!("{} would be overwritten by its own subset", args.fasta));
+    }
+    // read as protein, which leaves U as it is: the bases are copied, not searched
+    let searcher = Searcher::new(&args.fasta, SearchConfig { alphabet: Alphabet::Protein, ..SearchConfig::default() }).unwrap_or_else(|e| fail(e));
+    if let Some(contig) = args.contig.iter().find(|contig| searcher.length(contig).is_none()) {
+        fail(format!("{}: no sequence {}", args.fasta, contig));
+    }
+    if let Some(sample) = args.sample.iter().find(|sample| !searcher.contigs().iter().any(|(seq_name, _)| of_sample(sample, seq_name))) {
+        fail(format!("{}: no contigs of sample {}", args.fasta, sample));
+    }
+    // (name, contig, start, end): whole contigs in index order, then regions as given
+    let mut records: Vec<(String, String, usize, usize)> = searcher
+        .contigs()
+        .iter()
+        .filter(|(seq_name, _)| args.contig.contains(seq_name) || args.sample.iter().any(|sample| of_sample(sample, seq_name)))
+        .map(|(seq_name, length)| (seq_name.clone(), seq_name.clone(), 0, *length))
+        .collect();
+    let mut regions: Vec<(String, usize, usize)> = args.region.iter().map(|region| crate::parse_region(region).unwrap_or_else(|e| fail(e))).collect();
+    if let Some(bed) = &args.bed {
+        regions.extend(load_bed(bed).unwrap_or_else(|e| fail(e)));
+    }
+    for (seq_name, start, end) in regions {
+        let length = searcher.length(&seq_name).unwrap_or_else(|| fail(format!("{}: no sequence {}", args.fasta, seq_name)));
+        if start >= end || end > length {
+            fail(format!("{}:{}-{} is empty or lies beyond the end of {} ({} bp)", seq_name, start + 1, end, seq_name, length));
+        }
+        records.push((format!("{}:{}-{}", seq_name, start + 1, end), seq_name, start, end));
+    }
+    // a contig or region selected twice is written once
+    let mut named = HashSet::new();
+    records.retain(|(name, ..)| named.insert(name.clone()));
+
+    let file = File::create(&args.output).unwrap_or_else(|e| fail(format!("{}: {}", args.output, e)));
+    let mut out = BufWriter::new(file);
+    let (mut fai, mut offset, mut bases) = (String::new(), 0, 0);
+    let piece_length = (PIECE_LENGTH / args.line_width).max(1) * args.line_width;
+    for (name, seq_name, start, end) in &records {
+        let header = format!(">{}\n", name);
+        out.write_all(header.as_bytes()).unwrap();
+        offset += header.len();
+        fai.push_str(&format!("{}\t{}\t{}\t{}\t{}\n", name, end - start, offset, args.line_width, args.line_width + 1));
+        for piece_start in (*start..*end).step_by(piece_length) {
+            let piece = searcher.fetch(seq_name, piece_start, (piece_start + piece_length).min(*end)).unwrap_or_else(|e| fail(e));
+            for line in piece.as_bytes().chunks(args.line_width) {
+                out.write_all(line).unwrap();
+                out.write_all(b"\n").unwrap();
+                offset += line.len() + 1;
+            }
+        }
+        bases += end - start;
+    }
+    out.flush().unwrap_or_else(|e| fail(format!("{}: {}", args.output, e)));
+    let fai_path = format!("{}.fai", args.output);
+    std::fs::write(&fai_path, fai).unwrap_or_else(|e| fail(format!("{}: {}", fai_path, e)));
+    eprintln!("wrote {}: {} sequences, {} bases", args.output, records.len(), bases);
+}
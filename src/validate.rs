@@ -0,0 +1,136 @@
+// Cross-checks a FASTA against its .fai, since the search trusts the index for every coordinate.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+struct Record {
+    name: String,
+    length: usize,
+    offset: usize,
+    line_bases: usize,
+    line_width: usize,
+}
+
+// Records as samtools faidx would index them, with any layout problems that faidx rejects.
+fn scan_fasta(path: &str, problems: &mut Vec<String>) -> Result<Vec<Record>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut reader = BufReader::new(file);
+    let mut records: Vec<Record> = Vec::new();
+    // the first short line of the current record; any sequence line after it is misplaced
+    let mut short_line: Option<usize> = None;
+    // one layout problem per record is enough to make its index unusable
+    let mut misformatted = false;
+    let (mut offset, mut line_number) = (0, 0);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line).map_err(|e| format!("{}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        offset += read;
+        line_number += 1;
+        if line.first() == Some(&b'>') {
+            let header = String::from_utf8_lossy(&line[1..]);
+            let name = header.split_whitespace().next().unwrap_or_default().to_string();
+            records.push(Record { name, length: 0, offset, line_bases: 0, line_width: 0 });
+            short_line = None;
+            misformatted = false;
+            continue;
+        }
+        let record = match records.last_mut() {
+            Some(record) => record,
+            None => {
+                problems.push(format!("line {}: sequence before the first header", line_number));
+                continue;
+            }
+        };
+        let bases = line.iter().take_while(|&&b| b != b'\n' && b != b'\r').count();
+        record.length += bases;
+        if misformatted {
+            continue;
+        }
+        if record.line_bases == 0 && bases > 0 {
+            record.line_bases = bases;
+            record.line_width = read;
+        }
+        let layout = if let Some(short) = short_line.filter(|_| bases > 0) {
+            Some(format!("line {} follows the shorter line {}", line_number, short))
+        } else if bases > record.line_bases {
+            Some(format!("line {} has {} bases, the first line {}", line_number, bases, record.line_bases))
+        } else if line.ends_with(b"\n") && read - bases != record.line_width - record.line_bases {
+            Some(format!("line {} ends differently from the first line", line_number))
+        } else {
+            None
+        };
+        if let Some(layout) = layout {
+            problems.push(format!("{}: {}; all lines but the last must have the same width", record.name, layout));
+            misformatted = true;
+        }
+        if bases < record.line_bases || bases == 0 {
+            short_line.get_or_insert(line_number);
+        }
+    }
+    Ok(records)
+}
+
+// Every inconsistency between `path` and `path.fai`: names, duplicate names, lengths, offsets
+// and line widths. An empty list means the index can be trusted.
+pub fn validate_fai(path: &str) -> Result<Vec<String>, String> {
+    let fai_path = format!("{}.fai", path);
+    let file = File::open(&fai_path).map_err(|e| format!("{}: {}", fai_path, e))?;
+    let mut problems = Vec::new();
+    let mut indexed = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        let numbers: Option<Vec<usize>> = fields.iter().skip(1).take(4).map(|f| f.trim().parse().ok()).collect();
+        let numbers = match numbers {
+            Some(numbers) if numbers.len() == 4 => numbers,
+            _ => {
+                problems.push(format!("{} line {}: expected name, length, offset, line bases and line width", fai_path, n + 1));
+                continue;
+            }
+        };
+        if let Some(first) = seen.insert(fields[0].to_string(), n + 1) {
+            problems.push(format!("{}: indexed twice, at .fai lines {} and {}", fields[0], first, n + 1));
+        }
+        indexed.push(Record { name: fields[0].to_string(), length: numbers[0], offset: numbers[1], line_bases: numbers[2], line_width: numbers[3] });
+    }
+
+    let records = scan_fasta(path, &mut problems)?;
+    let in_fasta: HashMap<&str, &Record> = records.iter().map(|r| (r.name.as_str(), r)).collect();
+    let mut fasta_seen = HashSet::new();
+    for record in &records {
+        if !fasta_seen.insert(record.name.as_str()) {
+            problems.push(format!("{}: appears twice in the FASTA", record.name));
+        }
+        if !seen.contains_key(&record.name) {
+            problems.push(format!("{}: in the FASTA but not in the index", record.name));
+        }
+    }
+    for entry in &indexed {
+        let record = match in_fasta.get(entry.name.as_str()) {
+            Some(record) => record,
+            None => {
+                problems.push(format!("{}: in the index but not in the FASTA", entry.name));
+                continue;
+            }
+        };
+        let mut check = |field: &str, indexed: usize, actual: usize| {
+            if indexed != actual {
+                problems.push(format!("{}: index {} is {}, the FASTA has {}", entry.name, field, indexed, actual));
+            }
+        };
+        check("length", entry.length, record.length);
+        check("offset", entry.offset, record.offset);
+        // an empty record has no lines to measure
+        if record.length > 0 {
+            check("line bases", entry.line_bases, record.line_bases);
+            check("line width", entry.line_width, record.line_width);
+        }
+    }
+    Ok(problems)
+}
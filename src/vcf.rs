@@ -0,0 +1,183 @@
+// `--format vcf`: hits as VCF records, for tools that only read VCF. Each hit is a symbolic
+// <TARGET> allele at its first base that spans the window through INFO END, and every column
+// of the TSV output the record does not already carry becomes an INFO field of its own, so
+// the records hold what the rows would. Contigs finish in any order and a contig's minus-strand
+// hits come last, so records go through a `Spool` and are written sorted by contig and position
+// once the search is done, as `bcftools index` and tabix require.
+
+use crate::spool::{Spool, Spooled};
+use hamming_fasta::{get_reverse_complement, Hit, Strand};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+pub struct Vcf {
+    // per TSV column, its INFO key, or None for those in the record's own fields
+    keys: Vec<Option<String>>,
+    hit_id: Option<usize>,
+    // the TSV has a leading query column, otherwise QUERY comes from the query names
+    named: bool,
+    // the header's contigs, by output name, and the position of each
+    contigs: Vec<(String, usize)>,
+    order: HashMap<String, usize>,
+    records: Mutex<Spool<Record>>,
+}
+
+// A record, and where it sorts: the position of its contig among the header's, then its POS.
+struct Record {
+    contig: usize,
+    pos: usize,
+    text: String,
+}
+
+impl Spooled for Record {
+    fn size(&self) -> usize {
+        std::mem::size_of::<Record>() + self.text.len()
+    }
+
+    fn line(&self) -> String {
+        format!("{}\t{}\t{}", self.contig, self.pos, self.text)
+    }
+
+    fn parse(line: &str) -> Result<Record, String> {
+        let mut fields = line.splitn(3, '\t');
+        let mut number = || fields.next().and_then(|field| field.parse().ok()).ok_or_else(|| format!("malformed spooled record: {}", line));
+        let (contig, pos) = (number()?, number()?);
+        let text = fields.next().ok_or_else(|| format!("malformed spooled record: {}", line))?.to_string();
+        Ok(Record { contig, pos, text })
+    }
+}
+
+// Records at one position are ordered by their text, so runs give the same file.
+fn record_order(a: &Record, b: &Record) -> Ordering {
+    (a.contig, a.pos, &a.text).cmp(&(b.contig, b.pos, &b.text))
+}
+
+// An INFO key for a column name, with what VCF keys may not hold replaced by `_`.
+fn info_key(column: &str) -> String {
+    column.to_ascii_uppercase().chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' }).collect()
+}
+
+// VCF percent-encoding of the characters that separate fields, lists and keys.
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '%' | ':' | ';' | '=' | ' ' | '\t' | '\r' | '\n' => write!(escaped, "%{:02X}", c as u32).unwrap(),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Vcf {
+    // Records of the TSV `header`'s rows on `contigs`, named and ordered as in the header, with
+    // up to `limit` bytes of them held in memory.
+    pub fn new(header: &str, contigs: Vec<(String, usize)>, limit: usize) -> Vcf {
+        let columns: Vec<&str> = header.split('\t').collect();
+        let keys = columns.iter().map(|&column| (!matches!(column, "seq_name" | "start" | "end" | "hit_id")).then(|| info_key(column))).collect();
+        let order = contigs.iter().enumerate().map(|(i, (name, _))| (name.clone(), i)).collect();
+        Vcf { keys, hit_id: columns.iter().position(|&column| column == "hit_id"), named: columns[0] == "query", contigs, order, records: Mutex::new(Spool::new(limit, record_order)) }
+    }
+
+    pub fn write_header<W: std::io::Write>(&self, out: &mut W, header: &str, fasta: &str) -> std::io::Result<()> {
+        writeln!(out, "##fileformat=VCFv4.3")?;
+        writeln!(out, "##source=hamming-fasta {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "##reference={}", fasta)?;
+        for (name, length) in &self.contigs {
+            writeln!(out, "##contig=<ID={},length={}>", name, length)?;
+        }
+        writeln!(out, "##ALT=<ID=TARGET,Description=\"Window within the mismatch limit of a query\">")?;
+        writeln!(out, "##INFO=<ID=END,Number=1,Type=Integer,Description=\"Last base of the window\">")?;
+        if !self.named {
+            writeln!(out, "##INFO=<ID=QUERY,Number=1,Type=String,Description=\"Query\">")?;
+        }
+        for (column, key) in header.split('\t').zip(&self.keys) {
+            if let Some(key) = key {
+                let (number, kind) = match column {
+                    "query" | "strand" | "sequence" | "protospacer" | "pam" => ("1", "String"),
+                    "seed_mismatches" => ("1", "Integer"),
+                    _ if column.starts_with("mismatches") => ("1", "Integer"),
+                    "tm" | "score" | "mit" => ("1", "Float"),
+                    _ => (".", "String"),
+                };
+                writeln!(out, "##INFO=<ID={},Number={},Type={},Description=\"The {} column of the TSV output\">", key, number, kind, column)?;
+            }
+        }
+        writeln!(out, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")
+    }
+
+    // Holds the record of a hit on `seq_name` from its TSV `row` for `finish`.
+    pub fn add_record(&self, seq_name: &str, query_name: &str, hit: &Hit, row: &str) -> Result<(), String> {
+        let fields: Vec<&str> = row.trim_end_matches('\n').split('\t').collect();
+        // the base of the + strand the window starts with
+        let first = match hit.strand {
+            Strand::Forward => hit.sequence[..1].to_string(),
+            Strand::Reverse => get_reverse_complement(&hit.sequence[hit.sequence.len() - 1..]),
+        };
+        let reference = match first.to_ascii_uppercase().as_str() {
+            base @ ("A" | "C" | "G" | "T") => base.to_string(),
+            _ => "N".to_string(),
+        };
+        let mut info = format!("END={}", hit.end);
+        if !self.named {
+            write!(info, ";QUERY={}", escape(query_name)).unwrap();
+        }
+        for (field, key) in fields.iter().zip(&self.keys) {
+            if let (Some(key), false) = (key, *field == ".") {
+                write!(info, ";{}={}", key, escape(field)).unwrap();
+            }
+        }
+        let id = self.hit_id.map_or(".", |i| fields[i]);
+        let text = format!("{}\t{}\t{}\t{}\t<TARGET>\t.\tPASS\t{}", seq_name, hit.start + 1, id, reference, info);
+        // a name missing from the header sorts last
+        let contig = self.order.get(seq_name).copied().unwrap_or(usize::MAX);
+        self.records.lock().unwrap().push(Record { contig, pos: hit.start, text })
+    }
+
+    // Writes the records, by contig in header order and then position.
+    pub fn finish<W: std::io::Write>(self, out: &mut W) -> Result<(), String> {
+        for record in self.records.into_inner().unwrap().into_sorted()? {
+            writeln!(out, "{}", record?.text).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn hit(seq_name: &str, strand: Strand, start: usize) -> Hit {
+        Hit { query: 0, seq_name: Arc::from(seq_name), strand, start, end: start + 4, sequence: "ACGT".to_string(), mismatches: 1, tm: None }
+    }
+
+    #[test]
+    fn records_are_written_by_contig_and_position() {
+        let header = "seq_name\tstrand\tstart\tend\tsequence\tmismatches";
+        // held in memory, and spilled as a run per record
+        for limit in [usize::MAX, 1] {
+            let vcf = Vcf::new(header, vec![("chr2".to_string(), 100), ("chr1".to_string(), 100)], limit);
+            // as a search delivers them: the shorter contig first, each with its minus-strand hits
+            // last and right to left
+            for hit in [hit("chr1", Strand::Forward, 10), hit("chr1", Strand::Forward, 40), hit("chr1", Strand::Reverse, 30), hit("chr1", Strand::Reverse, 5), hit("chr2", Strand::Forward, 50), hit("chr2", Strand::Reverse, 20)] {
+                let row = format!("{}\t{}\t{}\t{}\tACGT\t1\n", hit.seq_name, hit.strand, hit.start, hit.end);
+                vcf.add_record(&hit.seq_name.clone(), "q", &hit, &row).unwrap();
+            }
+            let mut out = Vec::new();
+            vcf.finish(&mut out).unwrap();
+            let positions: Vec<(String, String)> = String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let fields: Vec<&str> = line.split('\t').collect();
+                    (fields[0].to_string(), fields[1].to_string())
+                })
+                .collect();
+            let expected = [("chr2", "21"), ("chr2", "51"), ("chr1", "6"), ("chr1", "11"), ("chr1", "31"), ("chr1", "41")];
+            assert_eq!(positions, expected.map(|(contig, pos)| (contig.to_string(), pos.to_string())), "limit {}", limit);
+        }
+    }
+}